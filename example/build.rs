@@ -14,5 +14,5 @@ fn main() {
         Path::new("src/bin/singlefile.rs"),
     );
     bundler.crate_name("example");
-    bundler.run();
+    bundler.run().unwrap();
 }
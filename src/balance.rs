@@ -0,0 +1,178 @@
+/*!
+Check that the delimiters of a source file are balanced, to find files
+that are being edited, like a `{` without its `}` yet, before they make
+the whole bundle wrong.
+*/
+
+use std::fmt;
+
+use super::lexer::LexState;
+use super::lexer::Lexer;
+
+/// What is wrong with the delimiters of a file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Imbalance {
+    /// A delimiter is never closed.
+    Unclosed(char),
+    /// A closing delimiter has nothing to close.
+    Unexpected(char),
+    /// A closing delimiter closes another kind, like `(]`.
+    Mismatched { open: char, close: char },
+    /// A string literal or block comment never ends.
+    Unterminated(&'static str),
+}
+
+impl fmt::Display for Imbalance {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Imbalance::Unclosed(open) => write!(f, "unclosed `{}`", open),
+            Imbalance::Unexpected(close) => write!(f, "unexpected `{}`", close),
+            Imbalance::Mismatched { open, close } => {
+                write!(f, "`{}` closes `{}`", close, open)
+            }
+            Imbalance::Unterminated(what) => write!(f, "unterminated {}", what),
+        }
+    }
+}
+
+/// Finds the first problem with the delimiters of `source`, with the
+/// line it's at, starting at 1. An unclosed delimiter is reported at
+/// the line that opens it.
+pub fn check(source: &str) -> Option<(usize, Imbalance)> {
+    let mut lexer = Lexer::new();
+    // Delimiters that are open, with their line:
+    let mut open: Vec<(char, usize)> = Vec::new();
+    // Line where the last literal or comment that spans lines starts:
+    let mut literal_start = 1;
+    for (i, line) in source.lines().enumerate() {
+        let lineno = i + 1;
+        let (scan, ranges) = lexer.scan_code(line);
+        if scan.starts_in_code() && !scan.ends_in_code() {
+            literal_start = lineno;
+        }
+        for range in ranges {
+            for c in line[range].chars() {
+                let expected = match c {
+                    '{' | '(' | '[' => {
+                        open.push((c, lineno));
+                        continue;
+                    }
+                    '}' => '{',
+                    ')' => '(',
+                    ']' => '[',
+                    _ => continue,
+                };
+                match open.pop() {
+                    None => return Some((lineno, Imbalance::Unexpected(c))),
+                    Some((o, _)) if o != expected => {
+                        return Some((lineno, Imbalance::Mismatched { open: o, close: c }))
+                    }
+                    Some(_) => {}
+                }
+            }
+        }
+    }
+    match lexer.scan("").end {
+        LexState::Code => {}
+        LexState::BlockComment(_) => {
+            return Some((literal_start, Imbalance::Unterminated("block comment")))
+        }
+        LexState::Str | LexState::RawStr(_) => {
+            return Some((literal_start, Imbalance::Unterminated("string literal")))
+        }
+    }
+    open.pop()
+        .map(|(c, lineno)| (lineno, Imbalance::Unclosed(c)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_each_kind_of_problem() {
+        assert_eq!(check("fn f() {\n    g(1);\n}\n"), None);
+        assert_eq!(
+            check("fn f() {\n    g(1);\n"),
+            Some((1, Imbalance::Unclosed('{')))
+        );
+        assert_eq!(
+            check("fn f() {}\n}\n"),
+            Some((2, Imbalance::Unexpected('}')))
+        );
+        assert_eq!(
+            check("fn f() {\n    g(1];\n}\n"),
+            Some((
+                2,
+                Imbalance::Mismatched {
+                    open: '(',
+                    close: ']'
+                }
+            ))
+        );
+        assert_eq!(
+            check("fn f() {}\n/* unfinished\n"),
+            Some((2, Imbalance::Unterminated("block comment")))
+        );
+        assert_eq!(
+            check("let s = \"abc\n"),
+            Some((1, Imbalance::Unterminated("string literal")))
+        );
+    }
+
+    #[test]
+    fn ignores_delimiters_in_literals_and_comments() {
+        let source = "fn f() { // }\n    let s = \"{(\";\n    /* ] */\n    let c = '}';\n    let r = r#\"\"}\"#;\n}\n";
+        assert_eq!(check(source), None);
+    }
+
+    /// Small deterministic generator, to fuzz without dependencies.
+    struct Rng(u64);
+
+    impl Rng {
+        fn below(&mut self, n: usize) -> usize {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            (self.0 % n as u64) as usize
+        }
+    }
+
+    /// Appends balanced code to `source`, with delimiters in literals
+    /// and comments, noting where the delimiters of the code are.
+    fn balanced(rng: &mut Rng, depth: usize, source: &mut String, delimiters: &mut Vec<usize>) {
+        for _ in 0..rng.below(4) {
+            match rng.below(7) {
+                0..=2 if depth < 4 => {
+                    let (open, close) = [('{', '}'), ('(', ')'), ('[', ']')][rng.below(3)];
+                    delimiters.push(source.len());
+                    source.push(open);
+                    balanced(rng, depth + 1, source, delimiters);
+                    delimiters.push(source.len());
+                    source.push(close);
+                }
+                3 => source.push_str(" \"}{(\" "),
+                4 => source.push_str(" /* ] */ "),
+                5 => source.push_str(" // )\n"),
+                _ => source.push_str(" x;\n"),
+            }
+        }
+    }
+
+    #[test]
+    fn random_balanced_sources_pass_and_lose_their_balance_without_a_delimiter() {
+        let mut rng = Rng(0x2545_f491_4f6c_dd1d);
+        for _ in 0..500 {
+            let mut source = String::new();
+            let mut delimiters = Vec::new();
+            balanced(&mut rng, 0, &mut source, &mut delimiters);
+            assert_eq!(check(&source), None, "{:?}", source);
+            if delimiters.is_empty() {
+                continue;
+            }
+            let mut broken = source.clone();
+            broken.remove(delimiters[rng.below(delimiters.len())]);
+            assert!(check(&broken).is_some(), "{:?}", broken);
+        }
+    }
+}
@@ -0,0 +1,96 @@
+/*!
+Bundling of many targets at once, for CI jobs that check that all
+of them bundle, with a result for each target instead of stopping at
+the first failure.
+*/
+
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::thread;
+
+use super::json;
+use super::BundlePlan;
+use super::Bundler;
+use super::Error;
+use super::Report;
+
+/// Result of bundling one target.
+#[derive(Debug)]
+pub struct TargetResult {
+    /// Entry file of the target.
+    pub bin: PathBuf,
+    /// Bundle file of the target.
+    pub bundle: PathBuf,
+    pub result: Result<Report, Error>,
+}
+
+/// Run all the bundlers, in parallel, returning their results in the
+/// same order.
+pub fn run_all(bundlers: &[Bundler]) -> Vec<TargetResult> {
+    thread::scope(|scope| {
+        let handles: Vec<_> = bundlers
+            .iter()
+            .map(|bundler| scope.spawn(move || bundler.run()))
+            .collect();
+        bundlers
+            .iter()
+            .zip(handles)
+            .map(|(bundler, handle)| TargetResult {
+                bin: bundler.binrs_filename.to_path_buf(),
+                bundle: bundler.bundle_filename.to_path_buf(),
+                result: handle.join().unwrap_or_else(|_| {
+                    Err(Error::Panic {
+                        bin: bundler.binrs_filename.to_path_buf(),
+                    })
+                }),
+            })
+            .collect()
+    })
+}
+
+/// JSON report of the results, with an `ok` flag for the whole run
+/// and the size and warnings, or the error, of each target.
+pub fn results_json(results: &[TargetResult]) -> String {
+    let targets = results.iter().map(|target| {
+        let mut fields = vec![
+            ("bin", json::path(&target.bin)),
+            ("bundle", json::path(&target.bundle)),
+            ("ok", target.result.is_ok().to_string()),
+        ];
+        match target.result {
+            Ok(ref report) => {
+                fields.push(("size", report.size.to_string()));
+                fields.push((
+                    "warnings",
+                    json::array(report.warnings.iter().map(|w| json::string(&w.to_string()))),
+                ));
+            }
+            Err(ref error) => fields.push(("error", json::string(&error.to_string()))),
+        }
+        json::object(&fields)
+    });
+    let ok = results.iter().all(|target| target.result.is_ok());
+    json::object(&[("ok", ok.to_string()), ("targets", json::array(targets))])
+}
+
+/// Modules that none of the entry files of `plans`, as returned by
+/// [`Bundler::plan`] for each target, use, sorted. The submodules of an
+/// unused module are unused too, and are left out.
+pub fn unused_modules(plans: &[BundlePlan]) -> Vec<String> {
+    let mut referenced: BTreeMap<&str, bool> = BTreeMap::new();
+    for module in plans.iter().flat_map(|plan| &plan.modules) {
+        if module.expanded {
+            *referenced.entry(&module.path).or_default() |= module.referenced;
+        }
+    }
+    let mut unused: Vec<String> = Vec::new();
+    for (path, referenced) in referenced {
+        let in_unused = unused
+            .last()
+            .is_some_and(|parent| path.starts_with(&format!("{}::", parent)));
+        if !referenced && !in_unused {
+            unused.push(path.to_string());
+        }
+    }
+    unused
+}
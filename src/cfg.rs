@@ -0,0 +1,197 @@
+/*!
+Removal of the items that are compiled out with the judge's build
+profile, like `#[cfg(debug_assertions)]` visualizers in release builds,
+or with the enabled features. The judge has no features, so the items of
+the enabled ones lose their `#[cfg(feature = "...")]` instead.
+
+An item starts at its `#[cfg(...)]` attribute and ends at the first
+line, at the same delimiter depth, whose code ends with `}`, `;` or
+`,`, which covers items, statements, fields and variants.
+*/
+
+use regex::Captures;
+use regex::Regex;
+
+use super::lexer::Lexer;
+use super::BuildProfile;
+
+/// What to do with an item under a `#[cfg(...)]`.
+enum Cfg {
+    Keep,
+    /// Remove the attribute, keeping the item.
+    Unconditional,
+    Remove,
+}
+
+/// Removes the items of `bundle` that are not compiled with `profile`.
+/// Returns the new bundle and the number of items removed.
+pub fn strip_profile_items(bundle: &str, profile: BuildProfile) -> (String, usize) {
+    let cfg_re = Regex::new(
+        r"^\s*#\[\s*cfg\s*\(\s*(?P<cond>debug_assertions|not\s*\(\s*debug_assertions\s*\))\s*\)\s*\]",
+    )
+    .unwrap();
+    let debug = profile == BuildProfile::Debug;
+    strip_items(bundle, &cfg_re, |cap| {
+        if cap["cond"].starts_with("not") == debug {
+            Cfg::Remove
+        } else {
+            Cfg::Keep
+        }
+    })
+}
+
+/// Removes the items of `bundle` under `#[cfg(feature = "...")]` for a
+/// feature that is not in `enabled`, or under `#[cfg(not(feature =
+/// "..."))]` for one that is, and the attributes of the others. Returns
+/// the new bundle and the number of items removed.
+pub fn apply_features(bundle: &str, enabled: &[String]) -> (String, usize) {
+    let cfg_re = Regex::new(
+        r#"^\s*#\[\s*cfg\s*\(\s*(feature\s*=\s*"(?P<on>[^"]*)"|not\s*\(\s*feature\s*=\s*"(?P<off>[^"]*)"\s*\))\s*\)\s*\]"#,
+    )
+    .unwrap();
+    strip_items(bundle, &cfg_re, |cap| {
+        let (name, active_if_enabled) = match cap.name("on") {
+            Some(name) => (name.as_str(), true),
+            None => (&cap["off"], false),
+        };
+        if enabled.iter().any(|f| f == name) == active_if_enabled {
+            Cfg::Unconditional
+        } else {
+            Cfg::Remove
+        }
+    })
+}
+
+/// Removes the items whose `#[cfg(...)]` line matches `cfg_re` and that
+/// `decide` says are not compiled.
+fn strip_items<F: Fn(&Captures) -> Cfg>(
+    bundle: &str,
+    cfg_re: &Regex,
+    decide: F,
+) -> (String, usize) {
+    let mut lexer = Lexer::new();
+    let mut out = String::with_capacity(bundle.len());
+    let mut removed = 0;
+    // Delimiter depth of the item being removed, relative to its start:
+    let mut depth: Option<i32> = None;
+    // Attributes and docs that can be before a `#[cfg]`:
+    let mut pending = String::new();
+    for line in bundle.split_inclusive('\n') {
+        let scan = lexer.scan(line);
+        let mut line = line;
+        let unconditional;
+        if depth.is_none() && scan.starts_in_code() {
+            let cap = cfg_re.captures(line);
+            match cap.as_ref().map(|cap| (cap, decide(cap))) {
+                Some((_, Cfg::Remove)) => {
+                    removed += 1;
+                    depth = Some(0);
+                    pending.clear();
+                }
+                Some((cap, Cfg::Unconditional)) => {
+                    let rest = &line[cap.get(0).unwrap().end()..];
+                    if rest.trim().is_empty() {
+                        continue;
+                    }
+                    let indent = line.len() - line.trim_start().len();
+                    unconditional = format!("{}{}", &line[..indent], rest.trim_start());
+                    line = &unconditional;
+                }
+                _ => {
+                    let text = line.trim();
+                    let attribute = text.starts_with("#[") && text.ends_with(']');
+                    if scan.ends_in_code() && (attribute || text.starts_with("///")) {
+                        pending.push_str(line);
+                        continue;
+                    }
+                }
+            }
+        }
+        let Some(current) = depth else {
+            out.push_str(&pending);
+            pending.clear();
+            out.push_str(line);
+            continue;
+        };
+        let current = current + scan.delta;
+        let code = match scan.comment {
+            Some(comment) => &line[..comment],
+            None => line,
+        };
+        let code = code.trim();
+        let ends = current <= 0
+            && scan.ends_in_code()
+            && (code.ends_with('}') || code.ends_with(';') || code.ends_with(','));
+        depth = if ends { None } else { Some(current) };
+    }
+    out.push_str(&pending);
+    (out, removed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CODE: &str = "\
+/// Shows the graph.
+#[cfg(debug_assertions)]
+pub fn show(g: &[u32]) {
+    println!(\"{:?}\", g);
+}
+#[cfg(not(debug_assertions))]
+pub fn show(_: &[u32]) {}
+pub struct Solver {
+    #[cfg(debug_assertions)]
+    steps: usize,
+    n: usize,
+}
+fn solve() {
+    #[cfg(debug_assertions)]
+    eprintln!(\"{}\", \"}\");
+    run();
+}
+";
+
+    #[test]
+    fn release_builds_lose_the_debug_assertions_items() {
+        let (release, removed) = strip_profile_items(CODE, BuildProfile::Release);
+        assert_eq!(
+            release,
+            "#[cfg(not(debug_assertions))]\npub fn show(_: &[u32]) {}\npub struct Solver {\n    n: usize,\n}\nfn solve() {\n    run();\n}\n"
+        );
+        assert_eq!(removed, 3);
+    }
+
+    #[test]
+    fn debug_builds_lose_the_items_without_debug_assertions() {
+        let (debug, removed) = strip_profile_items(CODE, BuildProfile::Debug);
+        assert_eq!(
+            debug,
+            CODE.replace(
+                "#[cfg(not(debug_assertions))]\npub fn show(_: &[u32]) {}\n",
+                ""
+            )
+        );
+        assert_eq!(removed, 1);
+    }
+
+    #[test]
+    fn enabled_features_lose_their_attributes_and_others_their_items() {
+        let code = "\
+#[cfg(feature = \"fast\")]
+pub fn solve() -> u32 { 2 }
+#[cfg(not(feature = \"fast\"))]
+pub fn solve() -> u32 { 1 }
+#[cfg(feature = \"trace\")] use std::io;
+";
+        let enabled = ["fast".to_string()];
+        assert_eq!(
+            apply_features(code, &enabled),
+            ("pub fn solve() -> u32 { 2 }\n".to_string(), 2)
+        );
+        assert_eq!(
+            apply_features(code, &[]),
+            ("pub fn solve() -> u32 { 1 }\n".to_string(), 2)
+        );
+    }
+}
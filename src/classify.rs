@@ -0,0 +1,50 @@
+/*!
+Kinds of the lines of a source file, as the bundler reads them, for
+tools that want the same interpretation, see
+[`super::Bundler::classify_line`].
+*/
+
+use super::lexer::Lexer;
+
+/// Kind of a line of source, or of a statement written on several lines.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum LineKind {
+    /// Empty or whitespace.
+    Blank,
+    /// Only comments, removed from the bundle with the comments.
+    Comment,
+    /// `// bundler:...` comment that changes what's bundled, like
+    /// `// bundler:insert-lib`, kept out of the bundle in any case.
+    Directive,
+    /// `mod <name>;` declaration, expanded with the file of the module.
+    ModDecl { name: String },
+    /// `use` declaration, rewritten when it imports from the library.
+    UseDecl,
+    /// `extern crate <name>;`, removed for the bundled crate.
+    ExternCrate { name: String },
+    /// Anything else, copied to the bundle.
+    Code,
+}
+
+/// `stmt` on a single line, without its `//` comments, for
+/// [`super::Bundler::classify_stmt`].
+pub fn join_lines(stmt: &str) -> String {
+    let mut lexer = Lexer::new();
+    let mut joined = String::new();
+    for line in stmt.lines() {
+        let scan = lexer.scan(line);
+        let code = match scan.comment {
+            Some(comment) => &line[..comment],
+            None => line,
+        };
+        let code = code.trim();
+        if code.is_empty() {
+            continue;
+        }
+        if !joined.is_empty() {
+            joined.push(' ');
+        }
+        joined.push_str(code);
+    }
+    joined
+}
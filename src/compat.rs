@@ -0,0 +1,168 @@
+/*!
+Detection of the language and std features of the bundle that are
+newer than the Rust version of a judge, set with
+[`super::Bundler::max_rust_version`].
+
+Features are found with patterns on the code, as the bundle is not
+parsed, so they can miss some uses, and methods are found by name
+even on types of other crates.
+*/
+
+use regex::Regex;
+
+use super::lexer::Lexer;
+
+/// A feature, with the minor version of Rust 1 that stabilized it.
+struct Feature {
+    name: &'static str,
+    minor: u32,
+    pattern: &'static str,
+    /// Whether to look for the pattern in string literals too, for
+    /// format strings.
+    in_literals: bool,
+}
+
+const FEATURES: &[Feature] = &[
+    Feature {
+        name: "associated integer constants",
+        minor: 43,
+        pattern: r"\b[iu](8|16|32|64|128|size)::(MAX|MIN)\b",
+        in_literals: false,
+    },
+    Feature {
+        name: "captured format arguments",
+        minor: 58,
+        pattern: r#"\b(e?print(ln)?|format|write(ln)?|panic)!\s*\(.*"[^"]*\{[A-Za-z_]\w*(:[^}]*)?\}"#,
+        in_literals: true,
+    },
+    Feature {
+        name: "abs_diff",
+        minor: 60,
+        pattern: r"\.abs_diff\(",
+        in_literals: false,
+    },
+    Feature {
+        name: "then_some",
+        minor: 62,
+        pattern: r"\.then_some\(",
+        in_literals: false,
+    },
+    Feature {
+        name: "array::from_fn",
+        minor: 63,
+        pattern: r"\barray::from_fn\b",
+        in_literals: false,
+    },
+    Feature {
+        name: "let-else",
+        minor: 65,
+        pattern: r"\blet\b[^;{}=]*=[^;{}]*\belse\s*\{",
+        in_literals: false,
+    },
+    Feature {
+        name: "ilog",
+        minor: 67,
+        pattern: r"\.ilog(2|10)?\(",
+        in_literals: false,
+    },
+    Feature {
+        name: "is_some_and",
+        minor: 70,
+        pattern: r"\.is_(some|ok|err)_and\(",
+        in_literals: false,
+    },
+    Feature {
+        name: "OnceLock",
+        minor: 70,
+        pattern: r"\bOnceLock\b",
+        in_literals: false,
+    },
+    Feature {
+        name: "div_ceil",
+        minor: 73,
+        pattern: r"\.div_ceil\(",
+        in_literals: false,
+    },
+    Feature {
+        name: "inline const",
+        minor: 79,
+        pattern: r"\bconst\s*\{",
+        in_literals: false,
+    },
+    Feature {
+        name: "LazyLock",
+        minor: 80,
+        pattern: r"\bLazyLock\b",
+        in_literals: false,
+    },
+    Feature {
+        name: "isqrt",
+        minor: 84,
+        pattern: r"\.isqrt\(",
+        in_literals: false,
+    },
+    Feature {
+        name: "let chains",
+        minor: 88,
+        pattern: r"\bif\s+let\b[^{]*&&\s*let\b",
+        in_literals: false,
+    },
+];
+
+/// Features that `bundle` uses and that are newer than Rust
+/// `1.<max_minor>`, with the minor version that has them, in the order
+/// of the table.
+pub fn newer_features(bundle: &str, max_minor: u32) -> Vec<(&'static str, u32)> {
+    let features: Vec<(&Feature, Regex)> = FEATURES
+        .iter()
+        .filter(|feature| feature.minor > max_minor)
+        .map(|feature| (feature, Regex::new(feature.pattern).unwrap()))
+        .collect();
+    let mut found = vec![false; features.len()];
+    let mut lexer = Lexer::new();
+    for line in bundle.lines() {
+        let (_, ranges) = lexer.scan_code(line);
+        let code: Vec<&str> = ranges.into_iter().map(|range| &line[range]).collect();
+        let code = code.join(" ");
+        for (i, (feature, re)) in features.iter().enumerate() {
+            let text = if feature.in_literals { line } else { &code };
+            found[i] = found[i] || re.is_match(text);
+        }
+    }
+    features
+        .iter()
+        .zip(found)
+        .filter(|&(_, found)| found)
+        .map(|((feature, _), _)| (feature.name, feature.minor))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_features_newer_than_the_judge_are_found() {
+        let code = "\
+let x = a.abs_diff(b).div_ceil(2);
+let Some(y) = f() else { return };
+println!(\"{x}\");
+";
+        assert_eq!(
+            newer_features(code, 59),
+            [("abs_diff", 60), ("let-else", 65), ("div_ceil", 73)]
+        );
+        assert_eq!(newer_features(code, 70), [("div_ceil", 73)]);
+        assert!(newer_features(code, 88).is_empty());
+    }
+
+    #[test]
+    fn methods_in_comments_and_strings_are_not_features() {
+        let code = "// x.div_ceil(2)\nlet s = \".div_ceil(\";\n/* OnceLock */\n";
+        assert!(newer_features(code, 40).is_empty());
+        assert_eq!(
+            newer_features("println!(\"{n:>3}\", n = 1);\n", 57),
+            [("captured format arguments", 58)]
+        );
+    }
+}
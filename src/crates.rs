@@ -0,0 +1,50 @@
+/*!
+Detection of the external crates that a bundle uses, to check them
+against the ones the judge has.
+
+Crates are found in `extern crate` lines and in the first segment of
+`use` paths that is not a module of the bundle, nor an item, which are
+assumed to start with an uppercase letter.
+*/
+
+use std::collections::HashSet;
+
+use regex::Regex;
+
+use super::lexer::Lexer;
+
+/// Crates that every judge has.
+const BUILTIN: &[&str] = &["std", "core", "alloc", "proc_macro", "test"];
+
+/// Names of the external crates used by `bundle`, in the order they
+/// are first found.
+pub fn external_crates(bundle: &str) -> Vec<String> {
+    let extern_re = Regex::new(r"^\s*extern\s+crate\s+(?P<c>\w+)").unwrap();
+    let use_re =
+        Regex::new(r"^\s*(pub(\s*\([^)]*\))?\s+)?use\s+(::\s*)?(?P<c>\w+)\s*(::|;)").unwrap();
+    let mod_re = Regex::new(r"\bmod\s+(?P<m>\w+)\s*[{;]").unwrap();
+    let modules: HashSet<&str> = mod_re
+        .captures_iter(bundle)
+        .map(|cap| cap.name("m").unwrap().as_str())
+        .collect();
+    let mut lexer = Lexer::new();
+    let mut crates: Vec<String> = Vec::new();
+    for line in bundle.lines() {
+        if !lexer.scan(line).starts_in_code() {
+            continue;
+        }
+        let name = match extern_re.captures(line) {
+            Some(cap) => cap["c"].to_string(),
+            None => match use_re.captures(line) {
+                Some(cap) if !modules.contains(&cap["c"]) => cap["c"].to_string(),
+                _ => continue,
+            },
+        };
+        let local = ["crate", "self", "super"].contains(&name.as_str())
+            || name.starts_with(|c: char| c.is_uppercase());
+        if !local && !BUILTIN.contains(&name.as_str()) && !crates.contains(&name) {
+            crates.push(name);
+        }
+    }
+    crates
+}
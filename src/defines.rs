@@ -0,0 +1,97 @@
+/*!
+Substitution of the constants given to [`super::Bundler::define`], in
+`bundler_const!(<name>)` placeholders and in the initializer of
+`const <name>: <type> = ...;` lines.
+*/
+
+use regex::Regex;
+
+use super::lexer::Lexer;
+
+/// Replaces the placeholders and initializers of `defines` in the code
+/// of `bundle`. Returns the new bundle and the names of the constants
+/// that were found.
+pub fn substitute(bundle: &str, defines: &[(&str, &str)]) -> (String, Vec<String>) {
+    let rules: Vec<(Regex, Regex)> = defines
+        .iter()
+        .map(|&(name, _)| {
+            let name = regex::escape(name);
+            let placeholder =
+                Regex::new(&format!(r"\bbundler_const!\s*[(\[{{]\s*{}\s*[)\]}}]", name)).unwrap();
+            let initializer = Regex::new(&format!(
+                r"^(?P<head>\s*(pub(\s*\([^)]*\))?\s+)?const\s+{}\s*:[^=]+=\s*)[^;]*;",
+                name
+            ))
+            .unwrap();
+            (placeholder, initializer)
+        })
+        .collect();
+    let mut found = vec![false; defines.len()];
+    let mut lexer = Lexer::new();
+    let mut out = String::with_capacity(bundle.len());
+    for line in bundle.split_inclusive('\n') {
+        let scan = lexer.scan(line);
+        if !scan.starts_in_code() {
+            out.push_str(line);
+            continue;
+        }
+        let mut line = line.to_string();
+        for (i, (placeholder, initializer)) in rules.iter().enumerate() {
+            let value = defines[i].1;
+            if placeholder.is_match(&line) {
+                line = placeholder
+                    .replace_all(&line, regex::NoExpand(value))
+                    .into_owned();
+                found[i] = true;
+            }
+            if let Some(cap) = initializer.captures(&line) {
+                let end = cap.get(0).unwrap().end();
+                line = format!("{}{};{}", &cap["head"], value, &line[end..]);
+                found[i] = true;
+            }
+        }
+        out.push_str(&line);
+    }
+    let found = defines
+        .iter()
+        .zip(found)
+        .filter(|(_, found)| *found)
+        .map(|(&(name, _), _)| name.to_string())
+        .collect();
+    (out, found)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn placeholders_and_initializers_get_the_values() {
+        let code = "\
+pub const PROBLEM: &str = \"dev\";
+const N: usize = 10; // limit
+fn main() {
+    println!(\"{} {}\", bundler_const!(PROBLEM), bundler_const![N]);
+}
+";
+        let (out, found) = substitute(code, &[("PROBLEM", "\"1851F\""), ("N", "3"), ("M", "4")]);
+        assert_eq!(
+            out,
+            "\
+pub const PROBLEM: &str = \"1851F\";
+const N: usize = 3; // limit
+fn main() {
+    println!(\"{} {}\", \"1851F\", 3);
+}
+"
+        );
+        assert_eq!(found, ["PROBLEM", "N"]);
+    }
+
+    #[test]
+    fn values_are_inserted_literally() {
+        let (out, found) = substitute("const P: &str = \"\";\n", &[("P", "\"$1 ${x}\"")]);
+        assert_eq!(out, "const P: &str = \"$1 ${x}\";\n");
+        assert_eq!(found, ["P"]);
+    }
+}
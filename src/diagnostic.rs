@@ -0,0 +1,123 @@
+/*!
+Rendering of errors like rustc does, with the line of the source file
+they point at:
+
+```text
+error: could not find file for module graph
+ --> src/lib.rs:3
+  |
+3 | pub mod graph;
+  | ^^^^^^^^^^^^^^
+  |
+  = note: tried src/graph.rs src/graph/mod.rs
+```
+*/
+
+use std::fs;
+use std::path::Path;
+
+/// Renders `message`, the source line at `location` if it can be read,
+/// and the `notes`.
+pub fn render(message: &str, location: Option<(&Path, usize)>, notes: &[String]) -> String {
+    let (file, line) = match location {
+        Some(location) => location,
+        None => {
+            let mut out = format!("error: {}\n", message);
+            for note in notes {
+                out.push_str(&format!("  = note: {}\n", note));
+            }
+            return out;
+        }
+    };
+    let source = fs::read(file).ok().and_then(|contents| {
+        let contents = String::from_utf8_lossy(&contents).into_owned();
+        contents
+            .lines()
+            .nth(line.wrapping_sub(1))
+            .map(str::to_string)
+    });
+    let label = format!("{}:{}", file.display(), line);
+    render_source(message, &label, line, None, source.as_deref(), notes)
+}
+
+/// Renders `message` pointing at `source`, the text of `line`, which
+/// `label` shows as in `file:line`. The whole line is underlined,
+/// unless a 1-based `column` is given.
+pub fn render_source(
+    message: &str,
+    label: &str,
+    line: usize,
+    column: Option<usize>,
+    source: Option<&str>,
+    notes: &[String],
+) -> String {
+    let mut out = format!("error: {}\n", message);
+    let width = line.to_string().len();
+    let gutter = " ".repeat(width);
+    out.push_str(&format!("{}--> {}\n", gutter, label));
+    if let Some(source) = source {
+        let source = source.trim_end();
+        let marker = match column {
+            Some(column) => {
+                let before: String = source
+                    .chars()
+                    .take(column.saturating_sub(1))
+                    .map(|c| if c == '\t' { '\t' } else { ' ' })
+                    .collect();
+                before + "^"
+            }
+            None => {
+                let indent = source.len() - source.trim_start().len();
+                let carets = "^".repeat(source.trim_start().chars().count().max(1));
+                source[..indent].to_string() + &carets
+            }
+        };
+        out.push_str(&format!("{} |\n", gutter));
+        out.push_str(&format!("{} | {}\n", line, source));
+        out.push_str(&format!("{} | {}\n", gutter, marker));
+    }
+    if !notes.is_empty() {
+        out.push_str(&format!("{} |\n", gutter));
+    }
+    for note in notes {
+        out.push_str(&format!("{} = note: {}\n", gutter, note));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lines_are_underlined_or_pointed_at_a_column() {
+        let notes = ["tried a.rs".to_string()];
+        assert_eq!(
+            render_source("bad", "src/a.rs:12", 12, None, Some("    mod b;  "), &notes),
+            "error: bad\n  --> src/a.rs:12\n   |\n12 |     mod b;\n   |     ^^^^^^\n   |\n   = note: tried a.rs\n"
+        );
+        assert_eq!(
+            render_source(
+                "expected `;`",
+                "bundle:3:9",
+                3,
+                Some(9),
+                Some("\tlet x = 1"),
+                &[]
+            ),
+            "error: expected `;`\n --> bundle:3:9\n  |\n3 | \tlet x = 1\n  | \t       ^\n"
+        );
+    }
+
+    #[test]
+    fn errors_without_a_readable_source_have_no_snippet() {
+        assert_eq!(
+            render("oops", None, &["see above".to_string()]),
+            "error: oops\n  = note: see above\n"
+        );
+        assert_eq!(
+            render("gone", Some((Path::new("/nonexistent/a.rs"), 2)), &[]),
+            "error: gone\n --> /nonexistent/a.rs:2\n"
+        );
+    }
+}
@@ -0,0 +1,172 @@
+/*!
+Comparison of two bundles item by item, ignoring whitespace and
+comments, to review what an update of the library changes in them.
+*/
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+use super::json;
+use super::lexer::Lexer;
+use super::reach;
+use super::reach::ChunkKind;
+
+/// Items that differ between two bundles, by path, like `graph::Dinic`.
+/// `impl` blocks are named like `graph::impl Dinic`, and the other code
+/// of a module, like its `use` lines, like `graph::<other>`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BundleDiff {
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    pub changed: Vec<String>,
+}
+
+impl BundleDiff {
+    /// Whether the bundles have the same items.
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+
+    /// The diff as a JSON object.
+    pub fn to_json(&self) -> String {
+        let strings = |values: &[String]| json::array(values.iter().map(|v| json::string(v)));
+        json::object(&[
+            ("added", strings(&self.added)),
+            ("removed", strings(&self.removed)),
+            ("changed", strings(&self.changed)),
+        ])
+    }
+}
+
+impl fmt::Display for BundleDiff {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for path in &self.added {
+            writeln!(f, "+ {}", path)?;
+        }
+        for path in &self.removed {
+            writeln!(f, "- {}", path)?;
+        }
+        for path in &self.changed {
+            writeln!(f, "~ {}", path)?;
+        }
+        Ok(())
+    }
+}
+
+/// `text` without comments, and with each run of whitespace replaced
+/// by a single space.
+fn normalize(text: &str) -> String {
+    let mut lexer = Lexer::new();
+    let mut code = String::new();
+    for line in text.lines() {
+        let scan = lexer.scan(line);
+        if !scan.starts_in_code() {
+            code.push_str(line);
+        } else {
+            code.push_str(&line[..scan.comment.unwrap_or(line.len())]);
+        }
+        code.push(' ');
+    }
+    code.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Normalized code of the bundle, by item path.
+fn items(bundle: &str) -> BTreeMap<String, String> {
+    let mut items: BTreeMap<String, String> = BTreeMap::new();
+    for chunk in reach::chunks(bundle) {
+        let name = match (&chunk.kind, &chunk.name) {
+            (ChunkKind::Impl(ty), _) => format!("impl {}", ty),
+            (_, Some(name)) => name.clone(),
+            _ => "<other>".to_string(),
+        };
+        let path = if chunk.module.is_empty() {
+            name
+        } else {
+            format!("{}::{}", chunk.module, name)
+        };
+        let code = normalize(&chunk.text);
+        if code.is_empty() {
+            continue;
+        }
+        let item = items.entry(path).or_default();
+        item.push_str(&code);
+        item.push(' ');
+    }
+    items
+}
+
+/// Compare the bundles `old` and `new` item by item.
+pub fn diff_bundles(old: &str, new: &str) -> BundleDiff {
+    let old = items(old);
+    let new = items(new);
+    let mut diff = BundleDiff::default();
+    for (path, code) in &new {
+        match old.get(path) {
+            None => diff.added.push(path.clone()),
+            Some(old_code) if old_code != code => diff.changed.push(path.clone()),
+            Some(_) => {}
+        }
+    }
+    diff.removed = old
+        .keys()
+        .filter(|path| !new.contains_key(*path))
+        .cloned()
+        .collect();
+    diff
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OLD: &str = "\
+pub mod graph {
+use std::collections::VecDeque;
+pub struct Dinic {
+    n: usize,
+}
+impl Dinic {
+    pub fn flow(&self) -> u64 { 0 }
+}
+pub fn bfs() {}
+}
+";
+
+    #[test]
+    fn whitespace_and_comments_are_not_changes() {
+        let new = OLD
+            .replace("pub fn bfs() {}", "// Breadth first.\npub  fn bfs()\n{}")
+            .replace("    n: usize,", "    n: usize, // nodes");
+        let diff = diff_bundles(OLD, &new);
+        assert!(diff.is_empty(), "{:?}", diff);
+        assert_eq!(diff.to_string(), "");
+    }
+
+    #[test]
+    fn items_are_added_removed_and_changed_by_path() {
+        let new = OLD
+            .replace("pub fn bfs() {}", "pub fn dfs() {}")
+            .replace("{ 0 }", "{ 1 }")
+            .replace("VecDeque", "BinaryHeap");
+        let diff = diff_bundles(OLD, &new);
+        assert_eq!(
+            diff,
+            BundleDiff {
+                added: vec!["graph::dfs".to_string()],
+                removed: vec!["graph::bfs".to_string()],
+                changed: vec![
+                    "graph::<other>".to_string(),
+                    "graph::impl Dinic".to_string()
+                ],
+            }
+        );
+        assert_eq!(
+            diff.to_string(),
+            "+ graph::dfs\n- graph::bfs\n~ graph::<other>\n~ graph::impl Dinic\n"
+        );
+        assert_eq!(
+            diff.to_json(),
+            r#"{"added": ["graph::dfs"], "removed": ["graph::bfs"], "changed": ["graph::<other>", "graph::impl Dinic"]}"#
+        );
+    }
+}
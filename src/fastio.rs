@@ -0,0 +1,194 @@
+/*!
+Fast input and output templates, added to bundles as a `fastio` module
+with [`super::Bundler::inject_fastio`].
+
+Both templates have the same interface, so that a solution doesn't
+depend on which one is used:
+
+```text
+let mut scanner = fastio::Scanner::new();
+let mut out = fastio::writer();
+let n: usize = scanner.next();
+let values: Vec<i64> = scanner.vec(n);
+writeln!(out, "{}", values.iter().sum::<i64>()).unwrap();
+```
+
+They build with any edition, and with rustc 1.61 or later.
+*/
+
+use super::FastIoKind;
+
+const SCANNER: &str = r#"pub mod fastio {
+    use std::fmt::Debug;
+    use std::io::{self, BufWriter, Read, StdoutLock};
+    use std::str::{FromStr, SplitAsciiWhitespace};
+
+    /// Reads the tokens of the whole standard input.
+    pub struct Scanner {
+        tokens: SplitAsciiWhitespace<'static>,
+    }
+
+    impl Scanner {
+        pub fn new() -> Scanner {
+            let mut input = String::new();
+            io::stdin().lock().read_to_string(&mut input).unwrap();
+            let input: &'static str = Box::leak(input.into_boxed_str());
+            Scanner {
+                tokens: input.split_ascii_whitespace(),
+            }
+        }
+
+        pub fn next<T: FromStr>(&mut self) -> T
+        where
+            T::Err: Debug,
+        {
+            self.tokens.next().expect("end of input").parse().unwrap()
+        }
+
+        pub fn vec<T: FromStr>(&mut self, n: usize) -> Vec<T>
+        where
+            T::Err: Debug,
+        {
+            (0..n).map(|_| self.next()).collect()
+        }
+    }
+
+    /// Standard output, written when dropped.
+    pub fn writer() -> BufWriter<StdoutLock<'static>> {
+        BufWriter::new(io::stdout().lock())
+    }
+}
+"#;
+
+const INTERACTIVE: &str = r#"pub mod fastio {
+    use std::fmt::Debug;
+    use std::io::{self, BufRead, LineWriter, StdinLock, StdoutLock};
+    use std::str::FromStr;
+
+    /// Reads the tokens of the standard input a line at a time.
+    pub struct Scanner {
+        stdin: StdinLock<'static>,
+        tokens: Vec<String>,
+    }
+
+    impl Scanner {
+        pub fn new() -> Scanner {
+            Scanner {
+                stdin: io::stdin().lock(),
+                tokens: Vec::new(),
+            }
+        }
+
+        pub fn next<T: FromStr>(&mut self) -> T
+        where
+            T::Err: Debug,
+        {
+            while self.tokens.is_empty() {
+                let mut line = String::new();
+                if self.stdin.read_line(&mut line).unwrap() == 0 {
+                    panic!("end of input");
+                }
+                self.tokens = line.split_ascii_whitespace().rev().map(String::from).collect();
+            }
+            self.tokens.pop().unwrap().parse().unwrap()
+        }
+
+        pub fn vec<T: FromStr>(&mut self, n: usize) -> Vec<T>
+        where
+            T::Err: Debug,
+        {
+            (0..n).map(|_| self.next()).collect()
+        }
+    }
+
+    /// Standard output, written at the end of each line.
+    pub fn writer() -> LineWriter<StdoutLock<'static>> {
+        LineWriter::new(io::stdout().lock())
+    }
+}
+"#;
+
+/// Source of the `fastio` module of `kind`.
+pub fn source(kind: FastIoKind) -> &'static str {
+    match kind {
+        FastIoKind::Scanner => SCANNER,
+        FastIoKind::Interactive => INTERACTIVE,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+    use std::io::Write;
+    use std::process::Command;
+    use std::process::Stdio;
+
+    use super::*;
+
+    const MAIN: &str = "
+use std::io::Write;
+
+fn main() {
+    let mut scanner = fastio::Scanner::new();
+    let mut out = fastio::writer();
+    let n: usize = scanner.next();
+    let values: Vec<i64> = scanner.vec(n);
+    writeln!(out, \"{}\", values.iter().sum::<i64>()).unwrap();
+    let word: String = scanner.next();
+    writeln!(out, \"{}\", word).unwrap();
+}
+";
+
+    /// Builds the template of `kind` with a program that uses it, and
+    /// returns what the program prints for `input`.
+    fn build_and_run(kind: FastIoKind, edition: &str, input: &str) -> String {
+        let dir = env::temp_dir().join(format!(
+            "rustsourcebundler-fastio-{:?}-{}-{}",
+            kind,
+            edition,
+            std::process::id()
+        ));
+        fs::create_dir_all(&dir).unwrap();
+        let source = dir.join("main.rs");
+        let program = dir.join("main");
+        fs::write(&source, format!("{}{}", super::source(kind), MAIN)).unwrap();
+        let rustc = env::var("RUSTC").unwrap_or_else(|_| "rustc".to_string());
+        let status = Command::new(rustc)
+            .args(["--edition", edition, "-o"])
+            .arg(&program)
+            .arg(&source)
+            .status()
+            .unwrap();
+        assert!(
+            status.success(),
+            "{:?} doesn't build with {}",
+            kind,
+            edition
+        );
+        let mut child = Command::new(&program)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .unwrap();
+        child
+            .stdin
+            .take()
+            .unwrap()
+            .write_all(input.as_bytes())
+            .unwrap();
+        let output = child.wait_with_output().unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        String::from_utf8(output.stdout).unwrap()
+    }
+
+    #[test]
+    fn templates_read_tokens_across_lines() {
+        for kind in [FastIoKind::Scanner, FastIoKind::Interactive] {
+            for edition in ["2015", "2021"] {
+                let output = build_and_run(kind, edition, "3\n1 2\n  3\tend\n");
+                assert_eq!(output, "6\nend\n", "{:?} with {}", kind, edition);
+            }
+        }
+    }
+}
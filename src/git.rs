@@ -0,0 +1,66 @@
+/*!
+Revision of the git repository where the sources are.
+*/
+
+use std::fmt;
+use std::path::Path;
+use std::process::Command;
+
+/// Git revision of the sources.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GitRevision {
+    /// Output of `git describe --tags --always`.
+    pub describe: String,
+    /// Abbreviated hash of `HEAD`.
+    pub short_hash: String,
+    /// Whether there are uncommitted changes.
+    pub dirty: bool,
+}
+
+impl fmt::Display for GitRevision {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} ({}", self.describe, self.short_hash)?;
+        if self.dirty {
+            write!(f, ", dirty")?;
+        }
+        write!(f, ")")
+    }
+}
+
+/// Runs git in `dir`, returning its trimmed output if it succeeds.
+fn git(dir: &Path, args: &[&str]) -> Result<String, String> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(args)
+        .output()
+        .map_err(|e| format!("could not run git: {}", e))?;
+    if !output.status.success() {
+        return Err(String::from_utf8_lossy(&output.stderr).trim().to_string());
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+impl GitRevision {
+    /// Revision of the repository that has `dir`. Changes to the files
+    /// in `ignore`, like the bundle itself, don't make it dirty.
+    pub fn of(dir: &Path, ignore: &[&Path]) -> Result<GitRevision, String> {
+        let dir = if dir.as_os_str().is_empty() {
+            Path::new(".")
+        } else {
+            dir
+        };
+        let mut status = vec!["status".to_string(), "--porcelain".to_string()];
+        status.push("--".to_string());
+        status.push(".".to_string());
+        for path in ignore.iter().filter_map(|p| p.canonicalize().ok()) {
+            status.push(format!(":(exclude){}", path.display()));
+        }
+        let status: Vec<&str> = status.iter().map(String::as_str).collect();
+        Ok(GitRevision {
+            describe: git(dir, &["describe", "--tags", "--always"])?,
+            short_hash: git(dir, &["rev-parse", "--short", "HEAD"])?,
+            dirty: !git(dir, &status)?.is_empty(),
+        })
+    }
+}
@@ -0,0 +1,110 @@
+/*!
+Explicit imports for the glob imports of the entry file, like
+`use <crate>::graph::*;`, with [`super::Bundler::explicit_globs_set`].
+
+The public items, modules and `pub use` names of each module of the
+library are found as in [`super::reach`], and a glob imports the ones
+whose name appears in the entry file. Names are not resolved, so a name
+is imported if anything with the same name is used. Traits are always
+imported, since their methods can be called without naming them.
+*/
+
+use std::collections::HashMap;
+use std::collections::HashSet;
+
+use regex::Regex;
+
+use super::reach;
+use super::reach::ChunkKind;
+use super::split_use_group;
+use super::use_name;
+
+/// Names a glob import of each module would bring in scope.
+#[derive(Debug, Default)]
+pub struct GlobNames {
+    /// Names by module path, or `None` if the module has a glob
+    /// re-export, so they are not all known.
+    modules: HashMap<String, Option<Vec<String>>>,
+    /// Names that must be imported even if they are not used, by
+    /// module path.
+    traits: HashMap<String, HashSet<String>>,
+    /// Identifiers of the entry file.
+    used: HashSet<String>,
+}
+
+impl GlobNames {
+    /// Names of the modules of `bundle`, for the entry file `entry`.
+    pub fn new(bundle: &str, entry: &str) -> GlobNames {
+        let pub_use_re = Regex::new(r"(?m)^\s*pub\s+use\s+(?P<path>[^;]*);").unwrap();
+        let pub_mod_re = Regex::new(r"^\s*pub\s+mod\s+(?P<name>\w+)\s*\{\s*$").unwrap();
+        let trait_re = Regex::new(r"^\s*pub\s+(unsafe\s+)?trait\s").unwrap();
+        let mut names = GlobNames {
+            used: reach::idents(entry).map(str::to_string).collect(),
+            ..GlobNames::default()
+        };
+        for chunk in reach::chunks(bundle) {
+            let module = names
+                .modules
+                .entry(chunk.module.clone())
+                .or_insert(Some(Vec::new()));
+            let module = match module {
+                Some(module) => module,
+                None => continue,
+            };
+            match (&chunk.kind, &chunk.name) {
+                (ChunkKind::Root, Some(name)) => {
+                    module.push(name.clone());
+                    if chunk.text.lines().any(|line| trait_re.is_match(line)) {
+                        names
+                            .traits
+                            .entry(chunk.module.clone())
+                            .or_default()
+                            .insert(name.clone());
+                    }
+                    continue;
+                }
+                (ChunkKind::Root, None) => {}
+                _ => continue,
+            }
+            if let Some(cap) = chunk
+                .text
+                .lines()
+                .last()
+                .and_then(|l| pub_mod_re.captures(l))
+            {
+                module.push(cap["name"].to_string());
+            }
+            let mut glob = false;
+            for cap in pub_use_re.captures_iter(&chunk.text) {
+                for path in split_use_group(&cap["path"]) {
+                    match use_name(&path) {
+                        "*" => glob = true,
+                        "_" => {}
+                        name => module.push(name.to_string()),
+                    }
+                }
+            }
+            if glob {
+                names.modules.insert(chunk.module.clone(), None);
+            }
+        }
+        names
+    }
+
+    /// Names to import for a glob import of `module`, sorted, or
+    /// `None` if they are not known.
+    pub fn used_names(&self, module: &str) -> Option<Vec<String>> {
+        let traits = self.traits.get(module);
+        let mut used: Vec<String> = self
+            .modules
+            .get(module)?
+            .as_ref()?
+            .iter()
+            .filter(|name| self.used.contains(*name) || traits.is_some_and(|t| t.contains(*name)))
+            .cloned()
+            .collect();
+        used.sort();
+        used.dedup();
+        Some(used)
+    }
+}
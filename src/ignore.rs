@@ -0,0 +1,132 @@
+/*!
+Patterns of module files that are never bundled, in the style of
+`.gitignore`, given to [`super::Bundler::ignore_file`] or read from the
+`.bundleignore` file of the package.
+
+Paths are matched relative to the directory of the package, with `/`
+as separator. `*` matches within a path component and `**` across
+them; a pattern without a `/` matches the file name anywhere, a
+pattern that ends with `/` matches everything in the directory, and
+one that starts with `!` brings back files that an earlier pattern
+ignores.
+*/
+
+use regex::Regex;
+
+/// Name of the file with the patterns, next to Cargo.toml.
+pub const IGNORE_FILENAME: &str = ".bundleignore";
+
+#[derive(Debug)]
+struct Rule {
+    pattern: String,
+    re: Regex,
+    negated: bool,
+}
+
+/// The patterns, in order.
+#[derive(Debug, Default)]
+pub struct IgnoreRules {
+    rules: Vec<Rule>,
+}
+
+/// Regex for the glob `glob`.
+fn glob_regex(glob: &str) -> Regex {
+    let (glob, dir) = match glob.strip_suffix('/') {
+        Some(glob) => (glob, true),
+        None => (glob, false),
+    };
+    let mut re = String::from("^");
+    let glob = match glob.strip_prefix('/') {
+        Some(glob) => glob,
+        None if !glob.contains('/') => {
+            re.push_str("(.*/)?");
+            glob
+        }
+        None => glob,
+    };
+    let mut chars = glob.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '*' if chars.peek() == Some(&'*') => {
+                chars.next();
+                if chars.peek() == Some(&'/') {
+                    chars.next();
+                    re.push_str("(.*/)?");
+                } else {
+                    re.push_str(".*");
+                }
+            }
+            '*' => re.push_str("[^/]*"),
+            '?' => re.push_str("[^/]"),
+            c => re.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    re.push_str(if dir { "/.*$" } else { "(/.*)?$" });
+    Regex::new(&re).unwrap()
+}
+
+impl IgnoreRules {
+    /// Rules for the lines of `patterns`, skipping the blank ones and
+    /// the `#` comments.
+    pub fn new<'p, I: IntoIterator<Item = &'p str>>(patterns: I) -> IgnoreRules {
+        let rules = patterns
+            .into_iter()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(|line| {
+                let (glob, negated) = match line.strip_prefix('!') {
+                    Some(glob) => (glob, true),
+                    None => (line, false),
+                };
+                Rule {
+                    pattern: line.to_string(),
+                    re: glob_regex(glob),
+                    negated,
+                }
+            })
+            .collect();
+        IgnoreRules { rules }
+    }
+
+    /// The pattern that ignores `path`, if any.
+    pub fn ignoring(&self, path: &str) -> Option<&str> {
+        let rule = self
+            .rules
+            .iter()
+            .rev()
+            .find(|rule| rule.re.is_match(path))?;
+        if rule.negated {
+            None
+        } else {
+            Some(&rule.pattern)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn patterns_match_like_in_gitignore() {
+        let rules = IgnoreRules::new(
+            "# scratch files\n\ndev_*.rs\n/src/slow.rs\nsrc/old/\n**/gen/**\n!src/old/keep.rs\n"
+                .lines(),
+        );
+        let cases = [
+            ("src/dev_graph.rs", Some("dev_*.rs")),
+            ("src/io/dev_x.rs", Some("dev_*.rs")),
+            ("src/dev/graph.rs", None),
+            ("src/slow.rs", Some("/src/slow.rs")),
+            ("src/io/slow.rs", None),
+            ("src/old/a/b.rs", Some("src/old/")),
+            ("src/old/keep.rs", None),
+            ("src/gen/tables.rs", Some("**/gen/**")),
+            ("gen/x.rs", Some("**/gen/**")),
+            ("src/graph.rs", None),
+        ];
+        for (path, pattern) in cases {
+            assert_eq!(rules.ignoring(path), pattern, "{}", path);
+        }
+    }
+}
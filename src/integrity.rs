@@ -0,0 +1,141 @@
+/*!
+Integrity footer of the bundle, a last comment line with the bundler
+version and the SHA-256 of everything before it:
+
+```text
+// rustsourcebundler 0.9.0 sha256:<64 hexadecimal digits>
+```
+
+[`verify_bundle`] checks it, to find bundles that were edited by hand
+after being generated.
+*/
+
+use std::fs;
+use std::path::Path;
+
+use regex::Regex;
+
+use super::sha256;
+use super::Error;
+
+/// State of the integrity footer of a bundle, see [`verify_bundle`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Integrity {
+    /// The code matches the checksum of the footer, written by the
+    /// given version of the bundler.
+    Intact { version: String },
+    /// The code changed since the footer was written.
+    Modified { version: String },
+    /// The file has no footer.
+    Missing,
+}
+
+fn footer_re() -> Regex {
+    Regex::new(r"^// rustsourcebundler (?P<version>\S+) sha256:(?P<digest>[0-9a-f]{64})\r?$")
+        .unwrap()
+}
+
+/// Start of the last line of `contents`, ignoring the final newline.
+fn last_line_start(contents: &[u8]) -> usize {
+    let without_newline = contents.strip_suffix(b"\n").unwrap_or(contents);
+    without_newline
+        .iter()
+        .rposition(|&b| b == b'\n')
+        .map_or(0, |i| i + 1)
+}
+
+/// `bundle` with the footer appended.
+pub fn with_footer(mut bundle: String) -> String {
+    if !bundle.is_empty() && !bundle.ends_with('\n') {
+        bundle.push('\n');
+    }
+    let digest = sha256::hex_digest(bundle.as_bytes());
+    bundle.push_str(&format!(
+        "// rustsourcebundler {} sha256:{}\n",
+        env!("CARGO_PKG_VERSION"),
+        digest
+    ));
+    bundle
+}
+
+/// Checks the footer of `contents`.
+pub fn check(contents: &[u8]) -> Integrity {
+    let start = last_line_start(contents);
+    let footer = String::from_utf8_lossy(&contents[start..]);
+    let cap = match footer_re().captures(footer.trim_end_matches('\n')) {
+        Some(cap) => cap,
+        None => return Integrity::Missing,
+    };
+    let version = cap["version"].to_string();
+    if sha256::hex_digest(&contents[..start]) == cap["digest"] {
+        Integrity::Intact { version }
+    } else {
+        Integrity::Modified { version }
+    }
+}
+
+/// `bundle` without its footer, if it has one.
+pub fn without_footer(bundle: &str) -> &str {
+    let start = last_line_start(bundle.as_bytes());
+    if footer_re().is_match(bundle[start..].trim_end_matches('\n')) {
+        &bundle[..start]
+    } else {
+        bundle
+    }
+}
+
+/// Checks the integrity footer of the bundle at `path`, written with
+/// [`super::Bundler::integrity_footer_set`].
+pub fn verify_bundle(path: &Path) -> Result<Integrity, Error> {
+    let contents = fs::read(path).map_err(|error| Error::Io {
+        path: path.to_path_buf(),
+        error,
+    })?;
+    Ok(check(&contents))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BUNDLE: &str = "pub mod io {\n    pub fn read() {}\n}\nfn main() {}\n";
+
+    fn version() -> String {
+        env!("CARGO_PKG_VERSION").to_string()
+    }
+
+    #[test]
+    fn bundles_with_a_footer_are_intact_until_edited() {
+        let bundle = with_footer(BUNDLE.to_string());
+        let footer = bundle.strip_prefix(BUNDLE).unwrap();
+        assert!(footer.starts_with("// rustsourcebundler "), "{}", footer);
+        let intact = Integrity::Intact { version: version() };
+        assert_eq!(check(bundle.as_bytes()), intact);
+        assert_eq!(
+            check(bundle.replace('\n', "\r\n").as_bytes()),
+            Integrity::Modified { version: version() }
+        );
+        let edited = bundle.replace("read()", "read2()");
+        assert_eq!(
+            check(edited.as_bytes()),
+            Integrity::Modified { version: version() }
+        );
+        assert_eq!(check(BUNDLE.as_bytes()), Integrity::Missing);
+        assert_eq!(check(b""), Integrity::Missing);
+    }
+
+    #[test]
+    fn the_footer_can_be_removed_and_written_again() {
+        let bundle = with_footer(BUNDLE.to_string());
+        assert_eq!(without_footer(&bundle), BUNDLE);
+        assert_eq!(without_footer(BUNDLE), BUNDLE);
+        assert_eq!(with_footer(without_footer(&bundle).to_string()), bundle);
+        let unterminated = with_footer("fn main() {}".to_string());
+        assert_eq!(without_footer(&unterminated), "fn main() {}\n");
+        let stripped = unterminated.strip_suffix('\n').unwrap();
+        assert_eq!(
+            check(stripped.as_bytes()),
+            Integrity::Intact { version: version() }
+        );
+    }
+}
@@ -0,0 +1,124 @@
+/*!
+Removal of single items of the bundle, like a big precomputed table,
+selected by path or by a marker before them: a `// bundler:skip-item`
+line or a `#[cfg_attr(sourcebundle, skip)]` attribute, which can also
+be on a module.
+
+Items are found as in [`super::reach`], and the `impl` blocks of a
+removed type in the same module go with it.
+*/
+
+use std::collections::HashSet;
+
+use regex::Regex;
+
+use super::reach;
+use super::reach::ChunkKind;
+
+/// Removes the items of `bundle` at the given paths, like
+/// `math::PRIMES`, or with a line that matches `marker` before them.
+/// Returns the new bundle and the paths of the items removed.
+pub fn exclude(bundle: &str, paths: &[&str], marker: &Regex) -> (String, Vec<String>) {
+    let chunks = reach::chunks(bundle);
+    let path_of = |module: &str, name: &str| {
+        if module.is_empty() {
+            name.to_string()
+        } else {
+            format!("{}::{}", module, name)
+        }
+    };
+    let mod_re = Regex::new(r"^\s*(pub(\s*\([^)]*\))?\s+)?mod\s+(?P<name>\w+)\s*\{\s*$").unwrap();
+    let mut removed = Vec::new();
+    let mut removed_set = HashSet::new();
+    let module_header = |chunk: &reach::Chunk| {
+        let line = chunk.text.lines().last()?;
+        let cap = mod_re.captures(line).filter(|_| chunk.name.is_none())?;
+        Some(path_of(&chunk.module, &cap["name"]))
+    };
+    let mut removed_modules = Vec::new();
+    for chunk in &chunks {
+        if let Some(path) = module_header(chunk) {
+            if chunk.text.lines().any(|line| marker.is_match(line)) {
+                removed_modules.push(format!("{}::", path));
+                removed.push(path);
+            }
+        } else if let Some(ref name) = chunk.name {
+            let path = path_of(&chunk.module, name);
+            let marked = chunk.text.lines().any(|line| marker.is_match(line));
+            if marked || paths.contains(&path.as_str()) {
+                removed_set.insert(path.clone());
+                removed.push(path);
+            }
+        }
+    }
+    let mut out = String::with_capacity(bundle.len());
+    for chunk in &chunks {
+        let path = match (&chunk.kind, &chunk.name) {
+            (ChunkKind::Impl(ty), _) => Some(path_of(&chunk.module, ty)),
+            (_, Some(name)) => Some(path_of(&chunk.module, name)),
+            _ => None,
+        };
+        let module = format!("{}::", chunk.module);
+        let in_removed_module = removed_modules.iter().any(|m| module.starts_with(m));
+        let removed_header = module_header(chunk)
+            .is_some_and(|path| removed_modules.contains(&format!("{}::", path)));
+        if !in_removed_module
+            && !removed_header
+            && !path.is_some_and(|path| removed_set.contains(&path))
+        {
+            out.push_str(&chunk.text);
+        }
+    }
+    (out, removed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn marker() -> Regex {
+        Regex::new(r"^\s*(//\s*bundler:skip-item\s*$|#\[\s*cfg_attr\s*\(\s*sourcebundle\s*,\s*skip\s*\)\s*\])")
+            .unwrap()
+    }
+
+    const BUNDLE: &str = "\
+pub mod math {
+pub const PRIMES: [u32; 3] = [2, 3, 5];
+pub struct Table;
+impl Table {
+    pub fn get() {}
+}
+pub fn gcd() {}
+}
+fn main() {
+    math::gcd();
+}
+";
+
+    #[test]
+    fn items_are_excluded_by_path_with_their_impl_blocks() {
+        let (bundle, removed) = exclude(BUNDLE, &["math::PRIMES", "math::Table"], &marker());
+        assert_eq!(
+            bundle,
+            "pub mod math {\npub fn gcd() {}\n}\nfn main() {\n    math::gcd();\n}\n"
+        );
+        assert_eq!(removed, ["math::PRIMES", "math::Table"]);
+        let (bundle, removed) = exclude(BUNDLE, &["PRIMES", "math::Other"], &marker());
+        assert_eq!((&bundle[..], removed.len()), (BUNDLE, 0));
+    }
+
+    #[test]
+    fn items_and_modules_are_excluded_by_marker() {
+        let marked = BUNDLE.replace(
+            "pub fn gcd",
+            "// bundler:skip-item\npub fn lcm() {}\npub fn gcd",
+        );
+        let (bundle, removed) = exclude(&marked, &[], &marker());
+        assert_eq!(bundle, BUNDLE);
+        assert_eq!(removed, ["math::lcm"]);
+        let marked = format!("#[cfg_attr(sourcebundle, skip)]\n{}", BUNDLE);
+        let (bundle, removed) = exclude(&marked, &[], &marker());
+        assert_eq!(bundle, "fn main() {\n    math::gcd();\n}\n");
+        assert_eq!(removed, ["math"]);
+    }
+}
@@ -0,0 +1,107 @@
+/*!
+Just enough JSON output for the metadata files and reports, also used
+by the command line.
+*/
+
+use std::fmt::Write;
+use std::path::Path;
+
+/// JSON string literal with the contents of `s`.
+pub fn string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// JSON string literal with a path.
+pub fn path(p: &Path) -> String {
+    string(&p.to_string_lossy())
+}
+
+/// JSON object with the given already-encoded values.
+pub fn object(fields: &[(&str, String)]) -> String {
+    let fields: Vec<String> = fields
+        .iter()
+        .map(|(key, value)| format!("{}: {}", string(key), value))
+        .collect();
+    format!("{{{}}}", fields.join(", "))
+}
+
+/// JSON object with the given already-encoded values, for keys that
+/// are not known in advance.
+pub fn object_of<'k, I: IntoIterator<Item = (&'k str, String)>>(fields: I) -> String {
+    let fields: Vec<(&str, String)> = fields.into_iter().collect();
+    object(&fields)
+}
+
+/// JSON array with the given already-encoded values.
+pub fn array<I: IntoIterator<Item = String>>(values: I) -> String {
+    format!("[{}]", values.into_iter().collect::<Vec<_>>().join(", "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strings_escape_quotes_backslashes_and_control_characters() {
+        assert_eq!(string(r#"say "hi" \o/"#), r#""say \"hi\" \\o/""#);
+        assert_eq!(string("a\nb\r\tc"), r#""a\nb\r\tc""#);
+        assert_eq!(
+            string("\0\u{1}\u{8}\u{c}\u{1f}"),
+            r#""\u0000\u0001\u0008\u000c\u001f""#
+        );
+        assert_eq!(string("\u{7f} "), "\"\u{7f} \"");
+    }
+
+    #[test]
+    fn non_ascii_characters_are_kept_as_they_are() {
+        assert_eq!(string("größe π 東京 🦀"), "\"größe π 東京 🦀\"");
+        assert_eq!(string("\u{2028}\u{a0}"), "\"\u{2028}\u{a0}\"");
+    }
+
+    #[test]
+    fn objects_and_arrays_hold_encoded_values() {
+        let fields = [
+            ("name", string("a\"b")),
+            (
+                "files",
+                array(vec![path(Path::new("src/lib.rs")), "null".to_string()]),
+            ),
+            ("size", 12.to_string()),
+        ];
+        assert_eq!(
+            object(&fields),
+            r#"{"name": "a\"b", "files": ["src/lib.rs", null], "size": 12}"#
+        );
+        assert_eq!(
+            object_of(vec![("k\n", array(Vec::new()))]),
+            r#"{"k\n": []}"#
+        );
+        assert_eq!(object(&[]), "{}");
+    }
+
+    #[test]
+    fn the_output_is_valid_json() {
+        let text = "\"\\\n\r\t\0\u{1f}\u{7f}é🦀";
+        let parsed: String = serde_json::from_str(&string(text)).unwrap();
+        assert_eq!(parsed, text);
+        let value: serde_json::Value =
+            serde_json::from_str(&object(&[("x", array(vec![string(text)]))])).unwrap();
+        assert_eq!(value["x"][0], text);
+    }
+}
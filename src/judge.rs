@@ -0,0 +1,347 @@
+/*!
+Facade for contest helpers like cargo-compete and online-judge-tools.
+
+[`Preset`] has the settings of some judges, to apply with
+[`Bundler::preset`].
+
+Bundles each problem of a contest package, `src/bin/<problem>.rs`, into
+`<out dir>/<problem>.rs`, and writes `<out dir>/metadata.json` with the
+source, bundle, size and SHA-256 of each of them:
+
+```no_run
+use std::path::Path;
+use rustsourcebundler::judge::Contest;
+
+let mut contest = Contest::new(Path::new("."), "mycontest");
+contest.configure(|bundler| bundler.minify_set(true));
+let artifacts = contest.bundle_all().unwrap();
+println!("{}", artifacts[0].bundle.display());
+```
+*/
+
+use std::fs;
+use std::path::Path;
+use std::path::PathBuf;
+
+use super::json;
+use super::manifest::Manifest;
+use super::sha256;
+use super::Bundler;
+use super::Edition;
+use super::Error;
+use super::FastIoKind;
+use super::Warning;
+
+/// Name of the metadata file written to the output directory.
+pub const METADATA_FILENAME: &str = "metadata.json";
+
+/// Settings of a judge, applied with [`Bundler::preset`].
+pub trait JudgePreset {
+    fn configure(&self, bundler: &mut Bundler<'_>);
+}
+
+impl<F: Fn(&mut Bundler<'_>)> JudgePreset for F {
+    fn configure(&self, bundler: &mut Bundler<'_>) {
+        self(bundler)
+    }
+}
+
+/// Crates of the AtCoder judge, some of them.
+const ATCODER_CRATES: &[&str] = &[
+    "ac_library",
+    "alga",
+    "amplify",
+    "argio",
+    "arrayvec",
+    "ascii",
+    "bitvec",
+    "bstr",
+    "btreemultimap",
+    "either",
+    "fixedbitset",
+    "hashbag",
+    "im_rc",
+    "indexmap",
+    "itertools",
+    "lazy_static",
+    "maplit",
+    "memoise",
+    "nalgebra",
+    "ndarray",
+    "num",
+    "num_bigint",
+    "num_complex",
+    "num_integer",
+    "num_rational",
+    "num_traits",
+    "once_cell",
+    "ordered_float",
+    "permutohedron",
+    "petgraph",
+    "proconio",
+    "rand",
+    "rand_chacha",
+    "rand_core",
+    "rand_distr",
+    "rand_pcg",
+    "recur_fn",
+    "regex",
+    "rustc_hash",
+    "smallvec",
+    "static_assertions",
+    "statrs",
+    "superslice",
+    "text_io",
+    "whiteread",
+];
+
+/// Built-in judge settings.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Preset {
+    /// 64 KB source limit, edition 2021, std only.
+    Codeforces,
+    /// 512 KiB source limit, edition 2021, with the crates AtCoder has.
+    AtCoder,
+    /// The library is submitted, without the entry file, edition 2021,
+    /// std only.
+    Exercism,
+    /// 128 KiB source limit, edition 2021, std only.
+    Kattis,
+}
+
+impl Preset {
+    /// Source size limit of the judge, if it has one.
+    pub fn size_limit(&self) -> Option<usize> {
+        match self {
+            Preset::Codeforces => Some(65535),
+            Preset::AtCoder => Some(512 * 1024),
+            Preset::Exercism => None,
+            Preset::Kattis => Some(128 * 1024),
+        }
+    }
+
+    /// Kind of `fastio` module that suits the judge, to give to
+    /// [`Bundler::inject_fastio`], if its problems read standard input
+    /// and it has no crate for it.
+    pub fn fastio(&self) -> Option<FastIoKind> {
+        match self {
+            Preset::Codeforces | Preset::Kattis => Some(FastIoKind::Scanner),
+            Preset::AtCoder | Preset::Exercism => None,
+        }
+    }
+
+    /// Crates other than std that the judge has.
+    pub fn crates(&self) -> &'static [&'static str] {
+        match self {
+            Preset::AtCoder => ATCODER_CRATES,
+            _ => &[],
+        }
+    }
+}
+
+impl JudgePreset for Preset {
+    /// Sets the size limit, minifying only as much as needed to fit
+    /// in it, the edition and the allowed crates.
+    fn configure(&self, bundler: &mut Bundler<'_>) {
+        if let Some(limit) = self.size_limit() {
+            bundler.size_limit(limit);
+            bundler.minify_auto_set(true);
+        }
+        bundler.edition(Edition::Rust2021);
+        bundler.allowed_crates(self.crates());
+        if *self == Preset::Exercism {
+            bundler.library_only_set(true);
+        }
+    }
+}
+
+/// The bundle of a problem.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Artifact {
+    /// Name of the problem, which is also the name of the bin target.
+    pub problem: String,
+    /// Entry file of the problem.
+    pub source: PathBuf,
+    /// Bundle file, the one to submit.
+    pub bundle: PathBuf,
+    /// Size of the bundle in bytes.
+    pub size: usize,
+    /// SHA-256 of the bundle, in hex.
+    pub sha256: String,
+    pub warnings: Vec<Warning>,
+}
+
+type Configure = Box<dyn Fn(&mut Bundler)>;
+
+/// A contest package, with one bin target per problem.
+pub struct Contest<'a> {
+    root: &'a Path,
+    crate_name: &'a str,
+    out_dir: PathBuf,
+    problems: Vec<String>,
+    features: Vec<String>,
+    configure: Option<Configure>,
+}
+
+impl<'a> Contest<'a> {
+    /// Contest in the package at `root`, with the bundles going to
+    /// `<root>/target/bundle` by default.
+    pub fn new(root: &'a Path, crate_name: &'a str) -> Contest<'a> {
+        Contest {
+            root,
+            crate_name,
+            out_dir: root.join("target").join("bundle"),
+            problems: Vec::new(),
+            features: Vec::new(),
+            configure: None,
+        }
+    }
+
+    pub fn out_dir(&mut self, out_dir: &Path) {
+        self.out_dir = out_dir.to_path_buf();
+    }
+
+    /// Bundle only the given problem; can be called several times. By
+    /// default, all files in `src/bin` are bundled.
+    pub fn problem(&mut self, name: &str) {
+        self.problems.push(name.to_string());
+    }
+
+    /// Enable the feature `name` of the package for all problems, see
+    /// [`Bundler::enable_feature`]. Like cargo, the problems in `src/bin`
+    /// whose `required-features` are not all enabled are skipped.
+    pub fn enable_feature(&mut self, name: &str) {
+        self.features.push(name.to_string());
+    }
+
+    /// Function called to set up the bundler of each problem.
+    pub fn configure<F: Fn(&mut Bundler) + 'static>(&mut self, configure: F) {
+        self.configure = Some(Box::new(configure));
+    }
+
+    fn io_error(path: &Path) -> impl FnOnce(std::io::Error) -> Error + '_ {
+        move |error| Error::Io {
+            path: path.to_path_buf(),
+            error,
+        }
+    }
+
+    /// Names of the problems, sorted if they come from `src/bin`.
+    fn problem_names(&self) -> Result<Vec<String>, Error> {
+        if !self.problems.is_empty() {
+            return Ok(self.problems.clone());
+        }
+        let manifest = Manifest::read(&self.root.join("Cargo.toml")).unwrap_or_default();
+        let enabled = |name: &str| {
+            manifest
+                .required_features(name)
+                .iter()
+                .all(|feature| self.features.contains(feature))
+        };
+        let bin_dir = self.root.join("src").join("bin");
+        let mut names = Vec::new();
+        for entry in fs::read_dir(&bin_dir).map_err(Contest::io_error(&bin_dir))? {
+            let path = entry.map_err(Contest::io_error(&bin_dir))?.path();
+            if path.extension().is_some_and(|e| e == "rs") {
+                if let Some(stem) = path.file_stem() {
+                    let name = stem.to_string_lossy().into_owned();
+                    if enabled(&name) {
+                        names.push(name);
+                    }
+                }
+            }
+        }
+        names.sort();
+        Ok(names)
+    }
+
+    /// Modules of the library that none of the problems use, see
+    /// [`super::unused_modules`]. Nothing is written.
+    pub fn unused_modules(&self) -> Result<Vec<String>, Error> {
+        let librs = self.root.join("src").join("lib.rs");
+        let mut plans = Vec::new();
+        for problem in self.problem_names()? {
+            let source = self
+                .root
+                .join("src")
+                .join("bin")
+                .join(format!("{}.rs", problem));
+            let bundle = self.out_dir.join(format!("{}.rs", problem));
+            let mut bundler = Bundler::new_with_librs(&source, &bundle, &librs);
+            bundler.crate_name(self.crate_name);
+            for feature in &self.features {
+                bundler.enable_feature(feature);
+            }
+            if let Some(ref configure) = self.configure {
+                configure(&mut bundler);
+            }
+            plans.push(bundler.plan()?);
+        }
+        Ok(super::unused_modules(&plans))
+    }
+
+    /// Bundle all problems and write the metadata file.
+    pub fn bundle_all(&self) -> Result<Vec<Artifact>, Error> {
+        fs::create_dir_all(&self.out_dir).map_err(Contest::io_error(&self.out_dir))?;
+        let librs = self.root.join("src").join("lib.rs");
+        let mut artifacts = Vec::new();
+        for problem in self.problem_names()? {
+            let source = self
+                .root
+                .join("src")
+                .join("bin")
+                .join(format!("{}.rs", problem));
+            let bundle = self.out_dir.join(format!("{}.rs", problem));
+            let report = {
+                let mut bundler = Bundler::new_with_librs(&source, &bundle, &librs);
+                bundler.crate_name(self.crate_name);
+                for feature in &self.features {
+                    bundler.enable_feature(feature);
+                }
+                if let Some(ref configure) = self.configure {
+                    configure(&mut bundler);
+                }
+                bundler.run()?
+            };
+            let contents = fs::read(&bundle).map_err(Contest::io_error(&bundle))?;
+            artifacts.push(Artifact {
+                problem,
+                sha256: sha256::hex_digest(&contents),
+                source,
+                bundle,
+                size: report.size,
+                warnings: report.warnings,
+            });
+        }
+        let metadata_filename = self.out_dir.join(METADATA_FILENAME);
+        fs::write(&metadata_filename, metadata_json(&artifacts) + "\n")
+            .map_err(Contest::io_error(&metadata_filename))?;
+        Ok(artifacts)
+    }
+}
+
+/// Contents of the metadata file for the given artifacts.
+pub fn metadata_json(artifacts: &[Artifact]) -> String {
+    let problems = artifacts.iter().map(|artifact| {
+        json::object(&[
+            ("problem", json::string(&artifact.problem)),
+            ("source", json::path(&artifact.source)),
+            ("bundle", json::path(&artifact.bundle)),
+            ("size", artifact.size.to_string()),
+            ("sha256", json::string(&artifact.sha256)),
+            (
+                "warnings",
+                json::array(
+                    artifact
+                        .warnings
+                        .iter()
+                        .map(|w| json::string(&w.to_string())),
+                ),
+            ),
+        ])
+    });
+    json::object(&[
+        ("bundler_version", json::string(env!("CARGO_PKG_VERSION"))),
+        ("problems", json::array(problems)),
+    ])
+}
@@ -0,0 +1,310 @@
+/*!
+Minimal rust lexer, just enough to know when a line starts inside a
+string literal or a block comment, and which delimiters it opens.
+*/
+
+use std::ops::Range;
+
+/// What the lexer is in the middle of at a given point.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LexState {
+    /// Regular code.
+    #[default]
+    Code,
+    /// Block comment, with its nesting depth.
+    BlockComment(usize),
+    /// String literal.
+    Str,
+    /// Raw string literal, with its number of `#`.
+    RawStr(usize),
+}
+
+/// Result of scanning a line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LineScan {
+    /// State at the start of the line.
+    pub start: LexState,
+    /// State at the end of the line.
+    pub end: LexState,
+    /// Byte offset of the `//` comment that ends the line, if any.
+    pub comment: Option<usize>,
+    /// Delimiters opened minus delimiters closed in code.
+    pub delta: i32,
+}
+
+impl LineScan {
+    /// Whether the line starts in regular code, i.e. it can be
+    /// classified by looking at its text.
+    pub fn starts_in_code(&self) -> bool {
+        self.start == LexState::Code
+    }
+
+    /// Whether the line ends in regular code, i.e. trailing whitespace
+    /// is not part of a literal.
+    pub fn ends_in_code(&self) -> bool {
+        self.end == LexState::Code
+    }
+}
+
+/// Keeps the state across the lines of a file.
+#[derive(Debug, Clone, Default)]
+pub struct Lexer {
+    state: LexState,
+}
+
+fn is_ident_char(c: u8) -> bool {
+    c.is_ascii_alphanumeric() || c == b'_'
+}
+
+/// If there's a raw string start at `i`, returns its number of `#`
+/// and the offset after the opening quote.
+fn raw_string_start(bytes: &[u8], i: usize) -> Option<(usize, usize)> {
+    if bytes[i] != b'r' {
+        return None;
+    }
+    let prefix_ok = match i {
+        0 => true,
+        1 => !is_ident_char(bytes[0]) || bytes[0] == b'b',
+        _ => !is_ident_char(bytes[i - 1]) || (bytes[i - 1] == b'b' && !is_ident_char(bytes[i - 2])),
+    };
+    if !prefix_ok {
+        return None;
+    }
+    let mut j = i + 1;
+    while j < bytes.len() && bytes[j] == b'#' {
+        j += 1;
+    }
+    if j < bytes.len() && bytes[j] == b'"' {
+        Some((j - i - 1, j + 1))
+    } else {
+        None
+    }
+}
+
+/// If there's a char literal at `i`, returns the offset after it.
+/// Lifetimes and loop labels return `None`.
+fn char_literal_end(bytes: &[u8], i: usize) -> Option<usize> {
+    if bytes.get(i + 1) == Some(&b'\\') {
+        // Skip the escaped char, which might be a quote:
+        let mut j = i + 3;
+        while j < bytes.len() && bytes[j] != b'\'' {
+            j += 1;
+        }
+        return Some((j + 1).min(bytes.len()));
+    }
+    // A single, possibly multi-byte, char followed by the closing quote:
+    let len = bytes.get(i + 1).map(|&c| utf8_len(c))?;
+    if bytes.get(i + 1 + len) == Some(&b'\'') {
+        Some(i + 2 + len)
+    } else {
+        None
+    }
+}
+
+fn utf8_len(first: u8) -> usize {
+    match first {
+        0xF0..=0xFF => 4,
+        0xE0..=0xEF => 3,
+        0xC0..=0xDF => 2,
+        _ => 1,
+    }
+}
+
+impl Lexer {
+    pub fn new() -> Lexer {
+        Lexer::default()
+    }
+
+    /// Scan a line, updating the state for the next one.
+    pub fn scan(&mut self, line: &str) -> LineScan {
+        self.scan_code(line).0
+    }
+
+    /// Like [`Lexer::scan`], also returning the byte ranges of the line
+    /// that are code, i.e. not literals or comments.
+    pub fn scan_code(&mut self, line: &str) -> (LineScan, Vec<Range<usize>>) {
+        let start = self.state;
+        let bytes = line.as_bytes();
+        let mut comment = None;
+        let mut delta = 0;
+        let mut code = Vec::new();
+        let mut code_start = 0;
+        let mut i = 0;
+        while i < bytes.len() {
+            let c = bytes[i];
+            let next = bytes.get(i + 1).copied();
+            match self.state {
+                LexState::Code => {
+                    if c == b'/' && next == Some(b'/') {
+                        comment = Some(i);
+                        break;
+                    } else if c == b'/' && next == Some(b'*') {
+                        code.push(code_start..i);
+                        self.state = LexState::BlockComment(1);
+                        i += 1;
+                    } else if c == b'"' {
+                        code.push(code_start..i);
+                        self.state = LexState::Str;
+                    } else if let Some((hashes, after)) = raw_string_start(bytes, i) {
+                        code.push(code_start..i);
+                        self.state = LexState::RawStr(hashes);
+                        i = after;
+                        continue;
+                    } else if c == b'\'' {
+                        if let Some(after) = char_literal_end(bytes, i) {
+                            code.push(code_start..i);
+                            code_start = after;
+                            i = after;
+                            continue;
+                        }
+                    } else if c == b'{' || c == b'(' || c == b'[' {
+                        delta += 1;
+                    } else if c == b'}' || c == b')' || c == b']' {
+                        delta -= 1;
+                    } else if is_ident_char(c) {
+                        // Skip the rest of identifiers, so that the
+                        // `r` in `for"` is not taken as a raw string.
+                        while i + 1 < bytes.len() && is_ident_char(bytes[i + 1]) {
+                            if bytes[i + 1] == b'r' && raw_string_start(bytes, i + 1).is_some() {
+                                break;
+                            }
+                            i += 1;
+                        }
+                    }
+                }
+                LexState::BlockComment(depth) => {
+                    if c == b'/' && next == Some(b'*') {
+                        self.state = LexState::BlockComment(depth + 1);
+                        i += 1;
+                    } else if c == b'*' && next == Some(b'/') {
+                        self.state = if depth > 1 {
+                            LexState::BlockComment(depth - 1)
+                        } else {
+                            code_start = i + 2;
+                            LexState::Code
+                        };
+                        i += 1;
+                    }
+                }
+                LexState::Str => {
+                    if c == b'\\' {
+                        i += 1;
+                    } else if c == b'"' {
+                        code_start = i + 1;
+                        self.state = LexState::Code;
+                    }
+                }
+                LexState::RawStr(hashes) => {
+                    if c == b'"'
+                        && bytes.len() >= i + 1 + hashes
+                        && bytes[i + 1..i + 1 + hashes].iter().all(|&h| h == b'#')
+                    {
+                        self.state = LexState::Code;
+                        i += hashes;
+                        code_start = i + 1;
+                    }
+                }
+            }
+            i += 1;
+        }
+        if self.state == LexState::Code {
+            code.push(code_start..comment.unwrap_or(bytes.len()));
+        }
+        code.retain(|range| !range.is_empty());
+        let scan = LineScan {
+            start,
+            end: self.state,
+            comment,
+            delta,
+        };
+        (scan, code)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// States at the end of each of `lines`, and their deltas.
+    fn scan_lines(lines: &[&str]) -> Vec<(LexState, i32)> {
+        let mut lexer = Lexer::new();
+        lines
+            .iter()
+            .map(|line| {
+                let scan = lexer.scan(line);
+                (scan.end, scan.delta)
+            })
+            .collect()
+    }
+
+    /// The code of `line`, outside of literals and comments.
+    fn code_of(line: &str) -> String {
+        let (_, code) = Lexer::new().scan_code(line);
+        code.into_iter().map(|range| &line[range]).collect()
+    }
+
+    #[test]
+    fn raw_strings_end_with_as_many_hashes() {
+        let lines = [
+            "let s = r#\"a \"quoted\" { word\"#; {",
+            "let t = r##\"spans\"#\"",
+            "lines\"## ( br\"x\" rb",
+        ];
+        assert_eq!(
+            scan_lines(&lines),
+            [
+                (LexState::Code, 1),
+                (LexState::RawStr(2), 0),
+                (LexState::Code, 1),
+            ]
+        );
+        assert_eq!(code_of(r#"f(r"\"); g()"#), r#"f(); g()"#);
+        assert_eq!(code_of(r#"for"x" in br"y""#), "for in b");
+    }
+
+    #[test]
+    fn chars_are_told_apart_from_lifetimes_and_labels() {
+        assert_eq!(
+            code_of("let c = '{'; let q = '\\''; x"),
+            "let c = ; let q = ; x"
+        );
+        assert_eq!(
+            code_of("let e = 'é'; let u = '\\u{7f}';"),
+            "let e = ; let u = ;"
+        );
+        assert_eq!(
+            code_of("fn f<'a>(x: &'a str) { 'outer: loop { break 'outer; } }"),
+            "fn f<'a>(x: &'a str) { 'outer: loop { break 'outer; } }"
+        );
+        assert_eq!(
+            scan_lines(&["impl<'a> S<'a> { fn f(c: char) -> bool { c == '}' }"]),
+            [(LexState::Code, 1)]
+        );
+    }
+
+    #[test]
+    fn block_comments_nest() {
+        let lines = ["a /* one /* two */ still { */ b {", "/* /* */", "*/ } x"];
+        assert_eq!(
+            scan_lines(&lines),
+            [
+                (LexState::Code, 1),
+                (LexState::BlockComment(1), 0),
+                (LexState::Code, -1),
+            ]
+        );
+        assert_eq!(code_of("a /* x /* y */ z */ b"), "a  b");
+    }
+
+    #[test]
+    fn strings_and_line_comments_hide_delimiters() {
+        let mut lexer = Lexer::new();
+        let scan = lexer.scan(r#"let s = "{\"}"; // }"#);
+        assert_eq!((scan.delta, scan.comment), (0, Some(16)));
+        let scan = lexer.scan(r#"let m = "multi {"#);
+        assert_eq!((scan.end, scan.delta), (LexState::Str, 0));
+        let scan = lexer.scan(r#"line" ; f("//")"#);
+        assert!(!scan.starts_in_code() && scan.ends_in_code());
+        assert_eq!((scan.delta, scan.comment), (0, None));
+    }
+}
@@ -4,39 +4,1289 @@ Use this library in your build.rs to create a single file with all the crate's s
 That's useful for programming exercise sites that take a single source file.
 */
 
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::collections::HashMap;
 use std::collections::HashSet;
-use std::fs::File;
+use std::env;
+use std::error;
+use std::fmt;
+use std::fs;
 use std::io;
-use std::io::BufRead;
-use std::io::BufReader;
-use std::io::Write;
 use std::path::Path;
+use std::path::PathBuf;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::Duration;
 
 extern crate regex;
-use regex::Regex;
+pub use regex::Regex;
+#[cfg(feature = "serde")]
+extern crate serde;
+#[cfg(feature = "check-syntax")]
+extern crate syn;
+
+mod lexer;
+use lexer::Lexer;
+use lexer::LineScan;
+
+mod manifest;
+use manifest::Manifest;
+
+mod minify;
+mod nesting;
+use nesting::Nesting;
+
+mod plan;
+mod reach;
+pub use plan::BundlePlan;
+pub use plan::PlannedModule;
+pub use plan::SkipReason;
+pub use plan::SkippedLine;
+
+mod source;
+use source::SourceFile;
+
+mod git;
+pub use git::GitRevision;
+
+mod cfg;
+mod classify;
+pub use classify::LineKind;
+mod compat;
+mod crates;
+mod defines;
+mod diagnostic;
+mod fastio;
+mod globs;
+mod ignore;
+use ignore::IgnoreRules;
+mod items;
+mod rename;
+
+mod balance;
+mod batch;
+pub use batch::results_json;
+pub use batch::run_all;
+pub use batch::unused_modules;
+pub use batch::TargetResult;
+
+mod meta;
+pub use meta::MetaBundler;
+
+pub mod post;
+pub use post::PostProcessor;
+
+mod reader;
+pub use reader::BundleReader;
+
+mod diff;
+pub use diff::diff_bundles;
+pub use diff::BundleDiff;
+
+mod integrity;
+pub use integrity::verify_bundle;
+pub use integrity::Integrity;
+
+pub mod json;
+mod options;
+pub use options::BundlerOptions;
+mod sha256;
+mod split;
+#[cfg(feature = "check-syntax")]
+mod syntax;
+mod toc;
+mod verify;
+pub use verify::VerifyRun;
+
+pub mod judge;
 
 const LIBRS_FILENAME: &str = "src/lib.rs";
 
+/// Environment variables read by [`Bundler::apply_env`], for build.rs
+/// to print a `cargo:rerun-if-env-changed` line for each.
+pub const ENV_VARS: &[&str] = &[
+    "SOURCEBUNDLER_OUTPUT",
+    "SOURCEBUNDLER_MINIFY",
+    "SOURCEBUNDLER_STRIP_COMMENTS",
+    "SOURCEBUNDLER_PROFILE",
+];
+
+/// Environment variables of contest helpers read by
+/// [`Bundler::contest_header_set`], with the field of the header they
+/// fill in.
+pub const HEADER_ENV_VARS: &[(&str, &str)] = &[
+    ("CONTEST_ID", "contest"),
+    ("PROBLEM", "problem"),
+    ("ONLINE_JUDGE", "judge"),
+];
+
+/// Something noteworthy that happened while bundling, but that didn't
+/// prevent the bundle from being created.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Warning {
+    /// A `use <crate>::...` line refers to a module that was not bundled.
+    UnresolvedImport { path: String },
+    /// A `mod <name>;` declaration was not expanded.
+    SkippedModule { name: String },
+    /// The bundle is bigger than the configured size limit.
+    OversizedOutput { size: usize, limit: usize },
+    /// A file of a bundle split with [`Bundler::split_output`] is still
+    /// bigger than the limit, as its biggest module is.
+    OversizedPart {
+        file: PathBuf,
+        size: usize,
+        limit: usize,
+    },
+    /// An `include!` invocation was copied verbatim into the bundle.
+    UnexpandedInclude { file: PathBuf, line: usize },
+    /// An `env!` or `option_env!` invocation is in the bundle, where it
+    /// reads the environment of the judge's compiler instead.
+    EnvMacro { file: PathBuf, line: usize },
+    /// Nothing in the entry file triggered the expansion of the library.
+    LibraryNotInlined { crate_name: String },
+    /// A line is not valid UTF-8, the invalid bytes were replaced with
+    /// U+FFFD.
+    InvalidUtf8 { file: PathBuf, line: usize },
+    /// The git revision header was requested, but the revision could
+    /// not be found.
+    NoGitRevision { reason: String },
+    /// The bundle and the original entry file give different outputs
+    /// for an input given to [`Bundler::verify_input`].
+    OutputMismatch { input: PathBuf },
+    /// The bundle or the original entry file could not be built or run
+    /// to verify it.
+    VerificationFailed { reason: String },
+    /// The bundle or the original entry file was killed after the run
+    /// timeout of [`Bundler::verify_timeouts`] for an input, which was
+    /// not compared.
+    VerificationTimeout {
+        input: PathBuf,
+        program: String,
+        timeout: Duration,
+    },
+    /// A constant given to [`Bundler::define`] has no placeholder nor
+    /// `const` in the bundle.
+    UnusedDefine { name: String },
+    /// A `// bundler:only-local` section of the entry file has no
+    /// `// bundler:end`, so the rest of the file was left out.
+    UnterminatedSection { file: PathBuf, line: usize },
+    /// A module given to [`Bundler::rename_module`] is not in the
+    /// bundle.
+    UnusedRename { module: String },
+    /// The delimiters of a file are not balanced, at the given line, and
+    /// [`Bundler::lenient_set`] is on, so it was copied verbatim.
+    UnbalancedFile {
+        file: PathBuf,
+        line: usize,
+        problem: String,
+    },
+    /// The bundle uses a crate that is not in the
+    /// [`Bundler::allowed_crates`].
+    DisallowedCrate { name: String },
+    /// The bundle uses a feature of Rust `1.<minor>`, newer than the
+    /// [`Bundler::max_rust_version`] `1.<max>`, with no polyfill for it.
+    NewerRustFeature {
+        feature: String,
+        minor: u32,
+        max: u32,
+    },
+    /// An asset given to [`Bundler::embed_asset`] was not embedded,
+    /// because its module is not in the bundle.
+    UnembeddedAsset { name: String },
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Warning::UnresolvedImport { path } => {
+                write!(
+                    f,
+                    "import of {} refers to a module that was not bundled",
+                    path
+                )
+            }
+            Warning::SkippedModule { name } => write!(f, "module {} was not bundled", name),
+            Warning::OversizedOutput { size, limit } => {
+                write!(f, "bundle has {} bytes, limit is {}", size, limit)
+            }
+            Warning::OversizedPart { file, size, limit } => {
+                write!(
+                    f,
+                    "{}: file has {} bytes, limit is {}",
+                    file.display(),
+                    size,
+                    limit
+                )
+            }
+            Warning::UnexpandedInclude { file, line } => {
+                write!(f, "{}:{}: include! was not expanded", file.display(), line)
+            }
+            Warning::EnvMacro { file, line } => {
+                write!(
+                    f,
+                    "{}:{}: env! reads the environment of the judge",
+                    file.display(),
+                    line
+                )
+            }
+            Warning::LibraryNotInlined { crate_name } => {
+                write!(f, "library {} was not inlined", crate_name)
+            }
+            Warning::InvalidUtf8 { file, line } => {
+                write!(f, "{}:{}: invalid UTF-8 replaced", file.display(), line)
+            }
+            Warning::NoGitRevision { reason } => {
+                write!(f, "git revision not available: {}", reason)
+            }
+            Warning::OutputMismatch { input } => {
+                write!(
+                    f,
+                    "{}: the bundle and the original give different outputs",
+                    input.display()
+                )
+            }
+            Warning::VerificationFailed { reason } => {
+                write!(f, "could not verify the bundle: {}", reason)
+            }
+            Warning::VerificationTimeout {
+                input,
+                program,
+                timeout,
+            } => {
+                write!(
+                    f,
+                    "{}: the {} did not finish in {:?}",
+                    input.display(),
+                    program,
+                    timeout
+                )
+            }
+            Warning::UnusedDefine { name } => {
+                write!(f, "constant {} is not used in the bundle", name)
+            }
+            Warning::UnterminatedSection { file, line } => {
+                write!(
+                    f,
+                    "{}:{}: bundler:only-local section without bundler:end",
+                    file.display(),
+                    line
+                )
+            }
+            Warning::UnusedRename { module } => {
+                write!(f, "renamed module {} is not in the bundle", module)
+            }
+            Warning::UnbalancedFile {
+                file,
+                line,
+                problem,
+            } => {
+                write!(
+                    f,
+                    "{}:{}: {}, file copied verbatim",
+                    file.display(),
+                    line,
+                    problem
+                )
+            }
+            Warning::DisallowedCrate { name } => {
+                write!(f, "crate {} is not available in the judge", name)
+            }
+            Warning::NewerRustFeature {
+                feature,
+                minor,
+                max,
+            } => write!(
+                f,
+                "{} needs Rust 1.{}, newer than 1.{}",
+                feature, minor, max
+            ),
+            Warning::UnembeddedAsset { name } => {
+                write!(f, "asset {} not embedded, its module is not bundled", name)
+            }
+        }
+    }
+}
+
+/// Problem that prevented the bundle from being created.
+#[derive(Debug)]
+pub enum Error {
+    /// A file could not be read or written.
+    Io { path: PathBuf, error: io::Error },
+    /// A line is not valid UTF-8, and [`Bundler::strict_utf8_set`] is on.
+    InvalidUtf8 { path: PathBuf, line: usize },
+    /// The delimiters of a source file are not balanced, like in a file
+    /// that is being edited, and [`Bundler::lenient_set`] is off.
+    UnbalancedDelimiters {
+        path: PathBuf,
+        line: usize,
+        problem: String,
+    },
+    /// None of the files where a module could be was found.
+    ModuleNotFound {
+        name: String,
+        candidates: Vec<PathBuf>,
+        /// File and line of the `mod <name>;` declaration, if any.
+        declaration: Option<(PathBuf, usize)>,
+    },
+    /// A module is declared, through `#[path]`, in its own file or in
+    /// a module inside of it, so expanding it would never end.
+    ModuleCycle { name: String, file: PathBuf },
+    /// The file of a module matches `pattern`, given to
+    /// [`Bundler::ignore_file`] or in `.bundleignore`, and
+    /// [`Bundler::ignored_modules`] is [`IgnoredModules::Fail`].
+    IgnoredModule {
+        name: String,
+        file: PathBuf,
+        pattern: String,
+    },
+    /// A `mod <name>;` is declared inside an item where a module can't
+    /// be, like an `impl` block, so it has to be moved out of it.
+    MisplacedModule {
+        name: String,
+        item: String,
+        file: PathBuf,
+        line: usize,
+    },
+    /// Bundling went over a safety limit at `file`, like a chain of
+    /// `#[path]` modules that never ends or a `target` directory
+    /// bundled by mistake.
+    LimitExceeded {
+        limit: Limit,
+        max: usize,
+        file: PathBuf,
+    },
+    /// [`Bundler::check_only`] found problems that would make the
+    /// bundle wrong.
+    CheckFailed { problems: Vec<Warning> },
+    /// The bundler panicked while bundling the entry file in
+    /// [`run_all`], which is a bug.
+    Panic { bin: PathBuf },
+    /// [`Bundler::run_profile`] was called with a name that was not
+    /// given to [`Bundler::profile`].
+    UnknownProfile { name: String },
+    /// An environment variable read by [`Bundler::apply_env`] has a
+    /// value that is not valid for it.
+    InvalidEnv { name: String, value: String },
+    /// The code of the bundle matches a pattern given to
+    /// [`Bundler::forbid`], at the given line of the bundle.
+    ForbiddenPattern { pattern: String, line: usize },
+    /// Two crates given to [`MetaBundler::add_crate`] have the same
+    /// library name, so they would go in the same module.
+    DuplicateCrate { name: String, root: PathBuf },
+    /// A step given to [`Bundler::post_process`], or a built-in one,
+    /// failed with `message`.
+    PostProcess { name: String, message: String },
+    /// A name given to [`Bundler::crate_name`] or
+    /// [`Bundler::crate_aliases`] can't be the name of a crate, even
+    /// with its `-` as `_`.
+    InvalidCrateName { name: String },
+    /// The bundle doesn't parse, as found by
+    /// [`Bundler::check_syntax_set`]. `code` is the line of the bundle
+    /// with the error.
+    InvalidSyntax {
+        message: String,
+        line: usize,
+        column: usize,
+        code: String,
+    },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Io { path, error } => write!(f, "{}: {}", path.display(), error),
+            Error::InvalidUtf8 { path, line } => {
+                write!(f, "{}:{}: invalid UTF-8", path.display(), line)
+            }
+            Error::UnbalancedDelimiters {
+                path,
+                line,
+                problem,
+            } => write!(f, "{}:{}: {}", path.display(), line, problem),
+            Error::ModuleNotFound {
+                name,
+                candidates,
+                declaration,
+            } => {
+                if let Some((file, line)) = declaration {
+                    write!(f, "{}:{}: ", file.display(), line)?;
+                }
+                write!(f, "could not find file for module {}, tried", name)?;
+                for candidate in candidates {
+                    write!(f, " {}", candidate.display())?;
+                }
+                Ok(())
+            }
+            Error::ModuleCycle { name, file } => {
+                write!(
+                    f,
+                    "module {} includes its own file {}",
+                    name,
+                    file.display()
+                )
+            }
+            Error::IgnoredModule {
+                name,
+                file,
+                pattern,
+            } => write!(
+                f,
+                "{}: module {} is ignored by the pattern {}",
+                file.display(),
+                name,
+                pattern
+            ),
+            Error::MisplacedModule {
+                name,
+                item,
+                file,
+                line,
+            } => write!(
+                f,
+                "{}:{}: module {} is declared inside {}, move it out of it",
+                file.display(),
+                line,
+                name,
+                item
+            ),
+            Error::LimitExceeded { limit, max, file } => {
+                write!(f, "{}: more than {} {}", file.display(), max, limit)
+            }
+            Error::CheckFailed { problems } => {
+                write!(f, "check failed:")?;
+                for problem in problems {
+                    write!(f, "\n  {}", problem)?;
+                }
+                Ok(())
+            }
+            Error::Panic { bin } => write!(f, "{}: the bundler panicked", bin.display()),
+            Error::UnknownProfile { name } => write!(f, "unknown profile {}", name),
+            Error::InvalidEnv { name, value } => write!(f, "invalid {}: {:?}", name, value),
+            Error::ForbiddenPattern { pattern, line } => {
+                write!(f, "bundle:{}: forbidden pattern {}", line, pattern)
+            }
+            Error::InvalidCrateName { name } => write!(f, "invalid crate name {:?}", name),
+            Error::PostProcess { name, message } => {
+                write!(f, "post-processor {} failed: {}", name, message)
+            }
+            Error::DuplicateCrate { name, root } => write!(
+                f,
+                "{}: another crate is already bundled as the module {}",
+                root.display(),
+                name
+            ),
+            Error::InvalidSyntax {
+                message,
+                line,
+                column,
+                ..
+            } => write!(f, "bundle:{}:{}: {}", line, column, message),
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Error::Io { error, .. } => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl Error {
+    /// The error as rustc shows them, with the line of the source file
+    /// that it's about, if any, to print in build scripts and tools.
+    pub fn diagnostic(&self) -> String {
+        match self {
+            Error::InvalidUtf8 { path, line } => {
+                diagnostic::render("invalid UTF-8", Some((path, *line)), &[])
+            }
+            Error::UnbalancedDelimiters {
+                path,
+                line,
+                problem,
+            } => diagnostic::render(problem, Some((path, *line)), &[]),
+            Error::MisplacedModule {
+                name,
+                item,
+                file,
+                line,
+            } => diagnostic::render(
+                &format!("module {} is declared inside {}", name, item),
+                Some((file, *line)),
+                &[format!("move `mod {};` out of {}", name, item)],
+            ),
+            Error::ModuleNotFound {
+                name,
+                candidates,
+                declaration,
+            } => {
+                let tried: Vec<String> = candidates
+                    .iter()
+                    .map(|candidate| candidate.display().to_string())
+                    .collect();
+                diagnostic::render(
+                    &format!("could not find file for module {}", name),
+                    declaration
+                        .as_ref()
+                        .map(|(file, line)| (file.as_path(), *line)),
+                    &[format!("tried {}", tried.join(" "))],
+                )
+            }
+            Error::InvalidSyntax {
+                message,
+                line,
+                column,
+                code,
+            } => diagnostic::render_source(
+                message,
+                &format!("bundle:{}:{}", line, column),
+                *line,
+                Some(*column),
+                Some(code),
+                &[],
+            ),
+            _ => diagnostic::render(&self.to_string(), None, &[]),
+        }
+    }
+
+    /// Sets where the module of a [`Error::ModuleNotFound`] is declared,
+    /// if it's not set yet by a nested module.
+    fn declared_at(mut self, file: &Path, line: usize) -> Error {
+        if let Error::ModuleNotFound {
+            ref mut declaration,
+            ..
+        } = self
+        {
+            declaration.get_or_insert_with(|| (file.to_path_buf(), line));
+        }
+        self
+    }
+}
+
+/// Step of a bundler run, given to [`Bundler::on_progress`] as soon as
+/// it happens.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProgressEvent {
+    /// A source file was opened, including the files of `include!`.
+    FileOpened { path: PathBuf },
+    /// A module, like `graph::flow`, is being expanded from `file`.
+    ModuleExpanded { path: String, file: PathBuf },
+    /// A file of the output was written.
+    BytesWritten { path: PathBuf, bytes: usize },
+}
+
+/// Summary of a bundler run.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Report {
+    /// Warnings, in the order they were found.
+    pub warnings: Vec<Warning>,
+    /// Size of the bundle in bytes.
+    pub size: usize,
+    /// Size of the minified copy, with [`Bundler::emit_minified`].
+    pub minified_size: Option<usize>,
+    /// Minification level used, which can be higher than the
+    /// configured one with [`Bundler::minify_auto_set`].
+    pub minify_level: u8,
+    /// Private items removed with [`Bundler::public_api_only_set`].
+    pub pruned_items: Vec<String>,
+    /// Items removed because the [`Bundler::judge_profile`] doesn't
+    /// compile them.
+    pub profile_items: usize,
+    /// Items removed because their feature is not enabled, see
+    /// [`Bundler::enable_feature`].
+    pub feature_items: usize,
+    /// Items removed with [`Bundler::exclude_item`] or a
+    /// `// bundler:skip-item` line.
+    pub excluded_items: Vec<String>,
+    /// File of each generated `mod <name> {` wrapper, by module path,
+    /// like `graph::flow`.
+    pub module_map: BTreeMap<String, PathBuf>,
+    /// Files of the bundle split with [`Bundler::split_output`], the
+    /// bundle first.
+    pub parts: Vec<PathBuf>,
+    /// Runs of the programs compared with [`Bundler::verify_input`],
+    /// with their output.
+    pub verify_runs: Vec<VerifyRun>,
+    /// Source files read, each once, and the roots given to
+    /// [`Bundler::add_source_root`], to print a
+    /// `cargo:rerun-if-changed` line for each in build.rs.
+    pub sources: Vec<PathBuf>,
+}
+
+impl Report {
+    /// The report as a JSON object, for editor integrations.
+    pub fn to_json(&self) -> String {
+        let strings = |values: &[String]| json::array(values.iter().map(|v| json::string(v)));
+        let warnings: Vec<String> = self.warnings.iter().map(Warning::to_string).collect();
+        json::object(&[
+            ("size", self.size.to_string()),
+            (
+                "minified_size",
+                self.minified_size
+                    .map_or_else(|| "null".to_string(), |size| size.to_string()),
+            ),
+            ("minify_level", self.minify_level.to_string()),
+            ("warnings", strings(&warnings)),
+            ("pruned_items", strings(&self.pruned_items)),
+            ("profile_items", self.profile_items.to_string()),
+            ("feature_items", self.feature_items.to_string()),
+            ("excluded_items", strings(&self.excluded_items)),
+            (
+                "parts",
+                json::array(self.parts.iter().map(|part| json::path(part))),
+            ),
+            (
+                "sources",
+                json::array(self.sources.iter().map(|source| json::path(source))),
+            ),
+            (
+                "module_map",
+                json::object_of(
+                    self.module_map
+                        .iter()
+                        .map(|(module, file)| (module.as_str(), json::path(file))),
+                ),
+            ),
+            (
+                "verify_runs",
+                json::array(self.verify_runs.iter().map(|run| {
+                    json::object(&[
+                        ("input", json::path(&run.input)),
+                        ("program", json::string(run.program)),
+                        ("stdout", json::string(&run.stdout)),
+                        ("stderr", json::string(&run.stderr)),
+                        ("timed_out", run.timed_out.to_string()),
+                    ])
+                })),
+            ),
+        ])
+    }
+}
+
+/// Rust edition of the crate, which changes how the entry file
+/// references the library.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Edition {
+    #[cfg_attr(feature = "serde", serde(rename = "2015"))]
+    Rust2015,
+    #[cfg_attr(feature = "serde", serde(rename = "2018"))]
+    Rust2018,
+    #[cfg_attr(feature = "serde", serde(rename = "2021"))]
+    Rust2021,
+    #[cfg_attr(feature = "serde", serde(rename = "2024"))]
+    Rust2024,
+}
+
+impl Edition {
+    /// Parses the value of the `edition` key of Cargo.toml.
+    pub fn from_year(year: &str) -> Option<Edition> {
+        match year {
+            "2015" => Some(Edition::Rust2015),
+            "2018" => Some(Edition::Rust2018),
+            "2021" => Some(Edition::Rust2021),
+            "2024" => Some(Edition::Rust2024),
+            _ => None,
+        }
+    }
+
+    /// The value of the `edition` key of Cargo.toml.
+    pub fn year(&self) -> &'static str {
+        match self {
+            Edition::Rust2015 => "2015",
+            Edition::Rust2018 => "2018",
+            Edition::Rust2021 => "2021",
+            Edition::Rust2024 => "2024",
+        }
+    }
+}
+
+/// A safety limit of the bundler, see [`Bundler::max_depth`],
+/// [`Bundler::max_files`] and [`Bundler::max_output_size`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Limit {
+    /// Modules expanded inside each other.
+    Depth,
+    /// Source files read.
+    Files,
+    /// Bytes of the bundle.
+    OutputSize,
+}
+
+impl fmt::Display for Limit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Limit::Depth => write!(f, "nested modules"),
+            Limit::Files => write!(f, "source files"),
+            Limit::OutputSize => write!(f, "bytes of output"),
+        }
+    }
+}
+
+/// Visibility of the `mod <name> {` wrappers generated for the modules.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Visibility {
+    /// `pub mod`, the default.
+    #[default]
+    Pub,
+    /// `pub(crate) mod`
+    PubCrate,
+    /// `mod` for the modules at the root of the bundle, which the whole
+    /// crate can use. Nested modules keep the visibility they are
+    /// declared with, for paths like `crate::graph::flow::Dinic`.
+    Private,
+    /// Same visibility as the expanded `mod <name>;` declaration.
+    AsDeclared,
+}
+
+/// What to do with the inner doc attributes that include a file, like
+/// `#![doc = include_str!("../README.md")]`, whose path would be wrong
+/// in the bundle, see [`Bundler::doc_includes`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum DocIncludes {
+    /// Remove them, the default.
+    #[default]
+    Strip,
+    /// Replace the `include_str!` with the text of the file. The docs
+    /// of the library or of the entry file go to the top of the bundle,
+    /// where crate attributes have to be. Conditional ones, like
+    /// `#![cfg_attr(docsrs, doc = ...)]`, are removed.
+    Inline,
+}
+
+/// What to do with the modules whose file is ignored, see
+/// [`Bundler::ignore_file`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum IgnoredModules {
+    /// Fail with [`Error::IgnoredModule`], the default.
+    #[default]
+    Fail,
+    /// Bundle an empty module instead, for declarations that are only
+    /// used by code that is not compiled on the judge. Its wrapper has
+    /// the visibility of the others, see [`Bundler::wrapper_visibility`].
+    Stub,
+}
+
+/// Position in the bundle for the code given to
+/// [`Bundler::insert_raw`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum BundleAnchor {
+    /// Start of the bundle, after the inner attributes that have to
+    /// be first.
+    FileStart,
+    /// Right before the code of the library, after its inner
+    /// attributes.
+    BeforeLib,
+    /// Right after the code of the library.
+    AfterLib,
+    /// End of the bundle.
+    FileEnd,
+}
+
+/// What to do with the inner attributes of the entry file, like
+/// `#![allow(clippy::needless_range_loop)]`, see
+/// [`Bundler::entry_inner_attributes`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum InnerAttributes {
+    /// Leave them where they are, the default.
+    #[default]
+    Keep,
+    /// Move them to the top of the bundle, where they have to be.
+    Top,
+    /// Remove them.
+    Strip,
+}
+
+/// How to keep rustfmt from formatting the bundle, see
+/// [`Bundler::rustfmt_skip`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum RustfmtSkip {
+    /// Let it be formatted, the default.
+    #[default]
+    Off,
+    /// `#[rustfmt::skip]` on the wrappers of the modules at the root.
+    Wrappers,
+    /// `#![cfg_attr(rustfmt, rustfmt::skip)]` at the top of the bundle,
+    /// for the whole file.
+    File,
+}
+
+/// Fast input and output module added to the bundle with
+/// [`Bundler::inject_fastio`]. Both have a `fastio::Scanner` with
+/// `new()`, `next::<T>()` and `vec::<T>(n)`, and a `fastio::writer()`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum FastIoKind {
+    /// Reads the whole input at once, and writes the output at the end.
+    Scanner,
+    /// Reads a line at a time, and writes each line of output as soon
+    /// as it's complete, for interactive problems.
+    Interactive,
+}
+
+/// How the judge builds the submissions, which decides if the
+/// `#[cfg(debug_assertions)]` items are compiled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum BuildProfile {
+    /// Debug assertions on, `#[cfg(not(debug_assertions))]` items are
+    /// removed.
+    Debug,
+    /// Debug assertions off, `#[cfg(debug_assertions)]` items are
+    /// removed.
+    Release,
+}
+
+/// Function called with each warning as soon as it is found.
+#[derive(Clone)]
+struct WarningCallback<'a>(Arc<dyn Fn(&Warning) + Send + Sync + 'a>);
+
+impl fmt::Debug for WarningCallback<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("WarningCallback")
+    }
+}
+
+/// Step given to [`Bundler::post_process`].
+#[derive(Clone)]
+struct PostProcessorRef<'a>(Arc<dyn PostProcessor + 'a>);
+
+impl fmt::Debug for PostProcessorRef<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "PostProcessor({})", self.0.name())
+    }
+}
+
+/// Function called with each step of a run.
+#[derive(Clone)]
+struct ProgressCallback<'a>(Arc<dyn Fn(ProgressEvent) + Send + Sync + 'a>);
+
+impl fmt::Debug for ProgressCallback<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("ProgressCallback")
+    }
+}
+
+/// Named set of changes to the configuration, see [`Bundler::profile`].
+#[derive(Clone)]
+struct Profile<'a> {
+    name: &'a str,
+    configure: Arc<dyn Fn(&mut Bundler<'a>) + Send + Sync + 'a>,
+}
+
+impl fmt::Debug for Profile<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Profile({:?})", self.name)
+    }
+}
+
+/// Rule that rewrites lines of the entry file.
+#[derive(Clone)]
+enum Rewrite<'a> {
+    Regex(Regex, String),
+    Callback(Arc<dyn Fn(&str) -> String + Send + Sync + 'a>),
+}
+
+impl fmt::Debug for Rewrite<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Rewrite::Regex(re, replacement) => write!(f, "Regex({:?}, {:?})", re, replacement),
+            Rewrite::Callback(_) => f.write_str("Callback"),
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Bundler<'a> {
-    binrs_filename: &'a Path,
-    bundle_filename: &'a Path,
-    librs_filename: &'a Path,
+    binrs_filename: Cow<'a, Path>,
+    bundle_filename: Cow<'a, Path>,
+    librs_filename: Cow<'a, Path>,
     comment_re: Regex,
     warn_re: Regex,
-    _crate_name: &'a str,
-    skip_use: HashSet<String>,
-    minify_re: Option<Regex>,
+    include_re: Regex,
+    env_re: Regex,
+    macro_export_re: Regex,
+    macro_rules_re: Regex,
+    attribute_re: Regex,
+    outer_doc_re: Regex,
+    innerattr_re: Regex,
+    doc_include_re: Regex,
+    insert_lib_re: Regex,
+    only_local_re: Regex,
+    only_bundle_re: Regex,
+    section_end_re: Regex,
+    skip_item_re: Regex,
+    no_std_re: Regex,
+    extern_re: Regex,
+    pub_use_re: Regex,
+    use_re: Regex,
+    path_re: Regex,
+    include_file_re: Regex,
+    /// `mod <m>;` lines. The `m` and `attrs` groups are not optional,
+    /// so they are in every match.
+    mod_re: Regex,
+    _crate_name: Cow<'a, str>,
+    crate_aliases: Vec<&'a str>,
+    edition: Option<Edition>,
+    minify_level: u8,
+    minify_auto: bool,
+    max_blank_lines: Option<usize>,
+    minified_suffix: Option<&'a str>,
     skip_mod: HashSet<&'a str>,
+    source_roots: Vec<&'a Path>,
+    allow_lints: Vec<&'a str>,
+    allow_unused: bool,
     strip_comments: bool,
+    trim_trailing_whitespace: bool,
+    strip_indentation: bool,
+    strict_utf8: bool,
+    lenient: bool,
+    explicit_globs: bool,
+    hoist_macros: bool,
+    git_header: bool,
+    contest_header: bool,
+    header_fields: Vec<(&'a str, &'a str)>,
+    table_of_contents: bool,
+    integrity_footer: bool,
+    source_markers: bool,
+    wrapper_visibility: Visibility,
+    entry_inner_attributes: InnerAttributes,
+    rustfmt_skip: RustfmtSkip,
+    doc_includes: DocIncludes,
+    size_limit: Option<usize>,
+    max_depth: usize,
+    max_files: usize,
+    max_output_size: usize,
+    split_limit: Option<usize>,
+    on_warning: Option<WarningCallback<'a>>,
+    on_progress: Option<ProgressCallback<'a>>,
+    rewrites: Vec<Rewrite<'a>>,
+    post_processors: Vec<PostProcessorRef<'a>>,
+    provenance_filename: Option<&'a Path>,
+    public_api_only: bool,
+    kept_comments: Vec<Regex>,
+    prefix_comments: usize,
+    forced_mods: Vec<(&'a str, &'a Path)>,
+    assets: Vec<(&'a Path, &'a str)>,
+    fastio: Option<FastIoKind>,
+    profiles: Vec<Profile<'a>>,
+    judge_profile: Option<BuildProfile>,
+    features: Vec<&'a str>,
+    verify_inputs: Vec<&'a Path>,
+    verify_timeouts: (Duration, Duration),
+    library_only: bool,
+    excluded_items: Vec<&'a str>,
+    defines: Vec<(&'a str, &'a str)>,
+    renamed_modules: Vec<(&'a str, &'a str)>,
+    collect_licenses: bool,
+    forbidden: Vec<Regex>,
+    dropped_attributes: Vec<&'a str>,
+    ignored_files: Vec<&'a str>,
+    ignored_modules: IgnoredModules,
+    stripped_derives: Vec<&'a str>,
+    raw_chunks: Vec<(BundleAnchor, &'a str)>,
+    appended_files: Vec<(&'a Path, Option<&'a str>)>,
+    max_rust_version: Option<u32>,
+    polyfills: Vec<(&'a str, &'a str)>,
+    check_syntax: bool,
+    allowed_crates: Option<Vec<&'a str>>,
+}
+
+/// State of a single run of a [`Bundler`], which only reads the
+/// configuration, so that it can run many times, even in parallel.
+struct Run<'r, 'a> {
+    config: &'r Bundler<'a>,
+    /// Paths of the expanded modules, like `graph::flow`.
+    modules: HashSet<String>,
+    warnings: Vec<Warning>,
+    skipped_mods: HashSet<String>,
+    /// Canonical paths of the files of the plan.
+    opened_files: HashSet<PathBuf>,
+    root_extern_crates: HashSet<String>,
+    reexports: HashMap<String, Reexports>,
+    /// Names the entry file imports one by one, which the imports that
+    /// replace its glob imports leave out, so that they don't clash.
+    entry_imports: HashSet<String>,
+    /// Names for [`Bundler::explicit_globs_set`], found once the
+    /// library is in the bundle.
+    glob_names: Option<globs::GlobNames>,
+    ignore_rules: IgnoreRules,
+    /// Offset in the bundle and path of the wrapper of each module, for
+    /// [`Bundler::allow_unused_set`].
+    wrappers: Vec<(usize, String)>,
+    lib_inlined: bool,
+    hoist: MacroHoist,
+    plan: BundlePlan,
+    used_minify_level: u8,
+    pruned_items: Vec<String>,
+    profile_items: usize,
+    feature_items: usize,
+    excluded_items: Vec<String>,
+    verify_runs: Vec<VerifyRun>,
+    parts: Vec<PathBuf>,
+    /// Inner attributes of the entry file moved to the top.
+    top_attributes: String,
+    /// End of the comments kept at the top of the bundle.
+    prefix_end: usize,
+    minified: Option<Vec<u8>>,
+    edition: Edition,
+    /// Indexes in `plan.modules` of the modules being expanded.
+    module_stack: Vec<usize>,
+    /// Paths imported by the entry file, relative to the library.
+    bin_paths: Vec<String>,
+    /// Lines of the entry file that are not imports.
+    bin_code: String,
+    /// Source of the entry file given to [`Bundler::bundle_snippet`].
+    entry_source: Option<String>,
+    /// Start of a `use <crate>::` line in a module, like one of the
+    /// entry file importing a macro of the library.
+    crate_use_re: Regex,
+}
+
+/// State of the extraction of `#[macro_export]` macros, that are moved
+/// to the top of the bundle so that they are defined before any use.
+#[derive(Debug, Clone, Default)]
+struct MacroHoist {
+    /// Where the hoisted macros go in the bundle.
+    position: Option<usize>,
+    /// Hoisted macro definitions, already in output format.
+    macros: Vec<u8>,
+    /// Attribute lines seen since `#[macro_export]`.
+    pending: Vec<String>,
+    /// Delimiter depth of the macro being hoisted, if any.
+    depth: Option<i32>,
+    /// Whether the macro being hoisted opened its body.
+    opened: bool,
+}
+
+/// `pub use` lines of an expanded module, to replace glob imports of
+/// it in the entry file, like `use <crate>::prelude::*;`, with the
+/// imports of the items themselves.
+#[derive(Debug, Clone, Default)]
+struct Reexports {
+    /// Paths of the `pub use` lines, as written in the module.
+    paths: Vec<String>,
+    /// Whether the module has anything else the glob would import.
+    other_items: bool,
+}
+
+/// Inner attribute with an `include_str!` being read, see
+/// [`Bundler::doc_includes`].
+#[derive(Debug, Clone, Default)]
+struct DocInclude {
+    text: String,
+    /// Delimiter depth at the end of the last line read.
+    depth: i32,
+}
+
+/// Outer attributes and doc comments read before an item, held until
+/// it's known whether the item is a `mod <name>;` declaration: they go
+/// on the wrapper of an expanded module, and are dropped with an
+/// excluded one.
+#[derive(Debug, Clone, Default)]
+struct HeldAttrs {
+    /// Lines, with their file and number.
+    lines: Vec<(String, PathBuf, usize)>,
+    /// Delimiters left open by an attribute that spans lines.
+    depth: i32,
+}
+
+/// Flattens the group of paths in a `use`, like `a::{self, b::{C, D}}`
+/// into `a`, `a::b::C` and `a::b::D`.
+fn split_use_group(path: &str) -> Vec<String> {
+    let path = path.trim();
+    let (prefix, group) = match path.find('{') {
+        Some(open) if path.ends_with('}') => (&path[..open], &path[open + 1..path.len() - 1]),
+        _ => return vec![path.to_string()],
+    };
+    let mut items = Vec::new();
+    let mut depth = 0;
+    let mut start = 0;
+    for (i, c) in group.char_indices() {
+        match c {
+            '{' => depth += 1,
+            '}' => depth -= 1,
+            ',' if depth == 0 => {
+                items.push(&group[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    items.push(&group[start..]);
+    let mut paths = Vec::new();
+    for item in items.into_iter().map(str::trim).filter(|i| !i.is_empty()) {
+        for path in split_use_group(item) {
+            if path == "self" {
+                paths.push(prefix.trim_end_matches("::").to_string());
+            } else {
+                paths.push(format!("{}{}", prefix, path));
+            }
+        }
+    }
+    paths.retain(|p| !p.is_empty());
+    paths
+}
+
+/// Name that a path of a `use` brings in scope, its alias or its last
+/// segment.
+fn use_name(path: &str) -> &str {
+    match path.rsplit_once(" as ") {
+        Some((_, alias)) => alias.trim(),
+        None => path.rsplit("::").next().unwrap_or(path).trim(),
+    }
+}
+
+/// Names that the `use` declarations of `source` import one by one,
+/// not with a glob.
+fn explicit_imports(source: &str) -> HashSet<String> {
+    let use_re = Regex::new(r"(?m)^\s*(?:pub(?:\s*\([^)]*\))?\s+)?use\s+(?P<path>[^;]*);").unwrap();
+    use_re
+        .captures_iter(source)
+        .flat_map(|cap| split_use_group(&cap["path"]))
+        .map(|path| use_name(&path).to_string())
+        .filter(|name| name != "*" && name != "_")
+        .collect()
+}
+
+/// Files that can have the module at `path`, without the `.rs`.
+fn module_files(path: &Path) -> [PathBuf; 2] {
+    let mut file = path.as_os_str().to_owned();
+    file.push(".rs");
+    [PathBuf::from(file), path.join("mod.rs")]
+}
+
+/// Fail if the declaration of the module `name`, at `line` of `file`,
+/// is inside `item`, as found by [`Nesting::item`], where the wrapper
+/// of the module can't go.
+fn check_mod_place(name: &str, item: Option<&str>, file: &Path, line: usize) -> Result<(), Error> {
+    match item {
+        Some(item) => Err(Error::MisplacedModule {
+            name: name.to_string(),
+            item: item.to_string(),
+            file: file.to_path_buf(),
+            line,
+        }),
+        None => Ok(()),
+    }
+}
+
+/// The comment at the top of a source file, if it mentions a license
+/// or a copyright.
+fn license_header(contents: &str) -> Option<String> {
+    let license_re =
+        Regex::new(r"(?i)\b(licen[sc]e[ds]?|copyright|SPDX-License-Identifier)\b").unwrap();
+    let mut header = String::new();
+    let mut in_block = false;
+    for line in contents.trim_start_matches('\u{FEFF}').lines() {
+        let text = line.trim();
+        let inner_doc = text.starts_with("//!") || text.starts_with("/*!");
+        if in_block || (text.starts_with("/*") && !inner_doc) {
+            in_block = !text.contains("*/");
+        } else if !text.starts_with("//") || inner_doc {
+            if text.is_empty() && header.is_empty() {
+                continue;
+            }
+            break;
+        }
+        header.push_str(line.trim_end());
+        header.push('\n');
+    }
+    license_re.is_match(&header).then_some(header)
+}
+
+/// The `//!` lines at the top of the file at `path`, and the number of
+/// the last one, 0 if there are none.
+fn leading_inner_docs(path: &Path) -> (Vec<String>, usize) {
+    let contents = fs::read(path).unwrap_or_default();
+    let mut docs = Vec::new();
+    let mut last = 0;
+    for (i, line) in String::from_utf8_lossy(&contents).lines().enumerate() {
+        let line = line.trim_start_matches('\u{FEFF}');
+        if line.trim_start().starts_with("//!") {
+            docs.push(line.trim_end().to_string());
+            last = i + 1;
+        } else if !line.trim().is_empty() {
+            break;
+        }
+    }
+    (docs, last)
+}
+
+/// The end of the inner attributes and inner docs that follow
+/// `position` in `bundle`, like the ones of lib.rs when it's inlined
+/// there, with only blank and comment lines between them, or
+/// `position` if there are none. Code put at `position` has to go
+/// after them, as they are not allowed after an item.
+fn after_inner_attributes(bundle: &[u8], position: usize) -> usize {
+    let text = String::from_utf8_lossy(&bundle[position..]);
+    let mut lexer = Lexer::new();
+    let mut end = position;
+    let mut offset = position;
+    let mut depth = 0;
+    let mut inner_comment = false;
+    for line in text.split_inclusive('\n') {
+        offset += line.len();
+        let scan = lexer.scan(line);
+        let code = line.trim();
+        if !scan.starts_in_code() {
+            if inner_comment {
+                end = offset;
+            }
+        } else if depth > 0 {
+            depth += scan.delta;
+            end = offset;
+        } else if code.starts_with("#![") {
+            depth = scan.delta;
+            end = offset;
+        } else if code.starts_with("//!") || code.starts_with("/*!") {
+            inner_comment = true;
+            end = offset;
+        } else if code.is_empty() || code.starts_with("//") || code.starts_with("/*") {
+            inner_comment = false;
+        } else {
+            break;
+        }
+    }
+    end
+}
+
+/// Removes the line terminator, LF or CRLF.
+fn trim_line(line: &mut String) {
+    if line.ends_with('\n') {
+        line.pop();
+    }
+    if line.ends_with('\r') {
+        line.pop();
+    }
+}
+
+/// Defines a regex to match a line of rust source, which can end in
+/// `/* ... */` and `//` comments.
+/// Uses a shorthand where "  " = "\s+" and " " = "\s*", both of which
+/// match tabs too.
+/// Whether `name` is an identifier, like the names of crates.
+fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars.next().is_some_and(|c| c.is_alphabetic() || c == '_')
+        && chars.all(|c| c.is_alphanumeric() || c == '_')
+        && name != "_"
 }
 
-/// Defines a regex to match a line of rust source.
-/// Uses a shorthand where "  " = "\s+" and " " = "\s*"
 fn source_line_regex<S: AsRef<str>>(source_regex: S) -> Regex {
     Regex::new(
         format!(
-            "^{}(?://.*)?$",
+            r"^{}(?:/\*.*\*/\s*)*(?://.*)?$",
             source_regex
                 .as_ref()
                 .replace("  ", r"\s+")
@@ -57,172 +1307,6245 @@ impl<'a> Bundler<'a> {
         bundle_filename: &'a Path,
         librs_filename: &'a Path,
     ) -> Bundler<'a> {
-        let mut skip_use = HashSet::new();
-        skip_use.insert("*".to_string());
         let mut skip_mod = HashSet::new();
         skip_mod.insert("tests");
         Bundler {
-            binrs_filename,
-            bundle_filename,
-            librs_filename,
+            binrs_filename: Cow::Borrowed(binrs_filename),
+            bundle_filename: Cow::Borrowed(bundle_filename),
+            librs_filename: Cow::Borrowed(librs_filename),
             comment_re: source_line_regex(r" "),
             warn_re: source_line_regex(r" #!\[warn\(.*"),
-            _crate_name: "",
-            skip_use,
-            minify_re: None,
+            include_re: Regex::new(r"\binclude!").unwrap(),
+            env_re: Regex::new(r"\b(option_)?env!").unwrap(),
+            macro_export_re: Regex::new(r"^\s*#\[macro_export\]\s*(?P<rest>.*)$").unwrap(),
+            macro_rules_re: Regex::new(r"^\s*macro_rules!").unwrap(),
+            attribute_re: Regex::new(r"^\s*#\[").unwrap(),
+            outer_doc_re: Regex::new(r"^\s*///([^/]|$)").unwrap(),
+            innerattr_re: Regex::new(r"^\s*#!\[").unwrap(),
+            doc_include_re: source_line_regex(
+                r#" #!\[ doc = include_str! \( "(?P<path>[^"]*)" \) \] "#,
+            ),
+            insert_lib_re: Regex::new(r"^\s*//\s*bundler:(insert|inline)-lib\s*$").unwrap(),
+            only_local_re: Regex::new(r"^\s*//\s*bundler:only-local\s*$").unwrap(),
+            only_bundle_re: Regex::new(r"^(?P<indent>\s*)//\s*bundler:only-bundle: ?(?P<code>.*)$")
+                .unwrap(),
+            section_end_re: Regex::new(r"^\s*//\s*bundler:end\s*$").unwrap(),
+            skip_item_re: Regex::new(
+                r"^\s*(//\s*bundler:skip-item\s*$|#\[\s*cfg_attr\s*\(\s*sourcebundle\s*,\s*skip\s*\)\s*\])",
+            )
+            .unwrap(),
+            no_std_re: source_line_regex(r" #!\[ (no_std|cfg_attr\(.*\bno_std ?\)) \] "),
+            extern_re: source_line_regex(r" extern  crate  (?P<c>\w+( as \w+)?) ; "),
+            pub_use_re: source_line_regex(r" pub( \( [^)]+ \) |  )use  (?P<path>.+) ; "),
+            use_re: source_line_regex(r" (pub( \( [^)]+ \) |  ))?use  .+ ; "),
+            path_re: source_line_regex(r#" #\[ path = "(?P<p>[^"]+)" \] "#),
+            include_file_re: source_line_regex(
+                r#" (?P<head>(pub( \( [^)]+ \) |  ))?mod  \w+ \{ )?include! \( "(?P<f>[^"]+)" \) ;? (?P<close>\} )?"#,
+            ),
+            mod_re: source_line_regex(
+                r" (?P<attrs>(#\[[^\]]*\] )*)(?P<vis>pub( \( [^)]+ \) |  ))?mod  (?P<m>\w+) ; ",
+            ),
+            _crate_name: Cow::Borrowed(""),
+            crate_aliases: Vec::new(),
+            edition: None,
+            minify_level: 0,
+            minify_auto: false,
+            max_blank_lines: None,
+            minified_suffix: None,
             skip_mod,
+            source_roots: Vec::new(),
+            allow_lints: Vec::new(),
+            allow_unused: false,
             strip_comments: true,
+            trim_trailing_whitespace: true,
+            strip_indentation: false,
+            strict_utf8: false,
+            lenient: false,
+            explicit_globs: false,
+            hoist_macros: true,
+            git_header: false,
+            contest_header: false,
+            header_fields: Vec::new(),
+            table_of_contents: false,
+            integrity_footer: false,
+            source_markers: false,
+            wrapper_visibility: Visibility::Pub,
+            entry_inner_attributes: InnerAttributes::Keep,
+            rustfmt_skip: RustfmtSkip::Off,
+            doc_includes: DocIncludes::Strip,
+            size_limit: None,
+            max_depth: 64,
+            max_files: 10_000,
+            max_output_size: 64 << 20,
+            split_limit: None,
+            on_warning: None,
+            on_progress: None,
+            rewrites: Vec::new(),
+            post_processors: Vec::new(),
+            provenance_filename: None,
+            public_api_only: false,
+            kept_comments: Vec::new(),
+            prefix_comments: 0,
+            forced_mods: Vec::new(),
+            assets: Vec::new(),
+            fastio: None,
+            profiles: Vec::new(),
+            judge_profile: None,
+            features: Vec::new(),
+            verify_inputs: Vec::new(),
+            verify_timeouts: (Duration::from_secs(300), Duration::from_secs(10)),
+            library_only: false,
+            excluded_items: Vec::new(),
+            defines: Vec::new(),
+            renamed_modules: Vec::new(),
+            collect_licenses: false,
+            forbidden: Vec::new(),
+            dropped_attributes: Vec::new(),
+            ignored_files: Vec::new(),
+            ignored_modules: IgnoredModules::Fail,
+            stripped_derives: Vec::new(),
+            raw_chunks: Vec::new(),
+            appended_files: Vec::new(),
+            max_rust_version: None,
+            polyfills: Vec::new(),
+            check_syntax: false,
+            allowed_crates: None,
+        }
+    }
+
+    /// Bundler for the common layout of a single package at `root`,
+    /// with both `src/lib.rs` and `src/main.rs`. The crate name is the
+    /// library name of Cargo.toml, the edition is read from it as
+    /// usual, and the bundle goes to `bundle.rs` in `root`, which
+    /// [`Bundler::bundle_file`] can change.
+    pub fn for_single_package(root: &'a Path) -> Result<Bundler<'a>, Error> {
+        let manifest_filename = root.join("Cargo.toml");
+        let manifest = Manifest::read(&manifest_filename).map_err(|error| Error::Io {
+            path: manifest_filename,
+            error,
+        })?;
+        let mut bundler = Bundler::new(Path::new(""), Path::new(""));
+        bundler.binrs_filename = Cow::Owned(root.join("src").join("main.rs"));
+        bundler.librs_filename = Cow::Owned(root.join(LIBRS_FILENAME));
+        bundler.bundle_filename = Cow::Owned(root.join("bundle.rs"));
+        if let Some(name) = manifest.lib_names().into_iter().next() {
+            bundler._crate_name = Cow::Owned(name);
         }
+        Ok(bundler)
     }
 
+    /// Don't bundle the module `mod_name`, like `graph`, or a submodule
+    /// given with its path, like `graph::matching`, keeping the rest of
+    /// `graph`.
     pub fn exclude_mod(&mut self, mod_name: &'a str) {
         self.skip_mod.insert(mod_name);
     }
 
+    /// Also look for the files of modules in `root`, like
+    /// `../common/src`, when they are not next to the file that
+    /// declares them: `mod geom;` in the entry file can be in
+    /// `root/geom.rs`, and `mod a;` in `util.rs` in `root/util/a.rs`.
+    pub fn add_source_root(&mut self, root: &'a Path) {
+        self.source_roots.push(root);
+    }
+
+    /// Don't bundle the item at `path`, like `math::PRIMES` for a big
+    /// precomputed table, nor the `impl` blocks of it in the same
+    /// module. Items can also be excluded with a `// bundler:skip-item`
+    /// line before them, and items and modules with a
+    /// `#[cfg_attr(sourcebundle, skip)]` attribute, which does nothing
+    /// when compiling. Can be called several times.
+    pub fn exclude_item(&mut self, path: &'a str) {
+        self.excluded_items.push(path);
+    }
+
+    /// Put an `#[allow(<lint>)]` on the modules of the library, e.g. with
+    /// `clippy::all` for judges that run clippy on the submissions. Can
+    /// be called several times.
+    pub fn allow_lint(&mut self, lint: &'a str) {
+        self.allow_lints.push(lint);
+    }
+
+    /// Put an `#[allow(unused)]` on the modules of the library that the
+    /// entry file doesn't import or name, and not on the others, so
+    /// that the warnings left are about the code the solution uses.
+    pub fn allow_unused_set(&mut self, enable: bool) {
+        self.allow_unused = enable;
+    }
+
+    /// Bundle the module `name` from `file` even if lib.rs doesn't
+    /// declare it, as `pub mod <name>` at the end of the library.
+    pub fn force_include_mod(&mut self, name: &'a str, file: &'a Path) {
+        self.forced_mods.push((name, file));
+    }
+
+    /// Embed the text file at `file`, like a grammar or an SQL schema
+    /// that the crate normally reads at build time, as a
+    /// `pub const <NAME>: &str` at `name`. `name` is a path like
+    /// `parser::GRAMMAR` to put it at the end of that module, or just
+    /// `GRAMMAR` for the root of the bundle.
+    pub fn embed_asset(&mut self, file: &'a Path, name: &'a str) {
+        self.assets.push((file, name));
+    }
+
+    /// Add a `fastio` module of the given kind at the end of the
+    /// bundle, unless the bundle already has one, like the local copy
+    /// of the template that the entry file builds with.
+    pub fn inject_fastio(&mut self, kind: FastIoKind) {
+        self.fastio = Some(kind);
+    }
+
+    /// Same as [`Bundler::minify_level`] 1 if enabled, 0 otherwise.
     pub fn minify_set(&mut self, enable: bool) {
-        self.minify_re = if enable {
-            Some(Regex::new(r"^\s*(?P<contents>.*)\s*$").unwrap())
-        } else {
-            None
-        };
+        self.minify_level(u8::from(enable));
+    }
+
+    /// Set how much the bundle is minified, from 0 to 3:
+    ///
+    /// 0. nothing, the default;
+    /// 1. trim the whitespace around lines of code;
+    /// 2. also remove blank lines and join lines like `}` to the
+    ///    previous one;
+    /// 3. also remove the whitespace between tokens and join all lines
+    ///    of code that don't end in a comment.
+    ///
+    /// Literals are never touched.
+    pub fn minify_level(&mut self, level: u8) {
+        self.minify_level = level.min(minify::MAX_LEVEL);
+    }
+
+    /// Increase the minification level, up to 3, until the bundle fits
+    /// in the [`Bundler::size_limit`].
+    pub fn minify_auto_set(&mut self, enable: bool) {
+        self.minify_auto = enable;
     }
 
     pub fn strip_comments_set(&mut self, enable: bool) {
         self.strip_comments = enable;
     }
 
-    pub fn crate_name(&mut self, name: &'a str) {
-        self._crate_name = name;
+    /// Keep the comment at the top of the source files that mention a
+    /// license or a copyright, like the ones of vendored code, once
+    /// each at the top of the bundle when comments are stripped.
+    pub fn collect_licenses_set(&mut self, enable: bool) {
+        self.collect_licenses = enable;
     }
 
-    pub fn run(&mut self) {
-        let mut o = File::create(&self.bundle_filename)
-            .unwrap_or_else(|_| panic!("error creating {}", &self.bundle_filename.display()));
-        self.binrs(&mut o).unwrap_or_else(|_| {
-            panic!(
-                "error creating bundle {} for {}",
-                self.bundle_filename.display(),
-                self.binrs_filename.display()
-            )
-        });
+    /// Never strip the lines that match `pattern`, like the pragmas
+    /// some judges look for in comments.
+    pub fn keep_comment(&mut self, pattern: Regex) {
+        self.kept_comments.push(pattern);
     }
 
-    /// From the file that has the main() function, expand "extern
-    /// crate <_crate_name>" into lib.rs contents, and smartly skips
-    /// "use <_crate_name>::" lines.
-    fn binrs(&mut self, mut o: &mut File) -> Result<(), io::Error> {
-        let bin_fd = File::open(self.binrs_filename)?;
-        let mut bin_reader = BufReader::new(&bin_fd);
+    /// Keep the comments in the first `lines` lines of the entry file,
+    /// at the very top of the bundle, for judges that want a magic
+    /// first line.
+    pub fn preserve_prefix_comments(&mut self, lines: usize) {
+        self.prefix_comments = lines;
+    }
 
-        let extcrate_re = source_line_regex(format!(
-            r" extern  crate  {} ; ",
-            String::from(self._crate_name)
-        ));
-        let usecrate_re = source_line_regex(
-            format!(r" use  {} :: (.*) ; ", String::from(self._crate_name)).as_str(),
-        );
+    /// Remove the whitespace at the end of the lines, enabled by
+    /// default. Whitespace in literals is never touched.
+    pub fn trim_trailing_whitespace_set(&mut self, enable: bool) {
+        self.trim_trailing_whitespace = enable;
+    }
 
-        eprintln!("{:?}", usecrate_re);
+    /// Remove the indentation of the lines, which minification also
+    /// does, but without doing anything else.
+    pub fn strip_indentation_set(&mut self, enable: bool) {
+        self.strip_indentation = enable;
+    }
 
-        let mut line = String::new();
-        while bin_reader.read_line(&mut line).unwrap() > 0 {
-            line.truncate(line.trim_end().len());
-            if self.strip_comments && (self.comment_re.is_match(&line) || self.warn_re.is_match(&line)) {
-            } else if extcrate_re.is_match(&line) {
-                self.librs(o)?;
-            } else if let Some(cap) = usecrate_re.captures(&line) {
-                let moduse = cap.get(1).unwrap().as_str();
-                if !self.skip_use.contains(moduse) {
-                    writeln!(&mut o, "use {};", moduse)?;
-                }
-            } else {
-                self.write_line(o, &line)?;
-            }
-            line.clear();
-        }
-        Ok(())
+    /// Also write a fully minified copy of the bundle, next to it and
+    /// with `suffix` instead of the extension: with `.min.rs`,
+    /// `solution.rs` gets a `solution.min.rs`. The bundle itself keeps
+    /// the configured [`Bundler::minify_level`], and the size limit is
+    /// checked on the copy, as it's the one to submit.
+    pub fn emit_minified(&mut self, suffix: &'a str) {
+        self.minified_suffix = Some(suffix);
     }
 
-    /// Expand lib.rs contents and "pub mod <>;" lines.
-    fn librs(&mut self, o: &mut File) -> Result<(), io::Error> {
-        let lib_fd = File::open(self.librs_filename).expect("could not open lib.rs");
-        let mut lib_reader = BufReader::new(&lib_fd);
+    /// Limit runs of blank lines in the bundle to `max_consecutive`,
+    /// whatever the minification level.
+    pub fn collapse_blank_lines(&mut self, max_consecutive: usize) {
+        self.max_blank_lines = Some(max_consecutive);
+    }
 
-        let mod_re = source_line_regex(r" (pub  )?mod  (?P<m>.+) ; ");
+    /// For libraries submitted as such, remove the private items that
+    /// no public item, nor `fn main`, uses. Items are matched by name
+    /// only, so this never removes anything that is used, but it can
+    /// keep some items that aren't.
+    pub fn public_api_only_set(&mut self, enable: bool) {
+        self.public_api_only = enable;
+    }
 
-        let mut line = String::new();
-        while lib_reader.read_line(&mut line).unwrap() > 0 {
-            line.pop();
-            if self.strip_comments && (self.comment_re.is_match(&line) || self.warn_re.is_match(&line)) {
-            } else if let Some(cap) = mod_re.captures(&line) {
-                let modname = cap.name("m").unwrap().as_str();
-                if !self.skip_mod.contains(modname) {
-                    self.usemod(o, modname, modname, modname)?;
-                }
-            } else {
-                self.write_line(o, &line)?;
-            }
-            line.clear(); // clear to reuse the buffer
-        }
-        Ok(())
+    /// Declare how the judge builds the submission, to remove the
+    /// items that it would not compile, like debug-only checks and
+    /// visualizers under `#[cfg(debug_assertions)]` for
+    /// [`BuildProfile::Release`]. Without it, all items are kept.
+    pub fn judge_profile(&mut self, profile: BuildProfile) {
+        self.judge_profile = Some(profile);
     }
 
-    /// Called to expand random .rs files from lib.rs. It recursivelly
-    /// expands further "pub mod <>;" lines and updates the list of
-    /// "use <>;" lines that have to be skipped.
-    fn usemod(
-        &mut self,
-        mut o: &mut File,
-        mod_name: &str,
-        mod_path: &str,
-        mod_import: &str,
-    ) -> Result<(), io::Error> {
-        let src_dir = self.librs_filename.parent().unwrap();
+    /// Bundle as if the feature `name` of the package was enabled: the
+    /// items under `#[cfg(feature = "<name>")]` are kept without the
+    /// attribute, as the judge has no features, and the ones for the
+    /// other features are removed. The `required-features` of the
+    /// `[[bin]]` of the entry file are enabled too. Can be called
+    /// several times.
+    pub fn enable_feature(&mut self, name: &'a str) {
+        self.features.push(name);
+    }
 
-        let mod_filenames0 = vec![
-            src_dir.join(mod_path.to_owned() + ".rs"),
-            src_dir.join(mod_path.to_owned()).join("mod.rs"),
-        ];
-        let mod_fd = mod_filenames0
-            .iter()
-            .map(|mod_filename| File::open(mod_filename))
-            .find(|fd| fd.is_ok());
-        assert!(mod_fd.is_some(), "could not find file for module");
-        let mut mod_reader = BufReader::new(mod_fd.unwrap().unwrap());
+    /// Bundle only the library, without reading the entry file, for
+    /// projects that `include!` the bundle or judges that provide their
+    /// own `main`. The inner attributes of lib.rs, like `#![allow(...)]`,
+    /// are dropped, as they are not allowed in an included file.
+    pub fn library_only_set(&mut self, enable: bool) {
+        self.library_only = enable;
+    }
 
-        let mod_re = source_line_regex(r" (pub  )?mod  (?P<m>.+) ; ");
+    /// Fail with [`Error::ForbiddenPattern`] if the code of the bundle,
+    /// without comments and literals, matches `pattern`, e.g. `dbg!`.
+    /// Can be called several times.
+    pub fn forbid(&mut self, pattern: Regex) {
+        self.forbidden.push(pattern);
+    }
 
-        let mut line = String::new();
+    /// Insert `code` as it is at `anchor`, like a polyfill for the old
+    /// compiler of a judge, after what was inserted there before. Code
+    /// for [`BundleAnchor::BeforeLib`] and [`BundleAnchor::AfterLib`] is
+    /// only inserted if the library is.
+    pub fn insert_raw(&mut self, anchor: BundleAnchor, code: &'a str) {
+        self.raw_chunks.push((anchor, code));
+    }
 
-        writeln!(&mut o, "pub mod {} {{", mod_name)?;
-        self.skip_use.insert(String::from(mod_import));
+    /// Append `file`, like the tests of a practice exercise, at the end
+    /// of the bundle, with its comments stripped like the rest, and its
+    /// paths to the crate, like `use <crate>::*;`, pointed to the root of
+    /// the bundle. With `wrap`, it's in `#[cfg(test)] mod <wrap> { ... }`.
+    pub fn append_file(&mut self, file: &'a Path, wrap: Option<&'a str>) {
+        self.appended_files.push((file, wrap));
+    }
 
-        while mod_reader.read_line(&mut line).unwrap() > 0 {
-            line.truncate(line.trim_end().len());
-            if self.strip_comments && (self.comment_re.is_match(&line) || self.warn_re.is_match(&line)) {
-            } else if let Some(cap) = mod_re.captures(&line) {
-                let submodname = cap.name("m").unwrap().as_str();
-                if submodname != "tests" {
-                    let submodfile = format!("{}/{}", mod_path, submodname);
-                    let submodimport = format!("{}::{}", mod_import, submodname);
-                    self.usemod(o, submodname, submodfile.as_str(), submodimport.as_str())?;
-                }
-            } else {
-                self.write_line(o, &line)?;
-            }
-            line.clear(); // clear to reuse the buffer
-        }
+    /// Warn with [`Warning::NewerRustFeature`] when the bundle uses a
+    /// feature newer than Rust `1.<minor>`, for judges with an old
+    /// compiler, like let-else or `div_ceil`. Features are found with
+    /// patterns on the code, so some uses can be missed.
+    pub fn max_rust_version(&mut self, minor: u32) {
+        self.max_rust_version = Some(minor);
+    }
 
-        writeln!(&mut o, "}}")?;
+    /// Add `code` at the end of the bundle when it uses `feature`, with
+    /// the name given by [`Warning::NewerRustFeature`], and it's newer
+    /// than the [`Bundler::max_rust_version`], instead of warning. For
+    /// missing std items, like a `DivCeil` trait for `div_ceil`.
+    pub fn polyfill(&mut self, feature: &'a str, code: &'a str) {
+        self.polyfills.push((feature, code));
+    }
 
+    /// Remove the outer attributes named `name` from the bundle to save
+    /// bytes, like `inline` for `#[inline]` and `#[inline(always)]`, or
+    /// `doc` for `#[doc = "..."]`. Can be called several times.
+    pub fn drop_attribute(&mut self, name: &'a str) {
+        self.dropped_attributes.push(name);
+    }
+
+    /// Never bundle the module files that match `pattern`, a glob like
+    /// `**/dev_*.rs` or `src/debug/`, relative to the directory of the
+    /// package, as in `.gitignore`. The patterns of the `.bundleignore`
+    /// file of the package are used too, before these. Can be called
+    /// several times.
+    pub fn ignore_file(&mut self, pattern: &'a str) {
+        self.ignored_files.push(pattern);
+    }
+
+    /// Set what to do when a declared module has an ignored file, see
+    /// [`Bundler::ignore_file`]. It fails by default.
+    pub fn ignored_modules(&mut self, policy: IgnoredModules) {
+        self.ignored_modules = policy;
+    }
+
+    /// Remove the trait `name` from the `#[derive(...)]` attributes of the
+    /// bundle, like `Serialize` for a judge without serde. With
+    /// [`Bundler::drop_attribute`] for the helper attributes, like
+    /// `serde`, the items build without the crate of the derive macro.
+    /// Can be called several times.
+    pub fn strip_derive(&mut self, name: &'a str) {
+        self.stripped_derives.push(name);
+    }
+
+    /// Forbid `todo!` and `unimplemented!`, to never submit a solution
+    /// that is not finished, as with [`Bundler::forbid`].
+    pub fn forbid_unfinished(&mut self) {
+        self.forbid(Regex::new(r"\b(todo|unimplemented)\s*!").unwrap());
+    }
+
+    /// Parse the bundle with syn, and fail with [`Error::InvalidSyntax`]
+    /// at the first syntax error, to catch a bundle broken by the
+    /// bundler without running rustc. Needs the `check-syntax` feature.
+    #[cfg(feature = "check-syntax")]
+    pub fn check_syntax_set(&mut self, enable: bool) {
+        self.check_syntax = enable;
+    }
+
+    /// Fail with [`Error::InvalidUtf8`] on source files that are not
+    /// valid UTF-8. By default, the invalid bytes are replaced and a
+    /// [`Warning::InvalidUtf8`] is emitted.
+    pub fn strict_utf8_set(&mut self, enable: bool) {
+        self.strict_utf8 = enable;
+    }
+
+    /// Copy source files whose delimiters are not balanced verbatim,
+    /// with a [`Warning::UnbalancedFile`], instead of failing with
+    /// [`Error::UnbalancedDelimiters`]. Their `mod` declarations are not
+    /// expanded, so the bundle may not build, but a file being edited
+    /// doesn't stop a watch loop.
+    pub fn lenient_set(&mut self, enable: bool) {
+        self.lenient = enable;
+    }
+
+    /// Replace the glob imports of modules of the library in the entry
+    /// file, like `use <crate>::graph::*;`, with imports of the names
+    /// the entry file uses, so that the rest of the module is not
+    /// imported. Public traits of the module are always imported. A
+    /// module that re-exports a glob itself keeps its glob import.
+    pub fn explicit_globs_set(&mut self, enable: bool) {
+        self.explicit_globs = enable;
+    }
+
+    /// Move `#[macro_export]` macros of the library to the top of the
+    /// bundle, so that they can be used by code that comes before the
+    /// module where they are defined. Enabled by default.
+    pub fn hoist_macros_set(&mut self, enable: bool) {
+        self.hoist_macros = enable;
+    }
+
+    /// Start the bundle with a comment that has the git revision of the
+    /// library (`git describe`, short hash and dirty flag), to be able
+    /// to trace old submissions back to their sources.
+    pub fn git_header_set(&mut self, enable: bool) {
+        self.git_header = enable;
+    }
+
+    /// Start the bundle with a comment for each field of the contest,
+    /// like `// problem: A`, taken from the [`HEADER_ENV_VARS`] set by
+    /// contest helpers. A field given to [`Bundler::header_field`] is
+    /// used instead of its variable, and empty variables are ignored.
+    pub fn contest_header_set(&mut self, enable: bool) {
+        self.contest_header = enable;
+    }
+
+    /// Add the field `name` to the header of the bundle, as a
+    /// `// <name>: <value>` comment after the git revision. It takes
+    /// precedence over the environment variable of the field with
+    /// [`Bundler::contest_header_set`].
+    pub fn header_field(&mut self, name: &'a str, value: &'a str) {
+        self.header_fields.push((name, value));
+    }
+
+    /// Start the bundle with a comment that lists the modules and the
+    /// line where each one starts, to find one's way in big bundles.
+    /// The lines are those of the bundle as written, after
+    /// minification.
+    pub fn table_of_contents_set(&mut self, enable: bool) {
+        self.table_of_contents = enable;
+    }
+
+    /// End the bundle with a comment that has the bundler version and
+    /// the SHA-256 of the code before it, for [`verify_bundle`] to
+    /// tell if it was edited by hand. Each file of a split bundle gets
+    /// its own.
+    pub fn integrity_footer_set(&mut self, enable: bool) {
+        self.integrity_footer = enable;
+    }
+
+    /// End each module expanded from a file with a constant that has the
+    /// path of the file, like
+    /// `pub const __BUNDLE_SRC_GRAPH_FLOW: &str = "src/graph/flow.rs";`,
+    /// for tools to find where the code came from even after comments
+    /// are minified away.
+    pub fn source_markers_set(&mut self, enable: bool) {
+        self.source_markers = enable;
+    }
+
+    /// Set the visibility of the generated module wrappers, including
+    /// the nested ones, except for [`Visibility::Private`].
+    pub fn wrapper_visibility(&mut self, visibility: Visibility) {
+        self.wrapper_visibility = visibility;
+    }
+
+    /// Set what to do with the inner attributes of the entry file, which
+    /// are kept where they are by default. `#![warn(...)]` is removed
+    /// with the comments in any case.
+    pub fn entry_inner_attributes(&mut self, policy: InnerAttributes) {
+        self.entry_inner_attributes = policy;
+    }
+
+    /// Set what to do with the inner doc attributes that include a file,
+    /// like `#![doc = include_str!("../README.md")]`, which are removed
+    /// by default.
+    pub fn doc_includes(&mut self, policy: DocIncludes) {
+        self.doc_includes = policy;
+    }
+
+    /// Keep rustfmt from formatting the bundle, e.g. when it's committed
+    /// and `cargo fmt` runs in CI. An inner `#![rustfmt::skip]` is not
+    /// stable yet, so [`RustfmtSkip::File`] uses a `cfg_attr` that only
+    /// rustfmt applies.
+    pub fn rustfmt_skip(&mut self, skip: RustfmtSkip) {
+        self.rustfmt_skip = skip;
+    }
+
+    /// Set the name the entry file imports the library with. The name
+    /// of the package, like `my-lib`, works too, as the `-` become `_`
+    /// and the `[lib]` name of the Cargo.toml is also accepted.
+    pub fn crate_name(&mut self, name: &'a str) {
+        self._crate_name = Cow::Borrowed(name);
+    }
+
+    /// Other names the entry file imports the library with, like
+    /// `algo` for a dependency on the package itself renamed with
+    /// `algo = { path = ".", package = "my-algo" }`. The names of
+    /// the Cargo.toml, the `[lib]` and package names and such renames,
+    /// are found without it.
+    pub fn crate_aliases(&mut self, aliases: &[&'a str]) {
+        self.crate_aliases.extend_from_slice(aliases);
+    }
+
+    /// Set the edition of the crate. By default it's read from the
+    /// Cargo.toml next to the `src` directory of lib.rs.
+    ///
+    /// From 2018 on, the library is inlined at the first `use
+    /// <crate>::...` line if there's no `extern crate <crate>;`.
+    /// See [`Bundler::run`] for how to choose the place explicitly.
+    pub fn edition(&mut self, edition: Edition) {
+        self.edition = Some(edition);
+    }
+
+    /// Warn with [`Warning::DisallowedCrate`] if the bundle uses an
+    /// external crate, other than std, core and alloc, that is not in
+    /// `crates`, i.e. that the judge doesn't have.
+    pub fn allowed_crates(&mut self, crates: &[&'a str]) {
+        self.allowed_crates = Some(crates.to_vec());
+    }
+
+    /// Apply the settings of a judge, like its size limit and edition,
+    /// in one call: see [`judge::Preset`] for the built-in ones. Custom
+    /// presets implement [`judge::JudgePreset`], as functions that take
+    /// the bundler do.
+    pub fn preset<P: judge::JudgePreset>(&mut self, preset: P) {
+        preset.configure(self);
+    }
+
+    /// Warn with [`Warning::OversizedOutput`] if the bundle has more
+    /// than `limit` bytes.
+    pub fn size_limit(&mut self, limit: usize) {
+        self.size_limit = Some(limit);
+    }
+
+    /// Fail with [`Error::LimitExceeded`] if modules are expanded more
+    /// than `depth` levels inside each other. The default is 64.
+    pub fn max_depth(&mut self, depth: usize) {
+        self.max_depth = depth;
+    }
+
+    /// Fail with [`Error::LimitExceeded`] if more than `count` source
+    /// files are read. The default is 10000.
+    pub fn max_files(&mut self, count: usize) {
+        self.max_files = count;
+    }
+
+    /// Fail with [`Error::LimitExceeded`] if the bundle gets bigger
+    /// than `bytes`, before or after minifying, unlike
+    /// [`Bundler::size_limit`] which only warns. The default is 64 MiB.
+    pub fn max_output_size(&mut self, bytes: usize) {
+        self.max_output_size = bytes;
+    }
+
+    /// For judges that take several files of at most `max_file_size`
+    /// bytes each, move the biggest modules at the root of the bundle
+    /// to their own files, like `bundle.graph.rs` for `graph`, until it
+    /// fits. A `.parts` file next to the bundle lists the files, the
+    /// bundle first, and so does [`Report::parts`].
+    pub fn split_output(&mut self, max_file_size: usize) {
+        self.split_limit = Some(max_file_size);
+    }
+
+    /// Call `callback` with each warning as soon as it is found, in
+    /// addition to collecting it in the [`Report`].
+    pub fn on_warning<F: Fn(&Warning) + Send + Sync + 'a>(&mut self, callback: F) {
+        self.on_warning = Some(WarningCallback(Arc::new(callback)));
+    }
+
+    /// Call `callback` with each step of a run, as it happens: the files
+    /// opened, the modules expanded and the files written, in order,
+    /// e.g. to show the progress of a watch mode.
+    pub fn on_progress<F: Fn(ProgressEvent) + Send + Sync + 'a>(&mut self, callback: F) {
+        self.on_progress = Some(ProgressCallback(Arc::new(callback)));
+    }
+
+    /// Replace the matches of `pattern` in the lines of the entry file,
+    /// as [`Regex::replace_all`] does, e.g. to turn `const LOCAL: bool =
+    /// true;` into `false`. Rules are applied in the order they are
+    /// added, to each line before it's processed and without its
+    /// terminator.
+    pub fn rewrite_bin(&mut self, pattern: Regex, replacement: &str) {
+        self.rewrites
+            .push(Rewrite::Regex(pattern, replacement.to_string()));
+    }
+
+    /// Replace each line of the entry file with what `rewrite` returns
+    /// for it, like [`Bundler::rewrite_bin`] does.
+    pub fn rewrite_bin_with<F: Fn(&str) -> String + Send + Sync + 'a>(&mut self, rewrite: F) {
+        self.rewrites.push(Rewrite::Callback(Arc::new(rewrite)));
+    }
+
+    /// Run `processor` on the text of the bundle, after the ones given
+    /// before it and the built-in steps, but before the integrity
+    /// footer. See [`post`] for the order of all the steps.
+    pub fn post_process<P: PostProcessor + 'a>(&mut self, processor: P) {
+        self.post_processors
+            .push(PostProcessorRef(Arc::new(processor)));
+    }
+
+    /// Replace `bundler_const!(<name>)` in the bundle, and the value of
+    /// a `const <name>: <type> = ...;` line, with `value`, which is
+    /// Rust code, e.g. `define("PROBLEM_ID", "\"1851F\"")` to stamp
+    /// the problem in a shared template. The `bundler_const!` macro is
+    /// to be defined by the crate, for the builds that are not bundled.
+    pub fn define(&mut self, name: &'a str, value: &'a str) {
+        self.defines.push((name, value));
+    }
+
+    /// Rename the module at `path`, like `io` or `graph::io`, to
+    /// `name` in the bundle, in its wrapper and in the paths that refer
+    /// to it, e.g. when the judge provides a crate with the same name.
+    /// Only paths with `::` outside of `use` groups are renamed.
+    pub fn rename_module(&mut self, path: &'a str, name: &'a str) {
+        self.renamed_modules.push((path, name));
+    }
+
+    /// Also write a JSON file with the SHA-256 of the bundle and of
+    /// each source file that went into it, the version of the bundler
+    /// and the options used, to be able to audit submissions later.
+    pub fn provenance_file(&mut self, filename: &'a Path) {
+        self.provenance_filename = Some(filename);
+    }
+
+    /// Write the bundle to `filename` instead of the file given to
+    /// [`Bundler::new`], e.g. in a [`Bundler::profile`].
+    pub fn bundle_file(&mut self, filename: &'a Path) {
+        self.bundle_filename = Cow::Borrowed(filename);
+    }
+
+    /// File the bundle is written to.
+    pub fn bundle_filename(&self) -> &Path {
+        &self.bundle_filename
+    }
+
+    /// Write the bundle to `target/sourcebundle/<bin name>.rs`, in the
+    /// `CARGO_TARGET_DIR` if it's set, or in the `target` directory
+    /// next to the Cargo.toml of lib.rs. The bin name is the stem of
+    /// the entry file, or the package name for `src/main.rs`.
+    pub fn default_bundle_file(&mut self) {
+        let target_dir = match env::var_os("CARGO_TARGET_DIR") {
+            Some(dir) => PathBuf::from(dir),
+            None => {
+                let manifest_filename = self.manifest_filename();
+                let root_dir = manifest_filename.parent().unwrap_or(Path::new(""));
+                root_dir.join("target")
+            }
+        };
+        let filename = format!("{}.rs", self.bin_name());
+        self.bundle_filename = Cow::Owned(target_dir.join("sourcebundle").join(filename));
+    }
+
+    /// After creating the bundle, build it with rustc and the original
+    /// entry file with cargo, run both with the contents of `input` as
+    /// stdin, and warn with [`Warning::OutputMismatch`] if their outputs
+    /// differ. Can be called several times.
+    pub fn verify_input(&mut self, input: &'a Path) {
+        self.verify_inputs.push(input);
+    }
+
+    /// Kill the builds of [`Bundler::verify_input`] after `build`, and
+    /// each run of a program after `run`, by default 5 minutes and 10
+    /// seconds. A run that times out is reported with
+    /// [`Warning::VerificationTimeout`] instead of hanging the build.
+    pub fn verify_timeouts(&mut self, build: Duration, run: Duration) {
+        self.verify_timeouts = (build, run);
+    }
+
+    /// Define the profile `name`, a set of changes to the configuration
+    /// that [`Bundler::run_profile`] applies to a copy of the bundler,
+    /// so that one build.rs can create a bundle for local review and
+    /// another to submit:
+    ///
+    /// ```no_run
+    /// use std::path::Path;
+    /// use rustsourcebundler::Bundler;
+    ///
+    /// let mut bundler = Bundler::new(Path::new("src/bin/a.rs"), Path::new("a.rs"));
+    /// bundler.crate_name("mycrate");
+    /// bundler.profile("debug", |b| b.strip_comments_set(false));
+    /// bundler.profile("submit", |b| {
+    ///     b.minify_level(3);
+    ///     b.bundle_file(Path::new("a.submit.rs"));
+    /// });
+    /// bundler.run_profile("debug").unwrap();
+    /// bundler.run_profile("submit").unwrap();
+    /// ```
+    ///
+    /// Defining a profile again replaces it.
+    pub fn profile<F: Fn(&mut Bundler<'a>) + Send + Sync + 'a>(
+        &mut self,
+        name: &'a str,
+        configure: F,
+    ) {
+        self.profiles.retain(|profile| profile.name != name);
+        self.profiles.push(Profile {
+            name,
+            configure: Arc::new(configure),
+        });
+    }
+
+    /// Change the configuration from the environment, to tweak a run
+    /// without editing build.rs:
+    ///
+    /// - `SOURCEBUNDLER_OUTPUT`: the bundle file;
+    /// - `SOURCEBUNDLER_MINIFY`: the [`Bundler::minify_level`];
+    /// - `SOURCEBUNDLER_STRIP_COMMENTS`: `1`/`true` or `0`/`false`;
+    /// - `SOURCEBUNDLER_PROFILE`: a [`Bundler::profile`] to apply, after
+    ///   the other variables.
+    ///
+    /// Variables that are not set, or empty, don't change anything. See
+    /// [`ENV_VARS`] to rebuild when they change.
+    pub fn apply_env(&mut self) -> Result<(), Error> {
+        let var = |name: &str| env::var(name).ok().filter(|value| !value.is_empty());
+        let invalid = |name: &str, value: String| Error::InvalidEnv {
+            name: name.to_string(),
+            value,
+        };
+        if let Some(output) = var("SOURCEBUNDLER_OUTPUT") {
+            self.bundle_filename = Cow::Owned(PathBuf::from(output));
+        }
+        if let Some(value) = var("SOURCEBUNDLER_MINIFY") {
+            match value.parse() {
+                Ok(level) => self.minify_level(level),
+                Err(_) => return Err(invalid("SOURCEBUNDLER_MINIFY", value)),
+            }
+        }
+        if let Some(value) = var("SOURCEBUNDLER_STRIP_COMMENTS") {
+            match value.as_str() {
+                "1" | "true" => self.strip_comments_set(true),
+                "0" | "false" => self.strip_comments_set(false),
+                _ => return Err(invalid("SOURCEBUNDLER_STRIP_COMMENTS", value)),
+            }
+        }
+        if let Some(name) = var("SOURCEBUNDLER_PROFILE") {
+            *self = self.with_profile(&name)?;
+        }
         Ok(())
     }
 
-    fn write_line(&self, mut o: &mut File, line: &str) -> Result<(), io::Error> {
-        if let Some(ref minify_re) = self.minify_re {
-            writeln!(&mut o, "{}", minify_re.replace_all(line, "$contents"))
+    /// Copy of the bundler with the profile `name` applied.
+    pub fn with_profile(&self, name: &str) -> Result<Bundler<'a>, Error> {
+        let profile = self
+            .profiles
+            .iter()
+            .find(|profile| profile.name == name)
+            .ok_or_else(|| Error::UnknownProfile {
+                name: name.to_string(),
+            })?;
+        let mut bundler = self.clone();
+        (profile.configure)(&mut bundler);
+        Ok(bundler)
+    }
+
+    /// [`Bundler::run`] with the profile `name` applied, without
+    /// changing this bundler.
+    pub fn run_profile(&self, name: &str) -> Result<Report, Error> {
+        self.with_profile(name)?.run()
+    }
+
+    /// Create the bundle.
+    ///
+    /// The library is inlined where the entry file has a `//
+    /// bundler:insert-lib` line (`// bundler:inline-lib` also works).
+    /// Without that line, it's inlined at the `extern crate <crate>;`
+    /// line or, from edition 2018 on, at the first `use <crate>::...`
+    /// line.
+    ///
+    /// The bundler is not changed, so it can run many times, and from
+    /// many threads at once.
+    pub fn run(&self) -> Result<Report, Error> {
+        self.run_with_plan().map(|(_, report)| report)
+    }
+
+    /// Run as [`Bundler::run`] does, and also return the plan of what
+    /// was done, as [`Bundler::plan`] would, without bundling twice.
+    pub fn run_with_plan(&self) -> Result<(BundlePlan, Report), Error> {
+        let mut run = Run::new(self);
+        let mut bundle = run.bundle()?;
+        if let Some(limit) = self.split_limit {
+            bundle = run.split(bundle, limit)?;
+        }
+        run.write_output(&self.bundle_filename, &bundle)?;
+        if let (Some(minified), Some(path)) = (&run.minified, self.minified_filename()) {
+            run.write_output(&path, minified)?;
+        }
+        if let Some(filename) = self.provenance_filename {
+            let provenance = run.provenance_json(&bundle)? + "\n";
+            run.write_output(filename, provenance.as_bytes())?;
+        }
+        if !self.verify_inputs.is_empty() {
+            run.verify();
+        }
+        let report = run.report(&bundle);
+        let mut plan = run.plan;
+        plan.warnings = run.warnings;
+        Ok((plan, report))
+    }
+
+    /// Create the bundle as [`Bundler::run`] does, but return it to be
+    /// read instead of writing it, nor the minified copy and the
+    /// provenance file. The whole bundle is made before this returns,
+    /// it's not streamed, see [`BundleReader`].
+    pub fn reader(&self) -> Result<BundleReader, Error> {
+        let mut run = Run::new(self);
+        let bundle = run.bundle()?;
+        let report = run.report(&bundle);
+        Ok(BundleReader::new(bundle, report))
+    }
+
+    /// Size in bytes of the bundle [`Bundler::run`] would write with
+    /// the current settings, without writing it. Handy to try
+    /// minification levels or excluded modules before picking one.
+    pub fn estimate_size(&self) -> Result<usize, Error> {
+        Ok(Run::new(self).bundle()?.len())
+    }
+
+    /// Bundle `entry_source` as the entry file, instead of reading the
+    /// one given to [`Bundler::new`], and return the bundle without
+    /// writing it, e.g. for a main made from a problem template. The
+    /// path of the entry file still names it in messages, and its
+    /// modules are looked for next to it, but it doesn't have to exist.
+    pub fn bundle_snippet(&self, entry_source: &str) -> Result<String, Error> {
+        let mut run = Run::new(self);
+        run.entry_source = Some(entry_source.to_string());
+        let bundle = run.bundle()?;
+        Ok(String::from_utf8_lossy(&bundle).into_owned())
+    }
+
+    /// Kind of `line`, as the bundler reads a line of a source file that
+    /// starts in code, i.e. not inside a block comment or a string.
+    /// Directives are found first, then blank and comment lines, then
+    /// `mod`, `extern crate` and `use` declarations written on the line.
+    pub fn classify_line(&self, line: &str) -> LineKind {
+        let directives = [
+            &self.insert_lib_re,
+            &self.only_local_re,
+            &self.only_bundle_re,
+            &self.section_end_re,
+            &self.skip_item_re,
+        ];
+        if directives.iter().any(|re| re.is_match(line)) {
+            LineKind::Directive
+        } else if line.trim().is_empty() {
+            LineKind::Blank
+        } else if self.comment_re.is_match(line) {
+            LineKind::Comment
+        } else if let Some(cap) = self.mod_re.captures(line) {
+            LineKind::ModDecl {
+                name: cap["m"].to_string(),
+            }
+        } else if let Some(cap) = self.extern_re.captures(line) {
+            let name = cap["c"].split_whitespace().next().unwrap_or("");
+            LineKind::ExternCrate {
+                name: name.to_string(),
+            }
+        } else if self.use_re.is_match(line) {
+            LineKind::UseDecl
+        } else {
+            LineKind::Code
+        }
+    }
+
+    /// Kind of `stmt`, a declaration that can be written on several
+    /// lines, like a `use` with a group of paths, classified as
+    /// [`Bundler::classify_line`] does once its lines are joined, without
+    /// their `//` comments.
+    pub fn classify_stmt(&self, stmt: &str) -> LineKind {
+        if !stmt.trim().contains('\n') {
+            return self.classify_line(stmt);
+        }
+        match classify::join_lines(stmt) {
+            joined if joined.is_empty() => LineKind::Comment,
+            joined => self.classify_line(&joined),
+        }
+    }
+
+    /// Go through the sources as [`Bundler::run`] would, without
+    /// writing anything, and fail with [`Error::CheckFailed`] if the
+    /// bundle would be wrong: an import of a module that is not bundled,
+    /// an `include!` or `env!` left in it, or a size over the
+    /// [`Bundler::size_limit`]. Missing modules and cycles fail as
+    /// usual. Meant for CI.
+    pub fn check_only(&self) -> Result<Report, Error> {
+        let mut run = Run::new(self);
+        let bundle = run.bundle()?;
+        let report = run.report(&bundle);
+        let problems: Vec<Warning> = report
+            .warnings
+            .iter()
+            .filter(|warning| {
+                matches!(
+                    warning,
+                    Warning::UnresolvedImport { .. }
+                        | Warning::UnexpandedInclude { .. }
+                        | Warning::EnvMacro { .. }
+                        | Warning::OversizedOutput { .. }
+                )
+            })
+            .cloned()
+            .collect();
+        if !problems.is_empty() {
+            return Err(Error::CheckFailed { problems });
+        }
+        Ok(report)
+    }
+
+    /// Go through the sources as [`Bundler::run`] would, but only
+    /// return what would be done, without writing anything.
+    pub fn plan(&self) -> Result<BundlePlan, Error> {
+        let mut run = Run::new(self);
+        run.bundle()?;
+        let mut plan = run.plan;
+        plan.warnings = run.warnings;
+        Ok(plan)
+    }
+
+    /// Copy of the bundler, with the same configuration, for another
+    /// entry file and bundle. Bundlers are `Send` and `Sync`, so the
+    /// targets can be bundled in parallel:
+    ///
+    /// ```no_run
+    /// use std::path::Path;
+    /// use std::thread;
+    /// use rustsourcebundler::Bundler;
+    ///
+    /// let mut bundler = Bundler::new(Path::new("src/bin/a.rs"), Path::new("a.rs"));
+    /// bundler.crate_name("mycrate");
+    /// let other = bundler.for_target(Path::new("src/bin/b.rs"), Path::new("b.rs"));
+    /// thread::scope(|scope| {
+    ///     scope.spawn(|| bundler.run().unwrap());
+    ///     scope.spawn(|| other.run().unwrap());
+    /// });
+    /// ```
+    pub fn for_target(&self, binrs_filename: &'a Path, bundle_filename: &'a Path) -> Bundler<'a> {
+        Bundler {
+            binrs_filename: Cow::Borrowed(binrs_filename),
+            bundle_filename: Cow::Borrowed(bundle_filename),
+            ..self.clone()
+        }
+    }
+
+    /// Where the minified copy goes, if there's one.
+    fn minified_filename(&self) -> Option<PathBuf> {
+        let suffix = self.minified_suffix?;
+        let stem = self.bundle_filename.file_stem()?.to_string_lossy();
+        Some(
+            self.bundle_filename
+                .with_file_name(format!("{}{}", stem, suffix)),
+        )
+    }
+
+    /// Options of the bundler, as a JSON object.
+    fn options_json(&self) -> String {
+        let strings = |values: Vec<&str>| json::array(values.into_iter().map(json::string));
+        let optional = |value: Option<String>| value.unwrap_or_else(|| "null".to_string());
+        let mut skip_mod: Vec<&str> = self.skip_mod.iter().copied().collect();
+        skip_mod.sort_unstable();
+        let rewrites = self.rewrites.iter().map(|rewrite| match rewrite {
+            Rewrite::Regex(pattern, replacement) => json::object(&[
+                ("pattern", json::string(pattern.as_str())),
+                ("replacement", json::string(replacement)),
+            ]),
+            Rewrite::Callback(_) => json::string("<function>"),
+        });
+        json::object(&[
+            ("crate_name", json::string(&self._crate_name)),
+            ("crate_aliases", strings(self.crate_aliases.clone())),
+            (
+                "edition",
+                optional(self.edition.map(|e| json::string(e.year()))),
+            ),
+            ("minify_level", self.minify_level.to_string()),
+            ("minify_auto", self.minify_auto.to_string()),
+            (
+                "collapse_blank_lines",
+                optional(self.max_blank_lines.map(|max| max.to_string())),
+            ),
+            (
+                "emit_minified",
+                optional(self.minified_suffix.map(json::string)),
+            ),
+            ("exclude_mod", strings(skip_mod)),
+            (
+                "source_roots",
+                json::array(self.source_roots.iter().map(|root| json::path(root))),
+            ),
+            ("exclude_item", strings(self.excluded_items.clone())),
+            ("allow_lint", strings(self.allow_lints.clone())),
+            ("allow_unused", self.allow_unused.to_string()),
+            (
+                "force_include_mod",
+                json::array(self.forced_mods.iter().map(|&(name, file)| {
+                    json::object(&[("name", json::string(name)), ("file", json::path(file))])
+                })),
+            ),
+            (
+                "embed_asset",
+                json::array(self.assets.iter().map(|&(file, name)| {
+                    json::object(&[("file", json::path(file)), ("name", json::string(name))])
+                })),
+            ),
+            ("strip_comments", self.strip_comments.to_string()),
+            ("collect_licenses", self.collect_licenses.to_string()),
+            (
+                "forbid",
+                strings(self.forbidden.iter().map(Regex::as_str).collect()),
+            ),
+            ("drop_attribute", strings(self.dropped_attributes.clone())),
+            ("strip_derive", strings(self.stripped_derives.clone())),
+            ("ignore_file", strings(self.ignored_files.clone())),
+            (
+                "ignored_modules",
+                json::string(&format!("{:?}", self.ignored_modules)),
+            ),
+            (
+                "max_rust_version",
+                optional(
+                    self.max_rust_version
+                        .map(|minor| json::string(&format!("1.{}", minor))),
+                ),
+            ),
+            (
+                "polyfill",
+                json::object_of(
+                    self.polyfills
+                        .iter()
+                        .map(|&(feature, code)| (feature, json::string(code))),
+                ),
+            ),
+            (
+                "insert_raw",
+                json::array(self.raw_chunks.iter().map(|&(anchor, code)| {
+                    json::object(&[
+                        ("anchor", json::string(&format!("{:?}", anchor))),
+                        ("code", json::string(code)),
+                    ])
+                })),
+            ),
+            (
+                "append_file",
+                json::array(self.appended_files.iter().map(|&(file, wrap)| {
+                    json::object(&[
+                        ("file", json::path(file)),
+                        ("wrap", optional(wrap.map(json::string))),
+                    ])
+                })),
+            ),
+            ("check_syntax", self.check_syntax.to_string()),
+            (
+                "keep_comment",
+                strings(self.kept_comments.iter().map(Regex::as_str).collect()),
+            ),
+            ("preserve_prefix_comments", self.prefix_comments.to_string()),
+            (
+                "trim_trailing_whitespace",
+                self.trim_trailing_whitespace.to_string(),
+            ),
+            ("strip_indentation", self.strip_indentation.to_string()),
+            ("strict_utf8", self.strict_utf8.to_string()),
+            ("lenient", self.lenient.to_string()),
+            ("explicit_globs", self.explicit_globs.to_string()),
+            ("public_api_only", self.public_api_only.to_string()),
+            ("library_only", self.library_only.to_string()),
+            ("enable_feature", strings(self.features.clone())),
+            (
+                "judge_profile",
+                optional(
+                    self.judge_profile
+                        .map(|p| json::string(&format!("{:?}", p))),
+                ),
+            ),
+            ("hoist_macros", self.hoist_macros.to_string()),
+            ("git_header", self.git_header.to_string()),
+            ("contest_header", self.contest_header.to_string()),
+            (
+                "header_field",
+                json::object_of(
+                    self.header_fields
+                        .iter()
+                        .map(|&(name, value)| (name, json::string(value))),
+                ),
+            ),
+            ("table_of_contents", self.table_of_contents.to_string()),
+            ("integrity_footer", self.integrity_footer.to_string()),
+            ("source_markers", self.source_markers.to_string()),
+            (
+                "wrapper_visibility",
+                json::string(&format!("{:?}", self.wrapper_visibility)),
+            ),
+            (
+                "entry_inner_attributes",
+                json::string(&format!("{:?}", self.entry_inner_attributes)),
+            ),
+            (
+                "doc_includes",
+                json::string(&format!("{:?}", self.doc_includes)),
+            ),
+            (
+                "rustfmt_skip",
+                json::string(&format!("{:?}", self.rustfmt_skip)),
+            ),
+            (
+                "inject_fastio",
+                optional(self.fastio.map(|kind| json::string(&format!("{:?}", kind)))),
+            ),
+            (
+                "size_limit",
+                optional(self.size_limit.map(|limit| limit.to_string())),
+            ),
+            ("max_depth", self.max_depth.to_string()),
+            ("max_files", self.max_files.to_string()),
+            ("max_output_size", self.max_output_size.to_string()),
+            (
+                "split_output",
+                optional(self.split_limit.map(|limit| limit.to_string())),
+            ),
+            (
+                "allowed_crates",
+                optional(self.allowed_crates.clone().map(strings)),
+            ),
+            ("rewrite_bin", json::array(rewrites)),
+            (
+                "post_process",
+                json::array(
+                    self.post_processors
+                        .iter()
+                        .map(|processor| json::string(processor.0.name())),
+                ),
+            ),
+            (
+                "define",
+                json::array(self.defines.iter().map(|&(name, value)| {
+                    json::object(&[("name", json::string(name)), ("value", json::string(value))])
+                })),
+            ),
+            (
+                "rename_module",
+                json::array(self.renamed_modules.iter().map(|&(path, name)| {
+                    json::object(&[("path", json::string(path)), ("name", json::string(name))])
+                })),
+            ),
+            (
+                "verify_input",
+                json::array(self.verify_inputs.iter().map(|input| json::path(input))),
+            ),
+            (
+                "verify_timeouts",
+                json::object(&[
+                    ("build", self.verify_timeouts.0.as_secs_f64().to_string()),
+                    ("run", self.verify_timeouts.1.as_secs_f64().to_string()),
+                ]),
+            ),
+            (
+                "profiles",
+                strings(self.profiles.iter().map(|profile| profile.name).collect()),
+            ),
+        ])
+    }
+
+    /// Cargo.toml next to the `src` directory of lib.rs.
+    fn manifest_filename(&self) -> PathBuf {
+        let src_dir = self.librs_filename.parent().unwrap_or(Path::new(""));
+        let root_dir = src_dir.parent().unwrap_or(Path::new(""));
+        root_dir.join("Cargo.toml")
+    }
+
+    /// Name of the bin target of the entry file: its stem, or the name
+    /// of the package for `src/main.rs`.
+    fn bin_name(&self) -> String {
+        let stem = self
+            .binrs_filename
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        if stem != "main" {
+            return stem;
+        }
+        Manifest::read(&self.manifest_filename())
+            .ok()
+            .and_then(|manifest| manifest.get("package", "name").map(str::to_string))
+            .unwrap_or(stem)
+    }
+
+    /// Features given to [`Bundler::enable_feature`], and the ones that
+    /// the bin target of the entry file requires.
+    fn enabled_features(&self) -> Vec<String> {
+        let mut features: Vec<String> = self.features.iter().map(|f| f.to_string()).collect();
+        if !self.library_only {
+            if let Ok(manifest) = Manifest::read(&self.manifest_filename()) {
+                features.extend(manifest.required_features(&self.bin_name()));
+            }
+        }
+        features.sort();
+        features.dedup();
+        features
+    }
+
+    /// Names the entry file can import the library with, as a regex
+    /// alternation: the crate name, the aliases and the names found in
+    /// the manifest and the root of the library. Only identifiers are
+    /// kept, so the regexes made with the pattern always compile.
+    fn crate_names_pattern(&self) -> String {
+        let mut names: Vec<String> = std::iter::once(&*self._crate_name)
+            .chain(self.crate_aliases.iter().copied())
+            .map(|name| name.replace('-', "_"))
+            .collect();
+        if let Ok(manifest) = Manifest::read(&self.manifest_filename()) {
+            names.extend(manifest.lib_names());
+        }
+        names.extend(self.self_aliases());
+        names.retain(|name| is_identifier(name));
+        names.sort();
+        names.dedup();
+        let names: Vec<String> = names.iter().map(|name| regex::escape(name)).collect();
+        format!("(?:{})", names.join("|"))
+    }
+
+    /// The first name given to [`Bundler::crate_name`] or
+    /// [`Bundler::crate_aliases`] that is not a valid crate name.
+    fn invalid_crate_name(&self) -> Option<&str> {
+        std::iter::once(&*self._crate_name)
+            .chain(self.crate_aliases.iter().copied())
+            .filter(|name| !name.is_empty())
+            .find(|name| !is_identifier(&name.replace('-', "_")))
+    }
+
+    /// Names the library gives itself in its root with `extern crate
+    /// self as <name>;`, to have the same paths inside and outside.
+    fn self_aliases(&self) -> Vec<String> {
+        let contents = match fs::read(&*self.librs_filename) {
+            Ok(contents) => String::from_utf8_lossy(&contents).into_owned(),
+            Err(_) => return Vec::new(),
+        };
+        let alias_re = source_line_regex(r" extern  crate  self  as  (?P<name>\w+) ; ");
+        let mut lexer = Lexer::new();
+        let mut aliases = Vec::new();
+        for line in contents.lines() {
+            if !lexer.scan(line).starts_in_code() {
+                continue;
+            }
+            if let Some(cap) = alias_re.captures(line) {
+                aliases.push(cap["name"].to_string());
+            }
+        }
+        aliases
+    }
+
+    /// Edition from the manifest, defaulting to 2015 like cargo.
+    fn detect_edition(&self) -> Edition {
+        Manifest::read(&self.manifest_filename())
+            .ok()
+            .and_then(|manifest| {
+                manifest
+                    .get("package", "edition")
+                    .and_then(Edition::from_year)
+            })
+            .unwrap_or(Edition::Rust2015)
+    }
+
+    /// Apply the rewrite rules of the entry file to `line`.
+    fn rewrite(&self, line: &mut String) {
+        if self.rewrites.is_empty() {
+            return;
+        }
+        let newline = line.ends_with('\n');
+        if newline {
+            line.pop();
+        }
+        for rewrite in &self.rewrites {
+            *line = match rewrite {
+                Rewrite::Regex(pattern, replacement) => {
+                    pattern.replace_all(line, replacement.as_str()).into_owned()
+                }
+                Rewrite::Callback(rewrite) => rewrite(line),
+            };
+        }
+        if newline {
+            line.push('\n');
+        }
+    }
+
+    /// Reason to drop a line of code, if comments are being stripped.
+    fn strip_reason(&self, line: &str) -> Option<SkipReason> {
+        if !self.strip_comments
+            || self.kept_comments.iter().any(|re| re.is_match(line))
+            || self.skip_item_re.is_match(line)
+        {
+            None
+        } else if line.trim().is_empty() {
+            Some(SkipReason::Blank)
+        } else if self.comment_re.is_match(line) {
+            Some(SkipReason::Comment)
+        } else if self.warn_re.is_match(line) {
+            Some(SkipReason::WarnAttribute)
+        } else {
+            None
+        }
+    }
+}
+
+/// JSON object with the path, size and SHA-256 of a file.
+fn file_json(path: &Path, contents: &[u8]) -> String {
+    json::object(&[
+        ("path", json::path(path)),
+        ("size", contents.len().to_string()),
+        ("sha256", json::string(&sha256::hex_digest(contents))),
+    ])
+}
+
+impl<'r, 'a> Run<'r, 'a> {
+    fn new(config: &'r Bundler<'a>) -> Run<'r, 'a> {
+        let ignore_filename = config
+            .manifest_filename()
+            .with_file_name(ignore::IGNORE_FILENAME);
+        let ignore_file = fs::read_to_string(ignore_filename).unwrap_or_default();
+        Run {
+            ignore_rules: IgnoreRules::new(
+                ignore_file
+                    .lines()
+                    .chain(config.ignored_files.iter().copied()),
+            ),
+            config,
+            wrappers: Vec::new(),
+            modules: HashSet::new(),
+            warnings: Vec::new(),
+            skipped_mods: HashSet::new(),
+            opened_files: HashSet::new(),
+            root_extern_crates: HashSet::new(),
+            reexports: HashMap::new(),
+            entry_imports: HashSet::new(),
+            glob_names: None,
+            lib_inlined: false,
+            hoist: MacroHoist::default(),
+            plan: BundlePlan::default(),
+            used_minify_level: 0,
+            pruned_items: Vec::new(),
+            profile_items: 0,
+            feature_items: 0,
+            excluded_items: Vec::new(),
+            verify_runs: Vec::new(),
+            parts: Vec::new(),
+            top_attributes: String::new(),
+            prefix_end: 0,
+            minified: None,
+            edition: Edition::Rust2015,
+            module_stack: Vec::new(),
+            bin_paths: Vec::new(),
+            bin_code: String::new(),
+            entry_source: None,
+            crate_use_re: Regex::new(&format!(
+                r"^(?P<use>\s*(pub(\s*\([^)]*\))?\s+)?use\s+)(::\s*)?{}\s*::",
+                config.crate_names_pattern()
+            ))
+            .unwrap(),
+        }
+    }
+
+    /// Put an `#[allow(unused)]` on the wrappers of the modules that the
+    /// entry file doesn't use, but not on their submodules, which it
+    /// covers, see [`Bundler::allow_unused_set`].
+    fn allow_unused(&mut self, bundle: &mut Vec<u8>) {
+        const ALLOW: &[u8] = b"#[allow(unused)]\n";
+        let wrappers = std::mem::take(&mut self.wrappers);
+        let mut unused: Vec<(usize, &str)> = Vec::new();
+        for (position, path) in &wrappers {
+            let covered = unused
+                .iter()
+                .any(|&(_, module)| path.starts_with(&format!("{}::", module)));
+            if !covered && !self.referenced(path) {
+                unused.push((*position, path));
+            }
+        }
+        for &(position, _) in unused.iter().rev() {
+            bundle.splice(position..position, ALLOW.iter().copied());
+            if let Some(ref mut hoist) = self.hoist.position {
+                if *hoist > position {
+                    *hoist += ALLOW.len();
+                }
+            }
+            if self.prefix_end > position {
+                self.prefix_end += ALLOW.len();
+            }
+        }
+    }
+
+    /// Whether the entry file imports or names something in the module
+    /// at `path`.
+    fn referenced(&self, path: &str) -> bool {
+        let segments: Vec<&str> = path.split("::").collect();
+        let imported = self.bin_paths.iter().any(|used| {
+            let used: Vec<&str> = used.split("::").map(str::trim).collect();
+            used.len() >= segments.len() && used[..segments.len()] == segments[..]
+        });
+        let named = Regex::new(&format!(r"\b{}\s*::", regex::escape(path)))
+            .is_ok_and(|re| re.is_match(&self.bin_code));
+        imported || named
+    }
+
+    /// Contents of the provenance file of the run that created `bundle`.
+    fn provenance_json(&self, bundle: &[u8]) -> Result<String, Error> {
+        let mut inputs = Vec::new();
+        for path in &self.plan.files {
+            let contents = fs::read(path).map_err(|error| Error::Io {
+                path: path.clone(),
+                error,
+            })?;
+            inputs.push(file_json(path, &contents));
+        }
+        let minified = match (&self.minified, self.config.minified_filename()) {
+            (Some(contents), Some(path)) => file_json(&path, contents),
+            _ => "null".to_string(),
+        };
+        Ok(json::object(&[
+            ("bundler_version", json::string(env!("CARGO_PKG_VERSION"))),
+            ("bundle", file_json(&self.config.bundle_filename, bundle)),
+            ("minified", minified),
+            ("inputs", json::array(inputs)),
+            ("edition", json::string(self.edition.year())),
+            ("minify_level", self.used_minify_level.to_string()),
+            ("options", self.config.options_json()),
+        ]))
+    }
+
+    /// Create the bundle in memory, and the minified copy if enabled.
+    fn bundle(&mut self) -> Result<Vec<u8>, Error> {
+        if let Some(name) = self.config.invalid_crate_name() {
+            return Err(Error::InvalidCrateName {
+                name: name.to_string(),
+            });
+        }
+        let edition = self
+            .config
+            .edition
+            .unwrap_or_else(|| self.config.detect_edition());
+        self.edition = edition;
+        let mut bundle = Vec::new();
+        if self.config.library_only {
+            self.hoist.position = Some(0);
+            self.inline_lib(&mut bundle)?;
+            self.lib_inlined = true;
         } else {
-            writeln!(&mut o, "{}", line)
+            self.binrs(&mut bundle, edition)?;
+        }
+        for i in 0..self.plan.modules.len() {
+            self.plan.modules[i].referenced = self.referenced(&self.plan.modules[i].path);
+        }
+        self.allow_unused(&mut bundle);
+        let hoisted = std::mem::take(&mut self.hoist);
+        let position = match hoisted.position {
+            Some(position) => after_inner_attributes(&bundle, position),
+            None => bundle.len(),
+        };
+        bundle.splice(position..position, hoisted.macros);
+        if self.config.collect_licenses && self.config.strip_comments {
+            let mut licenses: Vec<String> = Vec::new();
+            for path in &self.plan.files {
+                let contents = fs::read(path).unwrap_or_default();
+                if let Some(license) = license_header(&String::from_utf8_lossy(&contents)) {
+                    if !licenses.contains(&license) {
+                        licenses.push(license);
+                    }
+                }
+            }
+            let licenses = licenses.concat();
+            bundle.splice(self.prefix_end..self.prefix_end, licenses.into_bytes());
+        }
+        let fields = self.header_fields();
+        if !fields.is_empty() {
+            let header = post::Header::lines(&fields);
+            bundle.splice(self.prefix_end..self.prefix_end, header.into_bytes());
+        }
+        if self.config.git_header {
+            let dir = self.config.librs_filename.parent().unwrap_or(Path::new(""));
+            let minified_filename = self.config.minified_filename();
+            let mut ignore = vec![&*self.config.bundle_filename];
+            ignore.extend(minified_filename.as_deref());
+            ignore.extend(self.config.provenance_filename);
+            match GitRevision::of(dir, &ignore) {
+                Ok(revision) => {
+                    let header = format!("// git revision: {}\n", revision);
+                    bundle.splice(self.prefix_end..self.prefix_end, header.into_bytes());
+                }
+                Err(reason) => self.warn(Warning::NoGitRevision { reason }),
+            }
+        }
+        self.write_assets(&mut bundle, "")?;
+        for &(_, name) in &self.config.assets {
+            let module = name.rsplit_once("::").map_or("", |(module, _)| module);
+            if !module.is_empty() && !self.modules.contains(module) {
+                self.warn(Warning::UnembeddedAsset {
+                    name: name.to_string(),
+                });
+            }
+        }
+        if let Some(kind) = self.config.fastio {
+            if !self.modules.contains("fastio") {
+                bundle.extend_from_slice(fastio::source(kind).as_bytes());
+            }
+        }
+        if self.config.rustfmt_skip == RustfmtSkip::File {
+            self.top_attributes
+                .insert_str(0, "#![cfg_attr(rustfmt, rustfmt::skip)]\n");
+        }
+        let file_start = self.raw_code(BundleAnchor::FileStart);
+        let position = after_inner_attributes(&bundle, 0);
+        bundle.splice(position..position, file_start.into_bytes());
+        self.append_files(&mut bundle, edition)?;
+        bundle.extend_from_slice(self.raw_code(BundleAnchor::FileEnd).as_bytes());
+        let top_attributes = std::mem::take(&mut self.top_attributes);
+        bundle.splice(0..0, top_attributes.into_bytes());
+        if !self.lib_inlined {
+            self.warn(Warning::LibraryNotInlined {
+                crate_name: self.config._crate_name.to_string(),
+            });
+        }
+        let mut text = String::from_utf8_lossy(&bundle).into_owned();
+        if let Some(profile) = self.config.judge_profile {
+            let (stripped, removed) = cfg::strip_profile_items(&text, profile);
+            text = stripped;
+            self.profile_items = removed;
+        }
+        let features = self.config.enabled_features();
+        if !features.is_empty() {
+            let (applied, removed) = cfg::apply_features(&text, &features);
+            text = applied;
+            self.feature_items = removed;
+        }
+        if !self.config.excluded_items.is_empty()
+            || text.contains("bundler:skip-item")
+            || text.contains("sourcebundle")
+        {
+            let (excluded, removed) = items::exclude(
+                &text,
+                &self.config.excluded_items,
+                &self.config.skip_item_re,
+            );
+            text = excluded;
+            self.excluded_items = removed;
+        }
+        if let Some(ref allowed) = self.config.allowed_crates {
+            for name in crates::external_crates(&text) {
+                if !allowed.contains(&name.as_str()) {
+                    self.warn(Warning::DisallowedCrate { name });
+                }
+            }
+        }
+        if !self.config.defines.is_empty() {
+            let (substituted, found) = defines::substitute(&text, &self.config.defines);
+            text = substituted;
+            for &(name, _) in &self.config.defines {
+                if !found.iter().any(|f| f == name) {
+                    self.warn(Warning::UnusedDefine {
+                        name: name.to_string(),
+                    });
+                }
+            }
+        }
+        if !self.config.renamed_modules.is_empty() {
+            let crate_rooted_use = edition == Edition::Rust2015;
+            let (renamed, found) =
+                rename::rename(&text, &self.config.renamed_modules, crate_rooted_use);
+            text = renamed;
+            for &(path, _) in &self.config.renamed_modules {
+                if !found.iter().any(|f| f == path) {
+                    self.warn(Warning::UnusedRename {
+                        module: path.to_string(),
+                    });
+                }
+            }
+        }
+        if self.config.public_api_only {
+            let (pruned, removed) = reach::prune(&text);
+            text = pruned;
+            self.pruned_items = removed;
+        }
+        if !self.config.dropped_attributes.is_empty() {
+            text = minify::drop_attributes(&text, &self.config.dropped_attributes);
+        }
+        if !self.config.stripped_derives.is_empty() {
+            text = minify::strip_derives(&text, &self.config.stripped_derives);
+        }
+        if let Some(max) = self.config.max_rust_version {
+            for (feature, minor) in compat::newer_features(&text, max) {
+                let polyfills = self.config.polyfills.iter().filter(|p| p.0 == feature);
+                let mut polyfilled = false;
+                for &(_, code) in polyfills {
+                    text.push_str(code);
+                    if !code.ends_with('\n') {
+                        text.push('\n');
+                    }
+                    polyfilled = true;
+                }
+                if !polyfilled {
+                    self.warn(Warning::NewerRustFeature {
+                        feature: feature.to_string(),
+                        minor,
+                        max,
+                    });
+                }
+            }
+        }
+        let text = minify::trim(
+            &text,
+            self.config.strip_indentation,
+            self.config.trim_trailing_whitespace,
+        );
+        let config = self.config;
+        let report = self.report(text.as_bytes());
+        let output = |level| {
+            let minify = post::Minify { level };
+            let collapse = config
+                .max_blank_lines
+                .map(|max| post::CollapseBlankLines { max });
+            let mut steps: Vec<&dyn PostProcessor> = vec![&minify];
+            if let Some(ref collapse) = collapse {
+                steps.push(collapse);
+            }
+            if config.table_of_contents {
+                steps.push(&post::TableOfContents);
+            }
+            steps.extend(config.post_processors.iter().map(|p| &*p.0));
+            if config.integrity_footer {
+                steps.push(&post::IntegrityFooter);
+            }
+            post::run(&text, &steps, &report)
+        };
+        let mut level = self.config.minify_level;
+        let mut minified = output(level)?;
+        let auto = self.config.minify_auto && self.config.minified_suffix.is_none();
+        if let Some(limit) = self.config.size_limit.filter(|_| auto) {
+            while minified.len() > limit && level < minify::MAX_LEVEL {
+                level += 1;
+                minified = output(level)?;
+            }
+        }
+        self.used_minify_level = level;
+        if minified.len() > self.config.max_output_size {
+            return Err(Error::LimitExceeded {
+                limit: Limit::OutputSize,
+                max: self.config.max_output_size,
+                file: self.config.bundle_filename.to_path_buf(),
+            });
+        }
+        self.check_forbidden(&minified)?;
+        #[cfg(feature = "check-syntax")]
+        if self.config.check_syntax {
+            syntax::check(&minified)?;
+        }
+        let bundle = minified.into_bytes();
+        self.minified = match self.config.minified_suffix {
+            Some(_) => Some(output(minify::MAX_LEVEL)?.into_bytes()),
+            None => None,
+        };
+        let submitted = self.minified.as_ref().unwrap_or(&bundle).len();
+        if let Some(limit) = self.config.size_limit {
+            if submitted > limit {
+                self.warn(Warning::OversizedOutput {
+                    size: submitted,
+                    limit,
+                });
+            }
+        }
+        Ok(bundle)
+    }
+
+    /// Split `bundle` into files of at most `limit` bytes, writing the
+    /// files of the modules and the list of files, and return the new
+    /// bundle.
+    fn split(&mut self, bundle: Vec<u8>, limit: usize) -> Result<Vec<u8>, Error> {
+        let bundle_filename = &self.config.bundle_filename;
+        let stem = bundle_filename
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let text = String::from_utf8_lossy(&bundle).into_owned();
+        let text = integrity::without_footer(&text);
+        let (mut main, modules) = split::split(text, limit, |name| format!("{}.{}.rs", stem, name));
+        if self.config.integrity_footer {
+            main = integrity::with_footer(main);
+        }
+        let mut files = vec![(bundle_filename.to_path_buf(), main.len())];
+        for (name, mut contents) in modules {
+            if self.config.integrity_footer {
+                contents = integrity::with_footer(contents);
+            }
+            let path = bundle_filename.with_file_name(name);
+            self.write_output(&path, contents.as_bytes())?;
+            files.push((path, contents.len()));
         }
+        let mut list = String::new();
+        for (file, size) in files {
+            if size > limit {
+                self.warn(Warning::OversizedPart {
+                    file: file.clone(),
+                    size,
+                    limit,
+                });
+            }
+            if let Some(name) = file.file_name() {
+                list.push_str(&format!("{}\n", name.to_string_lossy()));
+            }
+            self.parts.push(file);
+        }
+        let path = bundle_filename.with_extension("parts");
+        self.write_output(&path, list.as_bytes())?;
+        Ok(main.into_bytes())
+    }
+
+    /// Fail if the code of `bundle` matches a forbidden pattern.
+    fn check_forbidden(&self, bundle: &str) -> Result<(), Error> {
+        if self.config.forbidden.is_empty() {
+            return Ok(());
+        }
+        let mut lexer = Lexer::new();
+        for (i, line) in bundle.lines().enumerate() {
+            let (_, ranges) = lexer.scan_code(line);
+            let code: Vec<&str> = ranges.into_iter().map(|range| &line[range]).collect();
+            let code = code.join(" ");
+            if let Some(pattern) = self.config.forbidden.iter().find(|re| re.is_match(&code)) {
+                return Err(Error::ForbiddenPattern {
+                    pattern: pattern.as_str().to_string(),
+                    line: i + 1,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Compare the outputs of the bundle, already written, and of the
+    /// original entry file for the inputs given to
+    /// [`Bundler::verify_input`].
+    fn verify(&mut self) {
+        // Each run builds in a directory of its own, so that runs in
+        // parallel, even of the same target, don't replace the programs
+        // of each other.
+        static RUNS: AtomicUsize = AtomicUsize::new(0);
+        let bin_name = self.config.bin_name();
+        let work_dir = std::env::temp_dir().join(format!(
+            "rustsourcebundler-verify-{}-{}-{}",
+            bin_name,
+            std::process::id(),
+            RUNS.fetch_add(1, Ordering::Relaxed)
+        ));
+        let verification = verify::Verification {
+            manifest: &self.config.manifest_filename(),
+            bin_name: &bin_name,
+            bundle: &self.config.bundle_filename,
+            edition: self.edition,
+            work_dir: &work_dir,
+            build_timeout: self.config.verify_timeouts.0,
+            run_timeout: self.config.verify_timeouts.1,
+        };
+        let mut runs = Vec::new();
+        let result = verification.mismatches(&self.config.verify_inputs, &mut runs);
+        let _ = fs::remove_dir_all(&work_dir);
+        for run in runs.iter().filter(|run| run.timed_out) {
+            self.warn(Warning::VerificationTimeout {
+                input: run.input.clone(),
+                program: run.program.to_string(),
+                timeout: verification.run_timeout,
+            });
+        }
+        self.verify_runs = runs;
+        match result {
+            Ok(mismatches) => {
+                for input in mismatches {
+                    self.warn(Warning::OutputMismatch { input });
+                }
+            }
+            Err(reason) => self.warn(Warning::VerificationFailed { reason }),
+        }
+    }
+
+    /// Report of the run that created `bundle`.
+    fn report(&self, bundle: &[u8]) -> Report {
+        let renames = &self.config.renamed_modules;
+        Report {
+            warnings: self.warnings.clone(),
+            size: bundle.len(),
+            minified_size: self.minified.as_ref().map(|minified| minified.len()),
+            minify_level: self.used_minify_level,
+            pruned_items: self.pruned_items.clone(),
+            profile_items: self.profile_items,
+            feature_items: self.feature_items,
+            excluded_items: self.excluded_items.clone(),
+            verify_runs: self.verify_runs.clone(),
+            parts: self.parts.clone(),
+            sources: self
+                .plan
+                .files
+                .iter()
+                .cloned()
+                .chain(
+                    self.config
+                        .source_roots
+                        .iter()
+                        .map(|root| root.to_path_buf()),
+                )
+                .collect(),
+            module_map: self
+                .plan
+                .modules
+                .iter()
+                .filter_map(|module| {
+                    let path = rename::rename_path(&module.path, renames);
+                    Some((path, module.file.clone()?))
+                })
+                .collect(),
+        }
+    }
+
+    fn warn(&mut self, warning: Warning) {
+        if let Some(WarningCallback(ref callback)) = self.config.on_warning {
+            callback(&warning);
+        }
+        self.warnings.push(warning);
+    }
+
+    fn progress(&self, event: ProgressEvent) {
+        if let Some(ProgressCallback(ref callback)) = self.config.on_progress {
+            callback(event);
+        }
+    }
+
+    /// Fail if expanding the module at `file` would go over a limit,
+    /// with `o` the bundle so far.
+    fn check_limits(&self, o: &[u8], file: &Path) -> Result<(), Error> {
+        let config = self.config;
+        let exceeded = if self.module_stack.len() >= config.max_depth {
+            Some((Limit::Depth, config.max_depth))
+        } else if self.plan.files.len() >= config.max_files {
+            Some((Limit::Files, config.max_files))
+        } else if o.len() > config.max_output_size {
+            Some((Limit::OutputSize, config.max_output_size))
+        } else {
+            None
+        };
+        match exceeded {
+            Some((limit, max)) => Err(Error::LimitExceeded {
+                limit,
+                max,
+                file: file.to_path_buf(),
+            }),
+            None => Ok(()),
+        }
+    }
+
+    /// Fields of the header of the bundle: those of the contest from the
+    /// environment, unless given to [`Bundler::header_field`], then
+    /// the others given to it.
+    fn header_fields(&self) -> Vec<(String, String)> {
+        let given = &self.config.header_fields;
+        let mut fields: Vec<(String, String)> = Vec::new();
+        if self.config.contest_header {
+            for &(var, name) in HEADER_ENV_VARS {
+                if given.iter().any(|&(given, _)| given == name) {
+                    continue;
+                }
+                if let Some(value) = env::var(var).ok().filter(|value| !value.is_empty()) {
+                    fields.push((name.to_string(), value));
+                }
+            }
+        }
+        fields.extend(
+            given
+                .iter()
+                .map(|&(name, value)| (name.to_string(), value.to_string())),
+        );
+        fields
+    }
+
+    /// Write the constants of the assets of `module`, given to
+    /// [`Bundler::embed_asset`], with `module` empty for the root.
+    fn write_assets(&mut self, o: &mut Vec<u8>, module: &str) -> Result<(), Error> {
+        for &(file, name) in &self.config.assets {
+            let (asset_module, name) = name.rsplit_once("::").unwrap_or(("", name));
+            if asset_module != module {
+                continue;
+            }
+            let contents = fs::read(file).map_err(|error| Error::Io {
+                path: file.to_path_buf(),
+                error,
+            })?;
+            let contents = String::from_utf8(contents).map_err(|error| {
+                let valid = &error.as_bytes()[..error.utf8_error().valid_up_to()];
+                Error::InvalidUtf8 {
+                    path: file.to_path_buf(),
+                    line: valid.iter().filter(|&&b| b == b'\n').count() + 1,
+                }
+            })?;
+            self.add_file(file);
+            self.write_line(o, &format!("pub const {}: &str = {:?};", name, contents));
+        }
+        Ok(())
+    }
+
+    /// Write the constant with the file of `module`, see
+    /// [`Bundler::source_markers_set`]. The name has the whole path of
+    /// the module, so that glob re-exports of several modules don't
+    /// clash.
+    fn write_source_marker(&mut self, o: &mut Vec<u8>, module: &str, file: &Path) {
+        if !self.config.source_markers {
+            return;
+        }
+        let name = module.replace("::", "_").to_uppercase();
+        let file = self.package_path(file);
+        self.write_line(o, "#[doc(hidden)]");
+        self.write_line(o, "#[allow(dead_code)]");
+        self.write_line(
+            o,
+            &format!("pub const __BUNDLE_SRC_{}: &str = {:?};", name, file),
+        );
+    }
+
+    /// The pattern of [`Bundler::ignore_file`] that ignores `file`, if
+    /// any.
+    fn ignored(&self, file: &Path) -> Option<String> {
+        let pattern = self.ignore_rules.ignoring(&self.package_path(file))?;
+        Some(pattern.to_string())
+    }
+
+    /// Path of `file` from the root of the package, with `/` between
+    /// the components, like `src/graph/flow.rs`.
+    fn package_path(&self, file: &Path) -> String {
+        let manifest_filename = self.config.manifest_filename();
+        let root = manifest_filename.parent().unwrap_or(Path::new(""));
+        let relative = file.strip_prefix(root).unwrap_or(file);
+        let components: Vec<String> = relative
+            .components()
+            .filter(|c| *c != std::path::Component::CurDir)
+            .map(|c| c.as_os_str().to_string_lossy().into_owned())
+            .collect();
+        components.join("/")
+    }
+
+    /// Add `path` to the files of the plan, as it's opened, unless
+    /// it's already there, maybe through a symbolic link.
+    fn add_file(&mut self, path: &Path) {
+        let canonical = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+        if self.opened_files.insert(canonical) {
+            self.plan.files.push(path.to_path_buf());
+        }
+        self.progress(ProgressEvent::FileOpened {
+            path: path.to_path_buf(),
+        });
+    }
+
+    /// Write `contents` to the output file at `path`, creating its
+    /// directory if needed.
+    fn write_output(&self, path: &Path, contents: &[u8]) -> Result<(), Error> {
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            fs::create_dir_all(dir).map_err(|error| Error::Io {
+                path: dir.to_path_buf(),
+                error,
+            })?;
+        }
+        fs::write(path, contents).map_err(|error| Error::Io {
+            path: path.to_path_buf(),
+            error,
+        })?;
+        self.progress(ProgressEvent::BytesWritten {
+            path: path.to_path_buf(),
+            bytes: contents.len(),
+        });
+        Ok(())
+    }
+
+    /// Read the next line of `src`, dealing with invalid UTF-8 according
+    /// to the settings. Returns false at the end of the file.
+    fn read_line(&mut self, src: &mut SourceFile, line: &mut String) -> Result<bool, Error> {
+        match src.read_line(line)? {
+            None => Ok(false),
+            Some(true) => Ok(true),
+            Some(false) if self.config.strict_utf8 => Err(Error::InvalidUtf8 {
+                path: src.path().to_path_buf(),
+                line: src.lineno(),
+            }),
+            Some(false) => {
+                self.warn(Warning::InvalidUtf8 {
+                    file: src.path().to_path_buf(),
+                    line: src.lineno(),
+                });
+                Ok(true)
+            }
+        }
+    }
+
+    /// Whether the file at `path` is to be copied verbatim, as its
+    /// delimiters are not balanced and [`Bundler::lenient_set`] is on.
+    fn unbalanced(&mut self, path: &Path) -> Result<bool, Error> {
+        let (line, problem) = match balance::check(&self.read_source(path)?) {
+            Some(imbalance) => imbalance,
+            None => return Ok(false),
+        };
+        if !self.config.lenient {
+            return Err(Error::UnbalancedDelimiters {
+                path: path.to_path_buf(),
+                line,
+                problem: problem.to_string(),
+            });
+        }
+        self.warn(Warning::UnbalancedFile {
+            file: path.to_path_buf(),
+            line,
+            problem: problem.to_string(),
+        });
+        Ok(true)
+    }
+
+    /// Contents of the source file at `path`, or the source of the
+    /// entry file given to [`Bundler::bundle_snippet`].
+    fn read_source(&self, path: &Path) -> Result<String, Error> {
+        match self.entry_source {
+            Some(ref source) if path == &*self.config.binrs_filename => Ok(source.clone()),
+            _ => {
+                let contents = fs::read(path).map_err(|error| Error::Io {
+                    path: path.to_path_buf(),
+                    error,
+                })?;
+                Ok(String::from_utf8_lossy(&contents).into_owned())
+            }
+        }
+    }
+
+    /// Copy the rest of `src` to the bundle as it is.
+    fn copy_verbatim(&mut self, o: &mut Vec<u8>, src: &mut SourceFile) -> Result<(), Error> {
+        let mut line = String::new();
+        while self.read_line(src, &mut line)? {
+            trim_line(&mut line);
+            self.write_line(o, &line);
+            line.clear();
+        }
+        Ok(())
+    }
+
+    /// Warn if a path imported by the entry file goes through a module
+    /// that is not bundled.
+    fn check_import(&mut self, path: &str) {
+        let segments: Vec<&str> = path.split("::").map(str::trim).collect();
+        let excluded =
+            (1..segments.len()).any(|len| self.skipped_mods.contains(&segments[..len].join("::")));
+        if excluded {
+            self.warn(Warning::UnresolvedImport {
+                path: path.to_string(),
+            });
+        }
+    }
+
+    /// Paths to import in the bundle for `path`, imported from the
+    /// library by the entry file: none if it's already in scope at
+    /// the root, the items re-exported by the module for globs, or the
+    /// path itself.
+    fn bin_use_paths(&self, path: &str, edition: Edition) -> Vec<String> {
+        // The root of the library is the root of the bundle, so its
+        // items and modules are in scope already:
+        if path == "*" || path.chars().all(|c| c.is_alphanumeric() || c == '_') {
+            return Vec::new();
+        }
+        let glob_of = path.strip_suffix("::*").map(str::trim);
+        if let Some(reexports) = glob_of.and_then(|m| self.reexports.get(m)) {
+            let module = glob_of.unwrap_or("");
+            let mut uses: Vec<String> = reexports
+                .paths
+                .iter()
+                .flat_map(|reexport| split_use_group(reexport))
+                .filter(|reexport| !self.entry_imports.contains(use_name(reexport)))
+                .map(|reexport| self.root_use_path(module, &reexport, edition))
+                .collect();
+            if reexports.other_items {
+                uses.push(self.root_use_path("", &format!("crate::{}", path), edition));
+            }
+            return uses;
+        }
+        vec![self.root_use_path("", &format!("crate::{}", path), edition)]
+    }
+
+    /// Imports of the names the entry file uses, for `path` if it's a
+    /// glob import of a module of the library, with
+    /// [`Bundler::explicit_globs_set`]. `o` is the bundle so far.
+    fn explicit_glob(&mut self, o: &[u8], path: &str, edition: Edition) -> Option<Vec<String>> {
+        let module = path.strip_suffix("::*")?.trim();
+        if !self.config.explicit_globs || !self.lib_inlined || !self.modules.contains(module) {
+            return None;
+        }
+        if self.glob_names.is_none() {
+            let entry = self.read_source(&self.config.binrs_filename).ok()?;
+            let bundle = String::from_utf8_lossy(o);
+            self.glob_names = Some(globs::GlobNames::new(&bundle, &entry));
+        }
+        let mut names = self.glob_names.as_ref()?.used_names(module)?;
+        names.retain(|name| !self.entry_imports.contains(name));
+        let path = match names.len() {
+            0 => return Some(Vec::new()),
+            1 => format!("crate::{}::{}", module, names[0]),
+            _ => format!("crate::{}::{{{}}}", module, names.join(", ")),
+        };
+        Some(vec![self.root_use_path("", &path, edition)])
+    }
+
+    /// Path to use from the root of the bundle for `path`, found in a
+    /// `use` of `module`.
+    fn root_use_path(&self, module: &str, path: &str, edition: Edition) -> String {
+        let mut base: Vec<&str> = module.split("::").filter(|s| !s.is_empty()).collect();
+        let mut rest = path.trim();
+        let in_crate = if let Some(r) = rest.strip_prefix("crate::") {
+            base.clear();
+            rest = r;
+            true
+        } else if rest.starts_with("self::") || rest.starts_with("super::") {
+            loop {
+                if let Some(r) = rest.strip_prefix("self::") {
+                    rest = r;
+                } else if let Some(r) = rest.strip_prefix("super::") {
+                    base.pop();
+                    rest = r;
+                } else {
+                    break;
+                }
+            }
+            true
+        } else if edition >= Edition::Rust2018 {
+            // A submodule, or else a path to another crate:
+            let first = rest.split("::").next().unwrap_or("").trim();
+            self.modules.contains(&format!("{}::{}", module, first))
+        } else {
+            // 2015 paths are relative to the root of the crate.
+            base.clear();
+            true
+        };
+        if !in_crate {
+            return rest.to_string();
+        }
+        base.push(rest);
+        let path = base.join("::");
+        if edition >= Edition::Rust2018 {
+            format!("crate::{}", path)
+        } else {
+            path
+        }
+    }
+
+    fn skip_line(&mut self, file: &Path, line: usize, reason: SkipReason) {
+        self.plan.skipped_lines.push(SkippedLine {
+            file: file.to_path_buf(),
+            line,
+            reason,
+        });
+    }
+
+    /// Whether the line is an `extern crate` already seen at the root of
+    /// the bundle. Only for lines of the entry file and lib.rs.
+    fn duplicate_extern_crate(&mut self, line: &str) -> bool {
+        match self.config.extern_re.captures(line) {
+            Some(cap) => {
+                let name = cap["c"].split_whitespace().collect::<Vec<_>>().join(" ");
+                !self.root_extern_crates.insert(name)
+            }
+            None => false,
+        }
+    }
+
+    /// Warn about `include!` and `env!` invocations that are going to
+    /// the bundle as-is.
+    fn check_include(&mut self, file: &Path, lineno: usize, line: &str) {
+        if self.config.include_re.is_match(line) {
+            self.warn(Warning::UnexpandedInclude {
+                file: file.to_path_buf(),
+                line: lineno,
+            });
+        }
+        if self.config.env_re.is_match(line) {
+            self.warn(Warning::EnvMacro {
+                file: file.to_path_buf(),
+                line: lineno,
+            });
+        }
+    }
+
+    /// From the file that has the main() function, expand "extern
+    /// crate <_crate_name>" into lib.rs contents, and smartly skips
+    /// "use <_crate_name>::" lines.
+    fn binrs(&mut self, o: &mut Vec<u8>, edition: Edition) -> Result<(), Error> {
+        let mut bin_src = match self.entry_source {
+            Some(ref source) => SourceFile::from_string(&self.config.binrs_filename, source),
+            None => SourceFile::open(&self.config.binrs_filename)?,
+        };
+
+        let crate_names = self.config.crate_names_pattern();
+        let extcrate_re = source_line_regex(format!(r" extern  crate  {} ; ", crate_names));
+        let usecrate_re =
+            source_line_regex(format!(r" use  (:: )?{} :: (?P<path>.*) ; ", crate_names).as_str());
+        let has_marker = self
+            .read_source(&self.config.binrs_filename)
+            .map(|contents| {
+                contents
+                    .lines()
+                    .any(|l| self.config.insert_lib_re.is_match(l))
+            })
+            .unwrap_or(false);
+        self.entry_imports = self
+            .read_source(&self.config.binrs_filename)
+            .map(|contents| explicit_imports(&contents))
+            .unwrap_or_default();
+        self.add_file(&self.config.binrs_filename);
+        if self.unbalanced(&self.config.binrs_filename)? {
+            return self.copy_verbatim(o, &mut bin_src);
+        }
+
+        let bin_dir = self.config.binrs_filename.parent().unwrap_or(Path::new(""));
+        let mut lexer = Lexer::new();
+        let mut nesting = Nesting::new();
+        let mut line = String::new();
+        let mut doc_include = None;
+        let mut in_prefix = true;
+        let mut held = HeldAttrs::default();
+        // Line of the `// bundler:only-local` of the section we are in:
+        let mut local_section = None;
+        // Delimiter depth of the inner attribute being moved or removed:
+        let mut attr_depth = 0;
+        let attr_policy = self.config.entry_inner_attributes;
+        // Whether the library is used only in blocks, and goes at the end:
+        let mut inline_at_end = false;
+        while self.read_line(&mut bin_src, &mut line)? {
+            let lineno = bin_src.lineno();
+            self.config.rewrite(&mut line);
+            if let Some(cap) = self.config.only_bundle_re.captures(line.trim_end()) {
+                line = format!("{}{}\n", &cap["indent"], &cap["code"]);
+            }
+            let (scan, code) = lexer.scan_code(&line);
+            let item = nesting.item();
+            let at_top = nesting.at_top();
+            nesting.advance(&line, &code);
+            trim_line(&mut line);
+            if local_section.is_some() || self.config.only_local_re.is_match(&line) {
+                let reason = if local_section.is_none() {
+                    local_section = Some(lineno);
+                    SkipReason::Directive
+                } else if self.config.section_end_re.is_match(&line) {
+                    local_section = None;
+                    SkipReason::Directive
+                } else {
+                    SkipReason::LocalOnly
+                };
+                self.skip_line(&self.config.binrs_filename, lineno, reason);
+                line.clear();
+                continue;
+            }
+            if !scan.starts_in_code() {
+                self.write_line(o, &line);
+                line.clear();
+                continue;
+            }
+            let binrs_filename = &*self.config.binrs_filename;
+            if self.doc_include(
+                o,
+                &mut doc_include,
+                &line,
+                &scan,
+                binrs_filename,
+                lineno,
+                true,
+            )? {
+                line.clear();
+                continue;
+            }
+            in_prefix = in_prefix
+                && lineno <= self.config.prefix_comments
+                && self.config.comment_re.is_match(&line);
+            if in_prefix {
+                self.write_line(o, &line);
+                self.prefix_end = o.len();
+                line.clear();
+                continue;
+            }
+            if attr_depth > 0
+                || (attr_policy != InnerAttributes::Keep
+                    && self.config.innerattr_re.is_match(&line)
+                    && self.config.strip_reason(&line).is_none())
+            {
+                attr_depth += scan.delta;
+                if attr_policy == InnerAttributes::Top {
+                    self.top_attributes.push_str(&line);
+                    self.top_attributes.push('\n');
+                } else {
+                    let binrs_filename = &self.config.binrs_filename;
+                    self.skip_line(binrs_filename, lineno, SkipReason::InnerAttribute);
+                }
+                line.clear();
+                continue;
+            }
+            if self.hoist.position.is_none()
+                && !self.config.comment_re.is_match(&line)
+                && !self.config.innerattr_re.is_match(&line)
+            {
+                self.hoist.position = Some(o.len());
+            }
+            if self.config.strip_reason(&line).is_none()
+                && !self.config.mod_re.is_match(&line)
+                && !extcrate_re.is_match(&line)
+                && !self.holds_attr(&held, &line, &scan)
+            {
+                self.flush_attrs(o, &mut held);
+            }
+            if self.config.insert_lib_re.is_match(&line) || extcrate_re.is_match(&line) {
+                let is_marker = self.config.insert_lib_re.is_match(&line);
+                let reason = if is_marker {
+                    SkipReason::Directive
+                } else {
+                    self.drop_attrs(&mut held, SkipReason::ExternCrate);
+                    SkipReason::ExternCrate
+                };
+                self.skip_line(&self.config.binrs_filename, lineno, reason);
+                if !self.lib_inlined && (has_marker == is_marker) {
+                    self.inline_lib(o)?;
+                    self.lib_inlined = true;
+                }
+            } else if let Some(reason) = self.config.strip_reason(&line) {
+                self.skip_line(&self.config.binrs_filename, lineno, reason);
+            } else if self.duplicate_extern_crate(&line) {
+                self.skip_line(
+                    &self.config.binrs_filename,
+                    lineno,
+                    SkipReason::DuplicateExternCrate,
+                );
+            } else if let Some(cap) = usecrate_re.captures(&line) {
+                // The library can't go in the body of a function, where
+                // its modules wouldn't be at `crate::`.
+                if edition >= Edition::Rust2018 && !self.lib_inlined && !has_marker {
+                    if at_top {
+                        self.inline_lib(o)?;
+                        self.lib_inlined = true;
+                    } else {
+                        inline_at_end = true;
+                    }
+                }
+                let moduse = &cap["path"];
+                let mut uses = Vec::new();
+                for path in split_use_group(moduse) {
+                    self.check_import(&path);
+                    match self.explicit_glob(o, &path, edition) {
+                        Some(explicit) => uses.extend(explicit),
+                        None => uses.extend(self.bin_use_paths(&path, edition)),
+                    }
+                    self.bin_paths.push(path);
+                }
+                if uses.is_empty() {
+                    self.skip_line(
+                        &self.config.binrs_filename,
+                        lineno,
+                        SkipReason::RedundantUse,
+                    );
+                }
+                for path in uses {
+                    self.write_line(o, &format!("use {};", path));
+                    let path = path.strip_prefix("crate::").unwrap_or(&path);
+                    self.bin_paths.push(path.to_string());
+                }
+            } else if self.holds_attr(&held, &line, &scan) {
+                let binrs_filename = &*self.config.binrs_filename;
+                self.hold_attr(o, &mut held, &line, binrs_filename, lineno, &scan);
+            } else if let Some(cap) = self.config.mod_re.captures(&line) {
+                let modname = &cap["m"];
+                let modvis = cap.name("vis").map_or("", |v| v.as_str().trim());
+                let binrs_filename = &*self.config.binrs_filename;
+                check_mod_place(modname, item, binrs_filename, lineno)?;
+                let modfile = self.take_path_attr(&mut held, bin_dir);
+                if !self.config.skip_mod.contains(modname) {
+                    let modpath = self.bin_module_path(modname);
+                    self.flush_attrs(o, &mut held);
+                    self.write_mod_attrs(o, &cap["attrs"]);
+                    self.usemod(o, modvis, modname, &modpath, modname, modfile.as_deref())
+                        .map_err(|error| error.declared_at(binrs_filename, lineno))?;
+                } else {
+                    self.drop_attrs(&mut held, SkipReason::ExcludedModule);
+                    self.exclude_module(&self.config.binrs_filename, lineno, modname);
+                }
+                self.bin_paths.push(modname.to_string());
+            } else {
+                self.check_include(&self.config.binrs_filename, lineno, &line);
+                self.write_line(o, &line);
+                self.bin_code.push_str(&line);
+                self.bin_code.push('\n');
+            }
+            line.clear();
+        }
+        self.flush_attrs(o, &mut held);
+        if inline_at_end && !self.lib_inlined {
+            self.inline_lib(o)?;
+            self.lib_inlined = true;
+        }
+        if let Some(line) = local_section {
+            self.warn(Warning::UnterminatedSection {
+                file: self.config.binrs_filename.to_path_buf(),
+                line,
+            });
+        }
+        Ok(())
+    }
+
+    /// Read `line` of `file` if it's part of an inner attribute with an
+    /// `include_str!`, and remove or inline the attribute, see
+    /// [`Bundler::doc_includes`], once it's complete. `crate_root` is
+    /// whether `file` is the root of a crate, whose attributes go to
+    /// the top of the bundle.
+    #[allow(clippy::too_many_arguments)]
+    fn doc_include(
+        &mut self,
+        o: &mut Vec<u8>,
+        pending: &mut Option<DocInclude>,
+        line: &str,
+        scan: &LineScan,
+        file: &Path,
+        lineno: usize,
+        crate_root: bool,
+    ) -> Result<bool, Error> {
+        if pending.is_none()
+            && (!self.config.innerattr_re.is_match(line) || !line.contains("include_str!"))
+        {
+            return Ok(false);
+        }
+        let attr = pending.get_or_insert_with(DocInclude::default);
+        attr.text.push_str(line);
+        attr.text.push('\n');
+        attr.depth += scan.delta;
+        self.skip_line(file, lineno, SkipReason::DocInclude);
+        if attr.depth > 0 {
+            return Ok(true);
+        }
+        let text = std::mem::take(&mut attr.text);
+        *pending = None;
+        let cap = self.config.doc_include_re.captures(&text);
+        let path = match cap {
+            Some(cap) if self.config.doc_includes == DocIncludes::Inline => cap["path"].to_string(),
+            _ => return Ok(true),
+        };
+        let path = file.parent().unwrap_or(Path::new("")).join(path);
+        let contents = fs::read(&path).map_err(|error| Error::Io {
+            path: path.clone(),
+            error,
+        })?;
+        self.add_file(&path);
+        let doc = format!("#![doc = {:?}]", String::from_utf8_lossy(&contents));
+        if crate_root {
+            self.top_attributes.push_str(&doc);
+            self.top_attributes.push('\n');
+        } else {
+            self.write_line(o, &doc);
+        }
+        Ok(true)
+    }
+
+    /// Code given to [`Bundler::insert_raw`] for `anchor`.
+    fn raw_code(&self, anchor: BundleAnchor) -> String {
+        let mut code = String::new();
+        for &(_, chunk) in self.config.raw_chunks.iter().filter(|c| c.0 == anchor) {
+            code.push_str(chunk);
+            if !chunk.ends_with('\n') {
+                code.push('\n');
+            }
+        }
+        code
+    }
+
+    /// Write the files given to [`Bundler::append_file`].
+    fn append_files(&mut self, o: &mut Vec<u8>, edition: Edition) -> Result<(), Error> {
+        let crate_names = self.config.crate_names_pattern();
+        let extcrate_re = source_line_regex(format!(r" extern  crate  {} ; ", crate_names));
+        let crate_path_re = Regex::new(&format!(r"(?:::\s*)?\b{}\s*::", crate_names)).unwrap();
+        let root = if edition >= Edition::Rust2018 {
+            "crate::"
+        } else {
+            "::"
+        };
+        for &(file, wrap) in &self.config.appended_files {
+            let mut src = SourceFile::open(file)?;
+            self.add_file(file);
+            if let Some(wrap) = wrap {
+                self.write_line(o, "#[cfg(test)]");
+                self.write_line(o, &format!("mod {} {{", wrap));
+            }
+            let mut lexer = Lexer::new();
+            let mut line = String::new();
+            while self.read_line(&mut src, &mut line)? {
+                let lineno = src.lineno();
+                trim_line(&mut line);
+                let (scan, code) = lexer.scan_code(&line);
+                if !scan.starts_in_code() {
+                    self.write_line(o, &line);
+                } else if let Some(reason) = self.config.strip_reason(&line) {
+                    self.skip_line(file, lineno, reason);
+                } else if extcrate_re.is_match(&line) {
+                    self.skip_line(file, lineno, SkipReason::ExternCrate);
+                } else {
+                    let mut rewritten = String::new();
+                    let mut last = 0;
+                    for range in code {
+                        for m in crate_path_re.find_iter(&line[range.clone()]) {
+                            rewritten.push_str(&line[last..range.start + m.start()]);
+                            rewritten.push_str(root);
+                            last = range.start + m.end();
+                        }
+                    }
+                    rewritten.push_str(&line[last..]);
+                    self.write_line(o, &rewritten);
+                }
+                line.clear();
+            }
+            if wrap.is_some() {
+                self.write_line(o, "}");
+            }
+        }
+        Ok(())
+    }
+
+    /// Expand lib.rs, with the code inserted before and after it. The
+    /// code before it goes after its inner attributes, which have to
+    /// come first.
+    fn inline_lib(&mut self, o: &mut Vec<u8>) -> Result<(), Error> {
+        let start = o.len();
+        self.librs(o)?;
+        let before = self.raw_code(BundleAnchor::BeforeLib);
+        let position = after_inner_attributes(o, start);
+        for (wrapper, _) in &mut self.wrappers {
+            if *wrapper >= position {
+                *wrapper += before.len();
+            }
+        }
+        o.splice(position..position, before.into_bytes());
+        o.extend_from_slice(self.raw_code(BundleAnchor::AfterLib).as_bytes());
+        Ok(())
+    }
+
+    /// Expand lib.rs contents and "pub mod <>;" lines.
+    fn librs(&mut self, o: &mut Vec<u8>) -> Result<(), Error> {
+        let mut lib_src = SourceFile::open(&self.config.librs_filename)?;
+        self.add_file(&self.config.librs_filename);
+        if self.unbalanced(&self.config.librs_filename)? {
+            return self.copy_verbatim(o, &mut lib_src);
+        }
+
+        let src_dir = self.config.librs_filename.parent().unwrap_or(Path::new(""));
+        let mut lexer = Lexer::new();
+        let mut nesting = Nesting::new();
+        let mut line = String::new();
+        let mut held = HeldAttrs::default();
+        let mut doc_include = None;
+        while self.read_line(&mut lib_src, &mut line)? {
+            let lineno = lib_src.lineno();
+            let file = lib_src.path().to_path_buf();
+            let (scan, code) = lexer.scan_code(&line);
+            let item = nesting.item();
+            nesting.advance(&line, &code);
+            trim_line(&mut line);
+            let strip = scan
+                .starts_in_code()
+                .then(|| self.config.strip_reason(&line));
+            let strip = strip.flatten();
+            if strip.is_none()
+                && !(scan.starts_in_code()
+                    && (self.config.mod_re.is_match(&line) || self.holds_attr(&held, &line, &scan)))
+            {
+                self.flush_attrs(o, &mut held);
+            }
+            if let Some(reason) = strip {
+                self.skip_line(&file, lineno, reason);
+            } else if self.hoist_line(o, &line, &scan) {
+            } else if !scan.starts_in_code() {
+                self.write_line(o, &line);
+            } else if self.doc_include(o, &mut doc_include, &line, &scan, &file, lineno, true)? {
+            } else if self.config.no_std_re.is_match(&line) {
+                self.skip_line(&file, lineno, SkipReason::NoStd);
+            } else if self.config.library_only && self.config.innerattr_re.is_match(&line) {
+                self.skip_line(&file, lineno, SkipReason::InnerAttribute);
+            } else if self.duplicate_extern_crate(&line) {
+                self.skip_line(&file, lineno, SkipReason::DuplicateExternCrate);
+            } else if let Some((included, head)) = self.included_file(&lib_src, &line) {
+                self.skip_line(&file, lineno, SkipReason::ExpandedInclude);
+                self.add_file(&included);
+                if let Some(ref head) = head {
+                    self.write_line(o, head);
+                }
+                lib_src.include(&included, head.map(|_| "}".to_string()))?;
+            } else if self.holds_attr(&held, &line, &scan) {
+                self.hold_attr(o, &mut held, &line, &file, lineno, &scan);
+            } else if let Some(cap) = self.config.mod_re.captures(&line) {
+                let modname = &cap["m"];
+                let modvis = cap.name("vis").map_or("", |v| v.as_str().trim());
+                check_mod_place(modname, item, &file, lineno)?;
+                let modfile = self.take_path_attr(&mut held, src_dir);
+                if !self.config.skip_mod.contains(modname) {
+                    let modpath = src_dir.join(modname);
+                    self.flush_attrs(o, &mut held);
+                    self.write_mod_attrs(o, &cap["attrs"]);
+                    self.usemod(o, modvis, modname, &modpath, modname, modfile.as_deref())
+                        .map_err(|error| error.declared_at(&file, lineno))?;
+                } else {
+                    self.drop_attrs(&mut held, SkipReason::ExcludedModule);
+                    self.exclude_module(&file, lineno, modname);
+                }
+            } else {
+                self.check_include(&file, lineno, &line);
+                self.write_line(o, &line);
+            }
+            line.clear(); // clear to reuse the buffer
+        }
+        self.flush_attrs(o, &mut held);
+        for &(name, file) in &self.config.forced_mods {
+            if !self.modules.contains(name) {
+                self.usemod(o, "pub", name, &src_dir.join(name), name, Some(file))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Called to expand random .rs files from lib.rs. It recursivelly
+    /// expands further "pub mod <>;" lines and updates the list of
+    /// "use <>;" lines that have to be skipped. `mod_path` is the path
+    /// of the module's file without the `.rs`, like `src/graph/flow`.
+    fn usemod(
+        &mut self,
+        o: &mut Vec<u8>,
+        mod_vis: &str,
+        mod_name: &str,
+        mod_path: &Path,
+        mod_import: &str,
+        mod_file: Option<&Path>,
+    ) -> Result<(), Error> {
+        let mod_filenames0 = match mod_file {
+            Some(mod_file) => vec![mod_file.to_path_buf()],
+            None => {
+                let relative: PathBuf = mod_import.split("::").collect();
+                let mut candidates = module_files(mod_path).to_vec();
+                for root in &self.config.source_roots {
+                    candidates.extend(module_files(&root.join(&relative)));
+                }
+                candidates
+            }
+        };
+        let mod_filename = match mod_filenames0.iter().find(|f| f.is_file()) {
+            Some(mod_filename) => mod_filename.clone(),
+            None => {
+                return Err(Error::ModuleNotFound {
+                    name: mod_import.to_string(),
+                    candidates: mod_filenames0,
+                    declaration: None,
+                })
+            }
+        };
+        let canonical = fs::canonicalize(&mod_filename).ok();
+        let open = std::iter::once(&*self.config.binrs_filename)
+            .chain(std::iter::once(&*self.config.librs_filename))
+            .chain(
+                self.module_stack
+                    .iter()
+                    .filter_map(|&i| self.plan.modules[i].file.as_deref()),
+            )
+            .any(|file| fs::canonicalize(file).ok() == canonical);
+        if open {
+            return Err(Error::ModuleCycle {
+                name: mod_import.to_string(),
+                file: mod_filename,
+            });
+        }
+        if let Some(pattern) = self.ignored(&mod_filename) {
+            if self.config.ignored_modules == IgnoredModules::Fail {
+                return Err(Error::IgnoredModule {
+                    name: mod_import.to_string(),
+                    file: mod_filename,
+                    pattern,
+                });
+            }
+            self.plan.modules.push(PlannedModule {
+                path: mod_import.to_string(),
+                ..PlannedModule::default()
+            });
+            let vis = self.wrapper_vis(mod_vis, mod_import);
+            self.write_line(o, &format!("{}mod {} {{}}", vis, mod_name));
+            return Ok(());
+        }
+        self.check_limits(o, &mod_filename)?;
+        let mut mod_src = SourceFile::open(&mod_filename)?;
+        self.add_file(&mod_filename);
+        let verbatim = self.unbalanced(&mod_filename)?;
+        self.progress(ProgressEvent::ModuleExpanded {
+            path: mod_import.to_string(),
+            file: mod_filename.clone(),
+        });
+        self.plan.modules.push(PlannedModule {
+            path: mod_import.to_string(),
+            file: Some(mod_filename.clone()),
+            expanded: true,
+            ..PlannedModule::default()
+        });
+        self.module_stack.push(self.plan.modules.len() - 1);
+
+        let mut lexer = Lexer::new();
+        let mut nesting = Nesting::new();
+        let mut line = String::new();
+
+        let nested = mod_import.contains("::");
+        let wrapper_vis = self.wrapper_vis(mod_vis, mod_import);
+        if self.config.allow_unused {
+            self.wrappers.push((o.len(), mod_import.to_string()));
+        }
+        if !self.config.allow_lints.is_empty() && !nested {
+            let lints = self.config.allow_lints.join(", ");
+            self.write_line(o, &format!("#[allow({})]", lints));
+        }
+        // The inner docs of the file document the module, so they become
+        // outer docs of the wrapper, where nothing can end up before them.
+        let (docs, leading_docs) = leading_inner_docs(&mod_filename);
+        for doc in docs {
+            if self.config.strip_reason(&doc).is_none() {
+                self.write_line(o, &doc.replacen("//!", "///", 1));
+            }
+        }
+        if self.config.rustfmt_skip == RustfmtSkip::Wrappers && !nested {
+            self.write_line(o, "#[rustfmt::skip]");
+        }
+        self.write_line(o, &format!("{}mod {} {{", wrapper_vis, mod_name));
+        self.modules.insert(String::from(mod_import));
+        if verbatim {
+            self.copy_verbatim(o, &mut mod_src)?;
+            self.write_assets(o, mod_import)?;
+            self.write_source_marker(o, mod_import, &mod_filename);
+            self.write_line(o, "}");
+            self.module_stack.pop();
+            return Ok(());
+        }
+
+        // Files given with `#[path]` work like mod.rs files: their
+        // submodules are in the same directory.
+        let mod_dir = mod_filename.parent().unwrap_or(Path::new(""));
+        let submod_base = match mod_file {
+            Some(_) => mod_dir,
+            None => mod_path,
+        };
+        let mut held = HeldAttrs::default();
+        let mut doc_include = None;
+        while self.read_line(&mut mod_src, &mut line)? {
+            let lineno = mod_src.lineno();
+            let file = mod_src.path().to_path_buf();
+            let (scan, code) = lexer.scan_code(&line);
+            let item = nesting.item();
+            nesting.advance(&line, &code);
+            trim_line(&mut line);
+            let strip = scan
+                .starts_in_code()
+                .then(|| self.config.strip_reason(&line));
+            let strip = strip.flatten();
+            if lineno <= leading_docs && file == mod_filename {
+                if let Some(reason) = strip {
+                    self.skip_line(&file, lineno, reason);
+                }
+                line.clear();
+                continue;
+            }
+            if strip.is_none()
+                && !(scan.starts_in_code()
+                    && (self.config.mod_re.is_match(&line) || self.holds_attr(&held, &line, &scan)))
+            {
+                self.flush_attrs(o, &mut held);
+            }
+            if let Some(reason) = strip {
+                self.skip_line(&file, lineno, reason);
+            } else if self.hoist_line(o, &line, &scan) {
+            } else if !scan.starts_in_code() {
+                self.write_line(o, &line);
+            } else if self.doc_include(o, &mut doc_include, &line, &scan, &file, lineno, false)? {
+            } else if let Some((included, head)) = self.included_file(&mod_src, &line) {
+                self.skip_line(&file, lineno, SkipReason::ExpandedInclude);
+                self.add_file(&included);
+                if let Some(ref head) = head {
+                    self.write_line(o, head);
+                }
+                mod_src.include(&included, head.map(|_| "}".to_string()))?;
+            } else if self.holds_attr(&held, &line, &scan) {
+                self.hold_attr(o, &mut held, &line, &file, lineno, &scan);
+            } else if let Some(cap) = self.config.mod_re.captures(&line) {
+                let submodname = &cap["m"];
+                let submodvis = cap.name("vis").map_or("", |v| v.as_str().trim());
+                check_mod_place(submodname, item, &file, lineno)?;
+                self.reexports
+                    .entry(mod_import.to_string())
+                    .or_default()
+                    .other_items = true;
+                let submodfile = self.take_path_attr(&mut held, mod_dir);
+                let submodimport = format!("{}::{}", mod_import, submodname);
+                if submodname != "tests" && !self.config.skip_mod.contains(submodimport.as_str()) {
+                    self.flush_attrs(o, &mut held);
+                    self.write_mod_attrs(o, &cap["attrs"]);
+                    self.usemod(
+                        o,
+                        submodvis,
+                        submodname,
+                        &submod_base.join(submodname),
+                        submodimport.as_str(),
+                        submodfile.as_deref(),
+                    )
+                    .map_err(|error| error.declared_at(&file, lineno))?;
+                } else {
+                    self.drop_attrs(&mut held, SkipReason::ExcludedModule);
+                    self.exclude_module(&file, lineno, &submodimport);
+                }
+            } else {
+                // The library is inlined at the root of the bundle.
+                if let Cow::Owned(rewritten) = self.crate_use_re.replace(&line, "${use}crate::") {
+                    line = rewritten;
+                }
+                let reexports = self.reexports.entry(mod_import.to_string()).or_default();
+                if let Some(cap) = self.config.pub_use_re.captures(&line) {
+                    reexports.paths.push(cap["path"].to_string());
+                } else if !self.config.use_re.is_match(&line)
+                    && !self.config.attribute_re.is_match(&line)
+                {
+                    reexports.other_items = true;
+                }
+                self.check_include(&file, lineno, &line);
+                self.write_line(o, &line);
+            }
+            line.clear(); // clear to reuse the buffer
+        }
+        self.flush_attrs(o, &mut held);
+        self.write_assets(o, mod_import)?;
+        self.write_source_marker(o, mod_import, &mod_filename);
+
+        self.write_line(o, "}");
+        self.module_stack.pop();
+
+        Ok(())
+    }
+
+    /// Path, without the `.rs`, of the module `name` declared in the
+    /// entry file: next to it, as rustc does for the root of a crate,
+    /// or in a directory named after it, as in `src/bin/a/helpers.rs`
+    /// for `src/bin/a.rs`. It never depends on where lib.rs is, so
+    /// `src/main.rs` and `src/bin/<name>.rs` work the same.
+    fn bin_module_path(&self, name: &str) -> PathBuf {
+        let bin = &*self.config.binrs_filename;
+        let dir = bin.parent().unwrap_or(Path::new(""));
+        let beside = dir.join(name);
+        match bin.file_stem() {
+            Some(stem) if !module_files(&beside).iter().any(|f| f.is_file()) => {
+                let nested = dir.join(stem).join(name);
+                if module_files(&nested).iter().any(|f| f.is_file()) {
+                    nested
+                } else {
+                    beside
+                }
+            }
+            _ => beside,
+        }
+    }
+
+    /// Skip the declaration of the module `path`, excluded from the
+    /// bundle.
+    fn exclude_module(&mut self, file: &Path, lineno: usize, path: &str) {
+        self.skip_line(file, lineno, SkipReason::ExcludedModule);
+        self.plan.modules.push(PlannedModule {
+            path: path.to_string(),
+            file: None,
+            ..PlannedModule::default()
+        });
+        self.skipped_mods.insert(path.to_string());
+        self.warn(Warning::SkippedModule {
+            name: path.to_string(),
+        });
+    }
+
+    /// Visibility of the wrapper of the module `mod_import`, declared
+    /// with `mod_vis`, with a space after it if it's not empty, see
+    /// [`Bundler::wrapper_visibility`].
+    fn wrapper_vis(&self, mod_vis: &str, mod_import: &str) -> String {
+        let nested = mod_import.contains("::");
+        match self.config.wrapper_visibility {
+            Visibility::Pub => "pub ".to_string(),
+            Visibility::PubCrate => "pub(crate) ".to_string(),
+            Visibility::Private if !nested => String::new(),
+            Visibility::Private | Visibility::AsDeclared if mod_vis.is_empty() => String::new(),
+            Visibility::Private | Visibility::AsDeclared => format!("{} ", mod_vis),
+        }
+    }
+
+    /// File of an `include!("<file>");` line that can be expanded in
+    /// place, relative to the file that has it, and the start of the
+    /// inline module the line has it in, as in `pub mod tables {
+    /// include!("tables.rs"); }`, if any.
+    fn included_file(&self, src: &SourceFile, line: &str) -> Option<(PathBuf, Option<String>)> {
+        let cap = self.config.include_file_re.captures(line)?;
+        let head = cap
+            .name("head")
+            .map(|head| head.as_str().trim().to_string());
+        if head.is_some() != cap.name("close").is_some() {
+            return None;
+        }
+        let dir = src.path().parent().unwrap_or(Path::new(""));
+        let path = dir.join(&cap["f"]);
+        (path.is_file() && !src.is_open(&path)).then_some((path, head))
+    }
+
+    /// Whether `line` is to be held with the attributes before a
+    /// possible `mod` declaration: an outer attribute or doc comment,
+    /// the rest of an attribute that spans lines, or a comment among
+    /// them.
+    fn holds_attr(&self, held: &HeldAttrs, line: &str, scan: &LineScan) -> bool {
+        if held.depth > 0 {
+            return true;
+        }
+        let attribute = self.config.attribute_re.is_match(line)
+            && (line.trim_end().ends_with(']') || (scan.delta > 0 && !line.contains(']')));
+        attribute
+            || self.config.outer_doc_re.is_match(line)
+            || (!held.lines.is_empty() && self.config.comment_re.is_match(line))
+    }
+
+    /// Hold `line`, for which [`Run::holds_attr`] is true. If it ends an
+    /// attribute that spans lines but has an item after it, it's not
+    /// before a `mod` declaration, so everything held is written.
+    fn hold_attr(
+        &mut self,
+        o: &mut Vec<u8>,
+        held: &mut HeldAttrs,
+        line: &str,
+        file: &Path,
+        lineno: usize,
+        scan: &LineScan,
+    ) {
+        let continued = held.depth > 0;
+        held.lines
+            .push((line.to_string(), file.to_path_buf(), lineno));
+        held.depth = (held.depth + scan.delta).max(0);
+        if continued && held.depth == 0 && !line.trim_end().ends_with(']') {
+            self.flush_attrs(o, held);
+        }
+    }
+
+    /// File given by the held `#[path]` attribute of a `mod`
+    /// declaration, if there was one, relative to `dir`.
+    fn take_path_attr(&mut self, held: &mut HeldAttrs, dir: &Path) -> Option<PathBuf> {
+        let i = held
+            .lines
+            .iter()
+            .position(|(line, _, _)| self.config.path_re.is_match(line))?;
+        let (line, file, lineno) = held.lines.remove(i);
+        self.skip_line(&file, lineno, SkipReason::PathAttribute);
+        let cap = self.config.path_re.captures(&line)?;
+        Some(dir.join(&cap["p"]))
+    }
+
+    /// Write the attributes that are on the same line as an expanded
+    /// `mod <name>;` declaration, like `#[cfg(...)]`, before its wrapper.
+    fn write_mod_attrs(&mut self, o: &mut Vec<u8>, attrs: &str) {
+        let attrs = attrs.trim();
+        if !attrs.is_empty() {
+            self.write_line(o, attrs);
+        }
+    }
+
+    /// Write the held lines, the attributes of an expanded module or
+    /// lines that turned out not to be before a `mod` declaration.
+    fn flush_attrs(&mut self, o: &mut Vec<u8>, held: &mut HeldAttrs) {
+        for (line, _, _) in std::mem::take(&mut held.lines) {
+            self.write_line(o, &line);
+        }
+        held.depth = 0;
+    }
+
+    /// Skip the held lines, the attributes of an excluded module or of
+    /// the `extern crate` line of the library, like `#[macro_use]`.
+    fn drop_attrs(&mut self, held: &mut HeldAttrs, reason: SkipReason) {
+        for (_, file, lineno) in std::mem::take(&mut held.lines) {
+            self.skip_line(&file, lineno, reason);
+        }
+        held.depth = 0;
+    }
+
+    /// Divert the lines of `#[macro_export]` macros to the hoisted
+    /// macros buffer. Returns true if the line was consumed.
+    fn hoist_line(&mut self, o: &mut Vec<u8>, line: &str, scan: &LineScan) -> bool {
+        if !self.config.hoist_macros {
+            return false;
+        }
+        let mut macros = std::mem::take(&mut self.hoist.macros);
+        let consumed = if let Some(depth) = self.hoist.depth {
+            self.write_line(&mut macros, line);
+            self.hoist_depth(depth + scan.delta, line);
+            true
+        } else if !scan.starts_in_code() {
+            false
+        } else if let Some(cap) = self.config.macro_export_re.captures(line) {
+            if self.config.macro_rules_re.is_match(&cap["rest"]) {
+                self.write_line(&mut macros, line);
+                self.hoist_depth(scan.delta, line);
+            } else {
+                self.hoist.pending.push(line.to_string());
+            }
+            true
+        } else if !self.hoist.pending.is_empty() {
+            let pending = std::mem::take(&mut self.hoist.pending);
+            if self.config.attribute_re.is_match(line) {
+                self.hoist.pending = pending;
+                self.hoist.pending.push(line.to_string());
+                true
+            } else if self.config.macro_rules_re.is_match(line) {
+                for pending_line in pending {
+                    self.write_line(&mut macros, &pending_line);
+                }
+                self.write_line(&mut macros, line);
+                self.hoist_depth(scan.delta, line);
+                true
+            } else {
+                for pending_line in pending {
+                    self.write_line(o, &pending_line);
+                }
+                false
+            }
+        } else {
+            false
+        };
+        self.hoist.macros = macros;
+        consumed
+    }
+
+    /// Update the depth of the macro being hoisted, finishing it when
+    /// all its delimiters are closed.
+    fn hoist_depth(&mut self, depth: i32, line: &str) {
+        self.hoist.opened |= line.contains(['{', '(', '[']);
+        if self.hoist.opened && depth <= 0 {
+            self.hoist.depth = None;
+            self.hoist.opened = false;
+        } else {
+            self.hoist.depth = Some(depth);
+        }
+    }
+
+    /// Write a line to the output, adding the terminator, and count it
+    /// in the module being expanded.
+    fn write_line(&mut self, o: &mut Vec<u8>, line: &str) {
+        o.extend_from_slice(line.as_bytes());
+        o.push(b'\n');
+        if let Some(&module) = self.module_stack.last() {
+            let module = &mut self.plan.modules[module];
+            module.lines += 1;
+            module.bytes += line.len() + 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::process::Command;
+    use std::thread;
+
+    use super::*;
+
+    /// A package with the given files in a new temporary directory.
+    fn package(name: &str, files: &[(&str, &str)]) -> PathBuf {
+        let root =
+            env::temp_dir().join(format!("rustsourcebundler-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        for &(path, contents) in files {
+            let path = root.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, contents).unwrap();
+        }
+        root
+    }
+
+    /// The bundle of the package at `root`, set up with `configure`.
+    fn bundle_of<'a, F: Fn(&mut Bundler<'a>)>(root: &'a Path, configure: F) -> String {
+        let mut bundler = Bundler::for_single_package(root).unwrap();
+        configure(&mut bundler);
+        let bundle = bundler.reader().unwrap().into_inner();
+        String::from_utf8(bundle).unwrap()
+    }
+
+    /// The report of a run on the package at `root`, set up with
+    /// `configure`.
+    fn report_of<'a, F: Fn(&mut Bundler<'a>)>(root: &'a Path, configure: F) -> Report {
+        let mut bundler = Bundler::for_single_package(root).unwrap();
+        configure(&mut bundler);
+        bundler.reader().unwrap().report().clone()
+    }
+
+    /// Panics with the errors of rustc if `bundle` doesn't build as a
+    /// program of the 2021 edition, in `root`.
+    fn assert_builds(root: &Path, bundle: &str) {
+        let source = root.join("bundle.rs");
+        fs::write(&source, bundle).unwrap();
+        let rustc = env::var("RUSTC").unwrap_or_else(|_| "rustc".to_string());
+        let output = Command::new(rustc)
+            .args(["--edition", "2021", "--out-dir"])
+            .arg(root)
+            .arg(&source)
+            .output()
+            .unwrap();
+        assert!(
+            output.status.success(),
+            "{}\n{}",
+            bundle,
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+
+    const MANIFEST: &str = "[package]\nname = \"fx\"\nedition = \"2021\"\n";
+
+    #[test]
+    fn run_fails_instead_of_panicking_on_broken_projects() {
+        let root = package(
+            "broken",
+            &[
+                ("Cargo.toml", MANIFEST),
+                ("src/lib.rs", "pub mod io;\npub mod graph;\n"),
+                ("src/io.rs", "pub fn read() {}\n"),
+                ("src/main.rs", "use fx::io;\nfn main() {}\n"),
+            ],
+        );
+        let bundler = Bundler::for_single_package(&root).unwrap();
+        match bundler.run() {
+            Err(Error::ModuleNotFound { name, .. }) => assert_eq!(name, "graph"),
+            other => panic!("{:?}", other),
+        }
+        fs::write(root.join("src/graph.rs"), "pub fn bfs() {\n").unwrap();
+        match bundler.run() {
+            Err(Error::UnbalancedDelimiters { path, .. }) => {
+                assert_eq!(path, root.join("src/graph.rs"))
+            }
+            other => panic!("{:?}", other),
+        }
+        fs::write(root.join("src/graph.rs"), "pub fn bfs() {}\n").unwrap();
+        let mut bundler = Bundler::for_single_package(&root).unwrap();
+        bundler.insert_raw(BundleAnchor::BeforeLib, "}\n");
+        bundler.public_api_only_set(true);
+        assert!(bundler.run().is_ok());
+        fs::remove_file(root.join("src/main.rs")).unwrap();
+        assert!(matches!(bundler.run(), Err(Error::Io { .. })));
+        fs::remove_file(root.join("Cargo.toml")).unwrap();
+        assert!(matches!(
+            Bundler::for_single_package(&root),
+            Err(Error::Io { .. })
+        ));
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn inner_attributes_stay_before_hoisted_macros() {
+        let bundle = b"fn a() {}\n#![allow(dead_code)]\n//! Docs.\n#![cfg_attr(\n    test,\n    allow(unused)\n)]\n\npub mod io {\n";
+        let end = after_inner_attributes(bundle, 10);
+        assert_eq!(&bundle[end..], b"\npub mod io {\n");
+        assert_eq!(after_inner_attributes(bundle, 0), 0);
+        assert_eq!(after_inner_attributes(b"", 0), 0);
+    }
+
+    #[test]
+    fn macros_are_hoisted_after_the_inner_attributes_of_the_library() {
+        let root = package(
+            "hoist-inner",
+            &[
+                ("Cargo.toml", MANIFEST),
+                (
+                    "src/lib.rs",
+                    "#![allow(dead_code)]\n//! Docs.\npub mod io;\n",
+                ),
+                (
+                    "src/io.rs",
+                    "#[macro_export]\nmacro_rules! two {\n    () => { 2 };\n}\n",
+                ),
+                (
+                    "src/main.rs",
+                    "use fx::io;\nfn main() {\n    println!(\"{}\", two!());\n}\n",
+                ),
+            ],
+        );
+        let bundle = bundle_of(&root, |bundler| bundler.strip_comments_set(false));
+        let attribute = bundle.find("#![allow(dead_code)]").unwrap();
+        let docs = bundle.find("//! Docs.").unwrap();
+        let hoisted = bundle.find("#[macro_export]").unwrap();
+        assert!(attribute < hoisted && docs < hoisted, "{}", bundle);
+        assert!(hoisted < bundle.find("pub mod io {").unwrap(), "{}", bundle);
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn private_wrappers_keep_the_visibility_of_nested_modules() {
+        let root = package(
+            "private-wrappers",
+            &[
+                ("Cargo.toml", MANIFEST),
+                (
+                    "src/lib.rs",
+                    "mod a;\npub fn item() -> crate::a::b::Item {\n    a::b::Item\n}\n",
+                ),
+                ("src/a.rs", "pub mod b;\nmod c;\n"),
+                ("src/a/b.rs", "pub struct Item;\n"),
+                ("src/a/c.rs", "pub fn c() {}\n"),
+                (
+                    "src/main.rs",
+                    "use fx::item;\nfn main() {\n    item();\n}\n",
+                ),
+            ],
+        );
+        let bundle = bundle_of(&root, |bundler| {
+            bundler.wrapper_visibility(Visibility::Private)
+        });
+        let lines: Vec<&str> = bundle.lines().collect();
+        assert!(lines.contains(&"mod a {"), "{}", bundle);
+        assert!(lines.contains(&"pub mod b {"), "{}", bundle);
+        assert!(lines.contains(&"mod c {"), "{}", bundle);
+        let bundle = bundle_of(&root, |bundler| {
+            bundler.wrapper_visibility(Visibility::PubCrate)
+        });
+        assert!(bundle.contains("pub(crate) mod b {"), "{}", bundle);
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn explicit_imports_are_the_names_of_non_glob_imports() {
+        let source = "use std::io::{self, Read as R};\nuse fx::prelude::*;\npub use fx::graph::{\n    flow::Dinic,\n    Bfs,\n};\nfn main() {}\n";
+        let mut names: Vec<String> = explicit_imports(source).into_iter().collect();
+        names.sort();
+        assert_eq!(names, ["Bfs", "Dinic", "R", "io"]);
+    }
+
+    #[test]
+    fn glob_imports_leave_out_the_names_imported_explicitly() {
+        let root = package(
+            "glob-clash",
+            &[
+                ("Cargo.toml", MANIFEST),
+                ("src/lib.rs", "pub mod graph;\npub mod prelude;\n"),
+                ("src/graph.rs", "pub struct Dinic;\npub struct Bfs;\n"),
+                ("src/prelude.rs", "pub use crate::graph::{Bfs, Dinic};\n"),
+                (
+                    "src/main.rs",
+                    "use fx::graph::Dinic;\nuse fx::prelude::*;\nfn main() {\n    let _ = (Dinic, Bfs);\n}\n",
+                ),
+            ],
+        );
+        let bundle = bundle_of(&root, |_| {});
+        let uses: Vec<&str> = bundle.lines().filter(|l| l.starts_with("use ")).collect();
+        assert_eq!(uses, ["use crate::graph::Dinic;", "use crate::graph::Bfs;"]);
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn trim_line_removes_lf_and_crlf() {
+        for (line, trimmed) in [("a\n", "a"), ("a\r\n", "a"), ("a\r", "a"), ("a", "a")] {
+            let mut line = line.to_string();
+            trim_line(&mut line);
+            assert_eq!(line, trimmed);
+        }
+    }
+
+    #[test]
+    fn crlf_and_mixed_sources_give_lf_bundles() {
+        let root = package(
+            "crlf",
+            &[
+                ("Cargo.toml", MANIFEST),
+                ("src/lib.rs", "pub mod io;\r\npub mod graph;\r\n"),
+                ("src/io.rs", "pub fn read() {}\r\n"),
+                ("src/graph.rs", "pub fn bfs() {}\npub fn dfs() {}\r\n"),
+                (
+                    "src/main.rs",
+                    "use fx::io::read;\r\nfn main() {\n    read();\r\n}\r\n",
+                ),
+            ],
+        );
+        let bundle = bundle_of(&root, |_| {});
+        assert!(!bundle.contains('\r'), "{:?}", bundle);
+        assert!(
+            bundle.contains("pub mod io {\npub fn read() {}\n}\n"),
+            "{:?}",
+            bundle
+        );
+        assert!(
+            bundle.contains("pub fn bfs() {}\npub fn dfs() {}\n"),
+            "{:?}",
+            bundle
+        );
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    /// Name, visibility and attributes of the `mod` declaration of
+    /// `line`, as the bundler reads it.
+    fn mod_declaration(line: &str) -> Option<(String, String, String)> {
+        let bundler = Bundler::new(Path::new("src/main.rs"), Path::new("bundle.rs"));
+        let cap = bundler.mod_re.captures(line)?;
+        let vis = cap.name("vis").map_or("", |v| v.as_str().trim());
+        Some((
+            cap["m"].to_string(),
+            vis.to_string(),
+            cap["attrs"].trim().to_string(),
+        ))
+    }
+
+    #[test]
+    fn mod_declarations_with_trailing_comments_and_attributes() {
+        let cases = [
+            ("mod io;", "io", "", ""),
+            ("pub mod io; // fast input", "io", "pub", ""),
+            ("pub mod io; /* docs */", "io", "pub", ""),
+            ("pub mod io; /* a */ /* b */ // c", "io", "pub", ""),
+            ("pub mod io;// no space", "io", "pub", ""),
+            ("pub(crate) mod io;", "io", "pub(crate)", ""),
+            ("pub(in crate::a) mod io ;", "io", "pub(in crate::a)", ""),
+            ("#[cfg(test)] mod tests;", "tests", "", "#[cfg(test)]"),
+            (
+                "#[cfg(feature = \"x\")] #[allow(dead_code)] pub mod io; // x",
+                "io",
+                "pub",
+                "#[cfg(feature = \"x\")] #[allow(dead_code)]",
+            ),
+            ("    pub mod io;   ", "io", "pub", ""),
+        ];
+        for (line, name, vis, attrs) in cases {
+            let expected = Some((name.to_string(), vis.to_string(), attrs.to_string()));
+            assert_eq!(mod_declaration(line), expected, "{:?}", line);
+        }
+        for line in [
+            "mod io {",
+            "// mod io;",
+            "pub mod io; fn f() {}",
+            "mod io; /* unfinished",
+            "let mod_io = 1;",
+        ] {
+            assert_eq!(mod_declaration(line), None, "{:?}", line);
+        }
+    }
+
+    /// Every way to write `template` with the whitespace of the
+    /// separators: `_` where some is needed, `~` where it's optional.
+    fn spacings(template: &[&str]) -> Vec<String> {
+        let mut lines = vec![String::new()];
+        for &part in template {
+            let options: &[&str] = match part {
+                "_" => &[" ", "\t", "  \t "],
+                "~" => &["", " ", "\t"],
+                token => &[token],
+            };
+            lines = lines
+                .iter()
+                .flat_map(|line| {
+                    options
+                        .iter()
+                        .map(move |option| format!("{}{}", line, option))
+                })
+                .collect();
+        }
+        lines
+    }
+
+    #[test]
+    fn declarations_match_with_any_spacing() {
+        let bundler = Bundler::new(Path::new("src/main.rs"), Path::new("bundle.rs"));
+        let endings = ["", " // comment", "\t/* comment */", "/* a */ /* b */"];
+        let check = |re: &Regex, template: &[&str], group: &str, expected: &str| {
+            for line in spacings(template) {
+                for ending in endings {
+                    let line = format!("{}{}", line, ending);
+                    let cap = re.captures(&line);
+                    let found = cap.as_ref().and_then(|cap| cap.name(group));
+                    let found = found.map(|m| m.as_str().trim());
+                    assert_eq!(found, Some(expected), "{:?}", line);
+                }
+            }
+        };
+        check(
+            &bundler.mod_re,
+            &["~", "pub", "_", "mod", "_", "io", "~", ";", "~"],
+            "m",
+            "io",
+        );
+        check(
+            &bundler.mod_re,
+            &[
+                "pub", "~", "(", "~", "crate", "~", ")", "~", "mod", "_", "io", "~", ";",
+            ],
+            "m",
+            "io",
+        );
+        check(
+            &bundler.mod_re,
+            &["#[cfg(test)]", "~", "mod", "_", "tests", "~", ";"],
+            "attrs",
+            "#[cfg(test)]",
+        );
+        check(
+            &bundler.pub_use_re,
+            &["~", "pub", "_", "use", "_", "graph::Dinic", "~", ";", "~"],
+            "path",
+            "graph::Dinic",
+        );
+        check(
+            &bundler.pub_use_re,
+            &[
+                "pub",
+                "(crate)",
+                "~",
+                "use",
+                "_",
+                "graph::{Bfs, Dinic}",
+                "~",
+                ";",
+            ],
+            "path",
+            "graph::{Bfs, Dinic}",
+        );
+        check(
+            &bundler.extern_re,
+            &["~", "extern", "_", "crate", "_", "fx", "~", ";", "~"],
+            "c",
+            "fx",
+        );
+        for line in spacings(&["~", "use", "_", "std::io", "~", ";", "~"]) {
+            assert!(bundler.use_re.is_match(&line), "{:?}", line);
+        }
+        for line in ["mod\tio", "pubmod io;", "modio;", "use;", "externcrate fx;"] {
+            assert!(!bundler.mod_re.is_match(line), "{:?}", line);
+            assert!(!bundler.use_re.is_match(line), "{:?}", line);
+            assert!(!bundler.extern_re.is_match(line), "{:?}", line);
+        }
+    }
+
+    #[test]
+    fn unbalanced_files_fail_or_are_copied_verbatim_when_lenient() {
+        let root = package(
+            "unbalanced",
+            &[
+                ("Cargo.toml", MANIFEST),
+                ("src/lib.rs", "pub mod io;\n"),
+                ("src/io.rs", "pub fn read() {\n    let x = (1;\n"),
+                ("src/main.rs", "use fx::io;\nfn main() {}\n"),
+            ],
+        );
+        let bundler = Bundler::for_single_package(&root).unwrap();
+        match bundler.reader() {
+            Err(Error::UnbalancedDelimiters { path, line, .. }) => {
+                assert_eq!((path, line), (root.join("src/io.rs"), 2));
+            }
+            other => panic!("{:?}", other),
+        }
+        let mut bundler = Bundler::for_single_package(&root).unwrap();
+        bundler.lenient_set(true);
+        let reader = bundler.reader().unwrap();
+        let warnings = reader.report().warnings.clone();
+        assert!(
+            matches!(warnings[..], [Warning::UnbalancedFile { line: 2, .. }]),
+            "{:?}",
+            warnings
+        );
+        let bundle = String::from_utf8(reader.into_inner()).unwrap();
+        assert!(
+            bundle.contains("pub fn read() {\n    let x = (1;\n"),
+            "{}",
+            bundle
+        );
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn fastio_is_injected_unless_the_crate_has_it() {
+        let root = package(
+            "fastio",
+            &[
+                ("Cargo.toml", MANIFEST),
+                ("src/lib.rs", "pub mod graph;\n"),
+                ("src/graph.rs", "pub fn bfs() {}\n"),
+                ("src/main.rs", "use fx::graph;\nfn main() {}\n"),
+            ],
+        );
+        let bundle = bundle_of(&root, |bundler| {
+            bundler.inject_fastio(FastIoKind::Interactive)
+        });
+        assert!(
+            bundle.ends_with(fastio::source(FastIoKind::Interactive)),
+            "{}",
+            bundle
+        );
+        fs::write(root.join("src/lib.rs"), "pub mod graph;\npub mod fastio;\n").unwrap();
+        fs::write(root.join("src/fastio.rs"), "pub struct Scanner;\n").unwrap();
+        let bundle = bundle_of(&root, |bundler| bundler.inject_fastio(FastIoKind::Scanner));
+        assert_eq!(bundle.matches("mod fastio {").count(), 1, "{}", bundle);
+        assert!(bundle.contains("pub struct Scanner;"), "{}", bundle);
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn source_markers_have_paths_from_the_package_root() {
+        let root = package(
+            "markers",
+            &[
+                ("Cargo.toml", MANIFEST),
+                ("src/lib.rs", "pub mod graph;\n"),
+                ("src/graph.rs", "pub mod flow;\n"),
+                ("src/graph/flow.rs", "pub fn dinic() {}\n"),
+                ("src/main.rs", "use fx::graph;\nfn main() {}\n"),
+            ],
+        );
+        let bundle = bundle_of(&root, |bundler| bundler.source_markers_set(true));
+        assert!(
+            bundle.contains("pub const __BUNDLE_SRC_GRAPH_FLOW: &str = \"src/graph/flow.rs\";"),
+            "{}",
+            bundle
+        );
+        assert!(!bundle.contains(&*root.to_string_lossy()), "{}", bundle);
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn the_library_is_not_inlined_in_the_body_of_a_function() {
+        let root = package(
+            "use-in-fn",
+            &[
+                ("Cargo.toml", MANIFEST),
+                ("src/lib.rs", "pub mod io;\n"),
+                ("src/io.rs", "pub fn read() -> u32 {\n    7\n}\n"),
+                (
+                    "src/main.rs",
+                    "fn main() {\n    use fx::io::read;\n    println!(\"{}\", read());\n}\n",
+                ),
+            ],
+        );
+        let bundle = bundle_of(&root, |_| {});
+        assert!(bundle.find("pub mod io {").unwrap() > bundle.find("}\n").unwrap());
+        assert_builds(&root, &bundle);
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn stubs_of_ignored_modules_have_the_visibility_of_wrappers() {
+        let root = package(
+            "stub-vis",
+            &[
+                ("Cargo.toml", MANIFEST),
+                ("src/lib.rs", "pub mod io;\nmod slow;\n"),
+                ("src/io.rs", "pub mod fast;\n"),
+                ("src/io/fast.rs", "pub fn read() {}\n"),
+                ("src/slow.rs", "pub fn read() {}\n"),
+                ("src/main.rs", "use fx::io;\nfn main() {}\n"),
+            ],
+        );
+        let bundle_with = |visibility| {
+            bundle_of(&root, |bundler| {
+                bundler.ignore_file("src/slow.rs");
+                bundler.ignore_file("src/io/fast.rs");
+                bundler.ignored_modules(IgnoredModules::Stub);
+                bundler.wrapper_visibility(visibility);
+            })
+        };
+        let bundle = bundle_with(Visibility::Pub);
+        assert!(bundle.contains("pub mod slow {}"), "{}", bundle);
+        assert!(bundle.contains("pub mod fast {}"), "{}", bundle);
+        let bundle = bundle_with(Visibility::Private);
+        assert!(bundle.contains("\nmod slow {}"), "{}", bundle);
+        assert!(bundle.contains("pub mod fast {}"), "{}", bundle);
+        let bundle = bundle_with(Visibility::AsDeclared);
+        assert!(bundle.contains("\nmod slow {}"), "{}", bundle);
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn parallel_runs_verify_in_their_own_directories() {
+        let roots: Vec<PathBuf> = ["1", "2"]
+            .iter()
+            .map(|output| {
+                let root = package(
+                    &format!("verify-{}", output),
+                    &[
+                        ("Cargo.toml", MANIFEST),
+                        ("src/lib.rs", "pub mod io;\n"),
+                        ("src/io.rs", &format!("pub const OUT: u32 = {};\n", output)),
+                        (
+                            "src/main.rs",
+                            "use fx::io;\nfn main() {\n    println!(\"{}\", io::OUT);\n}\n",
+                        ),
+                        ("in.txt", ""),
+                    ],
+                );
+                root
+            })
+            .collect();
+        let reports: Vec<Report> = thread::scope(|scope| {
+            let runs: Vec<_> = roots
+                .iter()
+                .map(|root| {
+                    scope.spawn(move || {
+                        let input = root.join("in.txt");
+                        let mut bundler = Bundler::for_single_package(root).unwrap();
+                        bundler.verify_input(&input);
+                        bundler.run().unwrap()
+                    })
+                })
+                .collect();
+            runs.into_iter().map(|run| run.join().unwrap()).collect()
+        });
+        for (report, output) in reports.iter().zip(["1\n", "2\n"]) {
+            assert!(report.warnings.is_empty(), "{:?}", report.warnings);
+            assert_eq!(report.verify_runs.len(), 2, "{:?}", report.verify_runs);
+            assert!(report.verify_runs.iter().all(|run| run.stdout == output));
+        }
+        let prefix = format!("rustsourcebundler-verify-fx-{}-", std::process::id());
+        let left = fs::read_dir(env::temp_dir())
+            .unwrap()
+            .filter_map(|entry| entry.ok())
+            .any(|entry| entry.file_name().to_string_lossy().starts_with(&prefix));
+        assert!(!left);
+        for root in roots {
+            fs::remove_dir_all(root).unwrap();
+        }
+    }
+
+    #[test]
+    fn run_with_plan_returns_the_plan_of_the_run() {
+        let root = package(
+            "run-with-plan",
+            &[
+                ("Cargo.toml", MANIFEST),
+                ("src/lib.rs", "pub mod io;\npub mod graph;\n"),
+                ("src/io.rs", "pub fn read() {}\n"),
+                ("src/graph.rs", "pub fn bfs() {}\n"),
+                ("src/main.rs", "use fx::io;\nfn main() {}\n"),
+            ],
+        );
+        let bundler = Bundler::for_single_package(&root).unwrap();
+        let (plan, report) = bundler.run_with_plan().unwrap();
+        assert_eq!(plan.to_json(), bundler.plan().unwrap().to_json());
+        let bundle = fs::read(root.join("bundle.rs")).unwrap();
+        assert_eq!(report.size, bundle.len());
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn the_library_can_be_imported_with_its_extern_crate_self_alias() {
+        let root = package(
+            "self-alias",
+            &[
+                ("Cargo.toml", MANIFEST),
+                ("src/lib.rs", "extern crate self as algo;\npub mod io;\n"),
+                (
+                    "src/io.rs",
+                    "pub fn read() -> u32 {\n    algo::io::seven()\n}\npub fn seven() -> u32 {\n    7\n}\n",
+                ),
+                (
+                    "src/main.rs",
+                    "use algo::io::read;\nfn main() {\n    println!(\"{}\", read());\n}\n",
+                ),
+            ],
+        );
+        let bundler = Bundler::for_single_package(&root).unwrap();
+        let reader = bundler.reader().unwrap();
+        assert!(
+            reader.report().warnings.is_empty(),
+            "{:?}",
+            reader.report().warnings
+        );
+        let bundle = String::from_utf8(reader.into_inner()).unwrap();
+        assert_builds(&root, &bundle);
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn invalid_crate_names_are_errors() {
+        assert!(is_identifier("algo_lib") && is_identifier("_x") && is_identifier("é"));
+        assert!(!is_identifier("") && !is_identifier("_") && !is_identifier("1x"));
+        assert!(!is_identifier("a.b") && !is_identifier("a b") && !is_identifier("(a|b)"));
+        let root = package(
+            "crate-name",
+            &[
+                ("Cargo.toml", MANIFEST),
+                ("src/lib.rs", "pub mod io;\n"),
+                ("src/io.rs", "pub fn read() {}\n"),
+                ("src/main.rs", "use fx::io;\nfn main() {}\n"),
+            ],
+        );
+        for names in [["my-lib", "algo"], ["fx", "(a|b"], ["fx", "a::b"]] {
+            let mut bundler = Bundler::for_single_package(&root).unwrap();
+            bundler.crate_name(names[0]);
+            bundler.crate_aliases(&names[1..]);
+            match bundler.run() {
+                Ok(_) if names[0] == "my-lib" => {}
+                Err(Error::InvalidCrateName { name }) => assert_eq!(name, names[1]),
+                other => panic!("{:?}", other),
+            }
+        }
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn warnings_are_returned_in_the_report() {
+        let root = package(
+            "warnings",
+            &[
+                ("Cargo.toml", MANIFEST),
+                ("src/lib.rs", "pub mod io;\npub mod debug;\n"),
+                ("src/io.rs", "pub const HOME: &str = env!(\"HOME\");\n"),
+                ("src/debug.rs", "pub fn dump() {}\n"),
+                (
+                    "src/main.rs",
+                    "use fx::io;\nuse fx::debug::dump;\nfn main() {}\n",
+                ),
+            ],
+        );
+        let report = report_of(&root, |bundler| bundler.exclude_mod("debug"));
+        assert_eq!(
+            report.warnings,
+            [
+                Warning::EnvMacro {
+                    file: root.join("src/io.rs"),
+                    line: 1
+                },
+                Warning::SkippedModule {
+                    name: "debug".to_string()
+                },
+                Warning::UnresolvedImport {
+                    path: "debug::dump".to_string()
+                },
+            ]
+        );
+        let bundle = bundle_of(&root, |_| {});
+        assert_eq!(report_of(&root, |_| {}).size, bundle.len());
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn the_library_is_inlined_at_the_insert_lib_marker() {
+        let root = package(
+            "insert-lib",
+            &[
+                ("Cargo.toml", MANIFEST),
+                ("src/lib.rs", "pub mod io;\n"),
+                ("src/io.rs", "pub fn read() -> u32 {\n    7\n}\n"),
+                (
+                    "src/main.rs",
+                    "use fx::io::read;\nfn main() {\n    println!(\"{}\", read());\n}\n// bundler:insert-lib\n",
+                ),
+            ],
+        );
+        let bundle = bundle_of(&root, |_| {});
+        let main = bundle.find("fn main()").unwrap();
+        let io = bundle.find("pub mod io {").unwrap();
+        assert!(main < io, "{}", bundle);
+        assert!(!bundle.contains("bundler:insert-lib"), "{}", bundle);
+        assert_builds(&root, &bundle);
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn plans_list_the_files_modules_and_skipped_lines_without_writing() {
+        let root = package(
+            "plan",
+            &[
+                ("Cargo.toml", MANIFEST),
+                ("src/lib.rs", "pub mod io;\npub mod debug;\n"),
+                ("src/io.rs", "// Reading.\npub fn read() {}\n"),
+                ("src/debug.rs", "pub fn dump() {}\n"),
+                (
+                    "src/main.rs",
+                    "use fx::io::read;\nfn main() {\n    read();\n}\n",
+                ),
+            ],
+        );
+        let mut bundler = Bundler::for_single_package(&root).unwrap();
+        bundler.exclude_mod("debug");
+        let plan = bundler.plan().unwrap();
+        let files = ["src/main.rs", "src/lib.rs", "src/io.rs"].map(|file| root.join(file));
+        assert_eq!(plan.files, files);
+        let modules = plan
+            .modules
+            .iter()
+            .map(|module| (&module.path[..], module.expanded, module.referenced))
+            .collect::<Vec<_>>();
+        assert_eq!(modules, [("io", true, true), ("debug", false, false)]);
+        assert!(plan.skipped_lines.contains(&SkippedLine {
+            file: root.join("src/io.rs"),
+            line: 1,
+            reason: SkipReason::Comment,
+        }));
+        assert!(!root.join("bundle.rs").exists());
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn boms_are_stripped_and_invalid_utf8_is_read_lossily() {
+        let root = package(
+            "utf8",
+            &[
+                ("Cargo.toml", MANIFEST),
+                ("src/lib.rs", "\u{FEFF}pub mod io;\n"),
+                (
+                    "src/main.rs",
+                    "\u{FEFF}use fx::io::NAME;\nfn main() {\n    print!(\"{}\", NAME);\n}\n",
+                ),
+            ],
+        );
+        fs::write(
+            root.join("src/io.rs"),
+            b"pub const NAME: &str = \"a\xffb\";\n",
+        )
+        .unwrap();
+        let bundle = bundle_of(&root, |_| {});
+        assert!(!bundle.contains('\u{FEFF}'), "{:?}", bundle);
+        assert!(bundle.contains("\"a\u{FFFD}b\""), "{}", bundle);
+        let report = report_of(&root, |_| {});
+        let invalid = Warning::InvalidUtf8 {
+            file: root.join("src/io.rs"),
+            line: 1,
+        };
+        assert_eq!(report.warnings, [invalid]);
+        let mut bundler = Bundler::for_single_package(&root).unwrap();
+        bundler.strict_utf8_set(true);
+        match bundler.run() {
+            Err(Error::InvalidUtf8 { path, line: 1 }) => assert_eq!(path, root.join("src/io.rs")),
+            other => panic!("{:?}", other),
+        }
+        fs::remove_file(root.join("src/io.rs")).unwrap();
+        match Bundler::for_single_package(&root).unwrap().run() {
+            Err(Error::ModuleNotFound { name, .. }) => assert_eq!(name, "io"),
+            other => panic!("{:?}", other),
+        }
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn contests_bundle_each_problem_and_write_the_metadata() {
+        let root = package(
+            "contest",
+            &[
+                ("Cargo.toml", MANIFEST),
+                ("src/lib.rs", "pub mod io;\n"),
+                ("src/io.rs", "pub fn read() {}\n"),
+                (
+                    "src/bin/b.rs",
+                    "use fx::io::read;\nfn main() {\n    read();\n}\n",
+                ),
+                ("src/bin/a.rs", "fn main() {}\n"),
+            ],
+        );
+        let artifacts = judge::Contest::new(&root, "fx").bundle_all().unwrap();
+        let out_dir = root.join("target/bundle");
+        let problems = artifacts.iter().map(|a| &a.problem[..]).collect::<Vec<_>>();
+        assert_eq!(problems, ["a", "b"]);
+        for artifact in &artifacts {
+            let bundle = fs::read(&artifact.bundle).unwrap();
+            assert_eq!(
+                artifact.bundle,
+                out_dir.join(format!("{}.rs", artifact.problem))
+            );
+            assert_eq!(
+                (artifact.size, &artifact.sha256[..]),
+                (bundle.len(), &sha256::hex_digest(&bundle)[..])
+            );
+        }
+        assert!(fs::read_to_string(&artifacts[1].bundle)
+            .unwrap()
+            .contains("pub fn read() {}"));
+        let metadata = fs::read_to_string(out_dir.join(judge::METADATA_FILENAME)).unwrap();
+        let metadata: serde_json::Value = serde_json::from_str(&metadata).unwrap();
+        assert_eq!(metadata["problems"][1]["problem"], "b");
+        assert_eq!(metadata["problems"][1]["sha256"], artifacts[1].sha256);
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn the_git_header_has_the_revision_of_the_library() {
+        let root = package(
+            "git-header",
+            &[
+                ("Cargo.toml", MANIFEST),
+                ("src/lib.rs", "pub fn f() {}\n"),
+                ("src/main.rs", "use fx::f;\nfn main() {\n    f();\n}\n"),
+            ],
+        );
+        let report = report_of(&root, |bundler| bundler.git_header_set(true));
+        assert!(
+            matches!(report.warnings[..], [Warning::NoGitRevision { .. }]),
+            "{:?}",
+            report.warnings
+        );
+        let git = |args: &[&str]| {
+            let output = Command::new("git")
+                .arg("-C")
+                .arg(&root)
+                .args(args)
+                .output()
+                .unwrap();
+            assert!(
+                output.status.success(),
+                "{}",
+                String::from_utf8_lossy(&output.stderr)
+            );
+            String::from_utf8(output.stdout).unwrap()
+        };
+        git(&["init", "-q"]);
+        git(&["add", "."]);
+        git(&[
+            "-c",
+            "user.name=t",
+            "-c",
+            "user.email=t@t",
+            "commit",
+            "-qm",
+            "start",
+        ]);
+        git(&["tag", "v1"]);
+        let hash = git(&["rev-parse", "--short", "HEAD"]);
+        let header = format!("// git revision: v1 ({})\n", hash.trim());
+        let mut bundler = Bundler::for_single_package(&root).unwrap();
+        bundler.git_header_set(true);
+        bundler.run().unwrap();
+        let bundle = fs::read_to_string(root.join("bundle.rs")).unwrap();
+        assert!(bundle.starts_with(&header), "{}", bundle);
+        bundler.run().unwrap();
+        let bundle = fs::read_to_string(root.join("bundle.rs")).unwrap();
+        assert!(
+            bundle.starts_with(&header),
+            "the bundle makes it dirty: {}",
+            bundle
+        );
+        fs::write(root.join("src/lib.rs"), "pub fn f() {}\npub fn g() {}\n").unwrap();
+        let bundle = bundle_of(&root, |bundler| bundler.git_header_set(true));
+        let dirty = format!("// git revision: v1 ({}, dirty)\n", hash.trim());
+        assert!(bundle.starts_with(&dirty), "{}", bundle);
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn auto_minification_goes_up_to_the_level_that_fits() {
+        let root = package(
+            "minify-auto",
+            &[
+                ("Cargo.toml", MANIFEST),
+                ("src/lib.rs", "pub mod io;\n"),
+                (
+                    "src/io.rs",
+                    "/// Reads.\npub fn read(\n    first: u32,\n    second: u32,\n) -> u32 {\n    first + second\n}\n",
+                ),
+                ("src/main.rs", "use fx::io::read;\nfn main() {\n    println!(\"{}\", read(1, 2));\n}\n"),
+            ],
+        );
+        let sizes = (0..=minify::MAX_LEVEL)
+            .map(|level| bundle_of(&root, |bundler| bundler.minify_level(level)).len())
+            .collect::<Vec<_>>();
+        assert!(sizes.windows(2).all(|w| w[0] > w[1]), "{:?}", sizes);
+        for (level, &size) in sizes.iter().enumerate() {
+            let report = report_of(&root, |bundler| {
+                bundler.size_limit(size);
+                bundler.minify_auto_set(true);
+            });
+            assert_eq!((report.minify_level, report.size), (level as u8, size));
+            assert!(report.warnings.is_empty(), "{:?}", report.warnings);
+        }
+        let report = report_of(&root, |bundler| {
+            bundler.size_limit(sizes[minify::MAX_LEVEL as usize] - 1);
+            bundler.minify_auto_set(true);
+        });
+        assert_eq!(report.minify_level, minify::MAX_LEVEL);
+        assert!(matches!(
+            report.warnings[..],
+            [Warning::OversizedOutput { .. }]
+        ));
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn no_std_libraries_using_alloc_build_in_std_bundles() {
+        let root = package(
+            "no-std",
+            &[
+                ("Cargo.toml", MANIFEST),
+                ("src/lib.rs", "#![no_std]\nextern crate alloc;\npub mod v;\n"),
+                (
+                    "src/v.rs",
+                    "use alloc::vec::Vec;\npub fn squares(n: u32) -> Vec<u32> {\n    (0..n).map(|i| i * i).collect()\n}\n",
+                ),
+                (
+                    "src/main.rs",
+                    "extern crate alloc;\nuse fx::v::squares;\nfn main() {\n    println!(\"{:?}\", squares(3));\n}\n",
+                ),
+            ],
+        );
+        let bundle = bundle_of(&root, |_| {});
+        assert!(!bundle.contains("no_std"), "{}", bundle);
+        assert_eq!(
+            bundle.matches("extern crate alloc;").count(),
+            1,
+            "{}",
+            bundle
+        );
+        assert_builds(&root, &bundle);
+        let plan = Bundler::for_single_package(&root).unwrap().plan().unwrap();
+        let reasons = plan
+            .skipped_lines
+            .iter()
+            .map(|s| s.reason)
+            .collect::<Vec<_>>();
+        assert!(reasons.contains(&SkipReason::NoStd), "{:?}", reasons);
+        assert!(
+            reasons.contains(&SkipReason::DuplicateExternCrate),
+            "{:?}",
+            reasons
+        );
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn a_minified_copy_is_written_next_to_the_bundle() {
+        let root = package(
+            "emit-minified",
+            &[
+                ("Cargo.toml", MANIFEST),
+                ("src/lib.rs", "pub mod io;\n"),
+                ("src/io.rs", "pub fn read() -> u32 {\n    1 + 2\n}\n"),
+                (
+                    "src/main.rs",
+                    "use fx::io::read;\nfn main() {\n    println!(\"{}\", read());\n}\n",
+                ),
+            ],
+        );
+        let mut bundler = Bundler::for_single_package(&root).unwrap();
+        bundler.emit_minified(".min.rs");
+        bundler.size_limit(1);
+        let report = bundler.run().unwrap();
+        let bundle = fs::read_to_string(root.join("bundle.rs")).unwrap();
+        let minified = fs::read_to_string(root.join("bundle.min.rs")).unwrap();
+        assert_eq!(bundle, bundle_of(&root, |_| {}));
+        assert_eq!(
+            minified,
+            bundle_of(&root, |b| b.minify_level(minify::MAX_LEVEL))
+        );
+        assert_eq!(
+            (report.size, report.minified_size),
+            (bundle.len(), Some(minified.len()))
+        );
+        let oversized = Warning::OversizedOutput {
+            size: minified.len(),
+            limit: 1,
+        };
+        assert_eq!(report.warnings, [oversized]);
+        assert_builds(&root, &minified);
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn rewrite_rules_apply_in_order_to_the_lines_of_the_entry_file() {
+        let root = package(
+            "rewrite",
+            &[
+                ("Cargo.toml", MANIFEST),
+                ("src/lib.rs", "pub const REMOTE: bool = true;\n"),
+                (
+                    "src/main.rs",
+                    "use fx::REMOTE;\nconst LOCAL: bool = true;\nfn main() {\n    println!(\"{}\", LOCAL || REMOTE);\n}\n",
+                ),
+            ],
+        );
+        let bundle = bundle_of(&root, |bundler| {
+            bundler.rewrite_bin(
+                Regex::new(r"LOCAL: bool = true").unwrap(),
+                "LOCAL: bool = false",
+            );
+            bundler.rewrite_bin(Regex::new(r"\bfalse\b").unwrap(), "1 > 2");
+            bundler.rewrite_bin_with(|line| line.replace("println", "print"));
+        });
+        assert!(
+            bundle.contains("const LOCAL: bool = 1 > 2;\n"),
+            "{}",
+            bundle
+        );
+        assert!(
+            bundle.contains("print!(\"{}\", LOCAL || REMOTE);"),
+            "{}",
+            bundle
+        );
+        assert!(
+            bundle.contains("pub const REMOTE: bool = true;"),
+            "{}",
+            bundle
+        );
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn glob_imports_of_library_modules_import_their_reexports() {
+        let root = package(
+            "glob-reexports",
+            &[
+                ("Cargo.toml", MANIFEST),
+                ("src/lib.rs", "pub mod io;\npub mod prelude;\n"),
+                ("src/io.rs", "pub fn read() -> u32 {\n    4\n}\n"),
+                ("src/prelude.rs", "pub use crate::io::read;\n"),
+                (
+                    "src/main.rs",
+                    "use fx::prelude::*;\nfn main() {\n    println!(\"{}\", read());\n}\n",
+                ),
+            ],
+        );
+        let bundle = bundle_of(&root, |_| {});
+        assert!(
+            bundle.contains("\nuse crate::io::read;\nfn main()"),
+            "{}",
+            bundle
+        );
+        assert!(!bundle.contains("prelude::*"), "{}", bundle);
+        assert_builds(&root, &bundle);
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn path_modules_are_bundled_and_imports_of_root_items_skipped() {
+        let root = package(
+            "path-attribute",
+            &[
+                ("Cargo.toml", MANIFEST),
+                (
+                    "src/lib.rs",
+                    "#[path = \"impls/fast_io.rs\"]\npub mod io;\npub fn twice(x: u32) -> u32 {\n    2 * x\n}\n",
+                ),
+                ("src/impls/fast_io.rs", "pub fn read() -> u32 {\n    4\n}\n"),
+                (
+                    "src/main.rs",
+                    "use fx::io::read;\nuse fx::twice;\nfn main() {\n    println!(\"{}\", twice(read()));\n}\n",
+                ),
+            ],
+        );
+        let bundle = bundle_of(&root, |_| {});
+        assert!(!bundle.contains("#[path"), "{}", bundle);
+        assert!(!bundle.contains("use fx::twice"), "{}", bundle);
+        assert_builds(&root, &bundle);
+        let plan = Bundler::for_single_package(&root).unwrap().plan().unwrap();
+        assert_eq!(
+            plan.modules[0].file,
+            Some(root.join("src/impls/fast_io.rs"))
+        );
+        let skipped = |file: &str, line, reason| SkippedLine {
+            file: root.join(file),
+            line,
+            reason,
+        };
+        let path = skipped("src/lib.rs", 1, SkipReason::PathAttribute);
+        let redundant = skipped("src/main.rs", 2, SkipReason::RedundantUse);
+        assert!(plan.skipped_lines.contains(&path));
+        assert!(plan.skipped_lines.contains(&redundant));
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn bundlers_are_reusable_and_run_in_parallel() {
+        fn assert_send_sync<T: Send + Sync>(_: &T) {}
+        let root = package(
+            "parallel-runs",
+            &[
+                ("Cargo.toml", MANIFEST),
+                ("src/lib.rs", "pub mod io;\npub mod graph;\n"),
+                ("src/io.rs", "pub fn read() {}\n"),
+                ("src/graph.rs", "pub struct Graph;\n"),
+                (
+                    "src/main.rs",
+                    "use fx::io::read;\nfn main() {\n    read();\n}\n",
+                ),
+            ],
+        );
+        let bundler = Bundler::for_single_package(&root).unwrap();
+        assert_send_sync(&bundler);
+        let first = bundler.reader().unwrap().into_inner();
+        let again = bundler.reader().unwrap().into_inner();
+        assert_eq!(first, again);
+        let bundles = thread::scope(|scope| {
+            let runs = (0..4)
+                .map(|_| scope.spawn(|| bundler.reader().unwrap().into_inner()))
+                .collect::<Vec<_>>();
+            runs.into_iter()
+                .map(|run| run.join().unwrap())
+                .collect::<Vec<_>>()
+        });
+        assert!(bundles.iter().all(|bundle| *bundle == first));
+        let plan = bundler.plan().unwrap();
+        assert_eq!(plan.files.len(), 4, "{:?}", plan.files);
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn allowed_lints_are_put_on_the_top_level_modules_of_the_library() {
+        let root = package(
+            "allow-lint",
+            &[
+                ("Cargo.toml", MANIFEST),
+                ("src/lib.rs", "pub mod graph;\n"),
+                ("src/graph.rs", "pub mod flow;\n"),
+                ("src/graph/flow.rs", "pub fn max_flow() {}\n"),
+                (
+                    "src/main.rs",
+                    "use fx::graph::flow::max_flow;\nfn main() {\n    max_flow();\n}\n",
+                ),
+            ],
+        );
+        let bundle = bundle_of(&root, |bundler| {
+            bundler.allow_lint("clippy::all");
+            bundler.allow_lint("dead_code");
+        });
+        let allow = "#[allow(clippy::all, dead_code)]\n";
+        assert!(
+            bundle.contains(&format!("{}pub mod graph {{", allow)),
+            "{}",
+            bundle
+        );
+        assert_eq!(bundle.matches(allow).count(), 1, "{}", bundle);
+        assert_builds(&root, &bundle);
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn the_provenance_file_has_the_checksums_and_the_options() {
+        let root = package(
+            "provenance",
+            &[
+                ("Cargo.toml", MANIFEST),
+                ("src/lib.rs", "pub mod io;\n"),
+                ("src/io.rs", "pub fn read() {}\n"),
+                (
+                    "src/main.rs",
+                    "use fx::io::read;\nfn main() {\n    read();\n}\n",
+                ),
+            ],
+        );
+        let provenance_filename = root.join("provenance.json");
+        let mut bundler = Bundler::for_single_package(&root).unwrap();
+        bundler.provenance_file(&provenance_filename);
+        bundler.minify_level(1);
+        bundler.define("N", "3");
+        bundler.run().unwrap();
+        let provenance = fs::read_to_string(&provenance_filename).unwrap();
+        let provenance: serde_json::Value = serde_json::from_str(&provenance).unwrap();
+        let bundle = fs::read(root.join("bundle.rs")).unwrap();
+        assert_eq!(provenance["bundler_version"], env!("CARGO_PKG_VERSION"));
+        assert_eq!(provenance["bundle"]["size"], bundle.len());
+        assert_eq!(provenance["bundle"]["sha256"], sha256::hex_digest(&bundle));
+        assert_eq!(provenance["minified"], serde_json::Value::Null);
+        let inputs = provenance["inputs"].as_array().unwrap();
+        let paths = inputs
+            .iter()
+            .map(|input| input["path"].as_str().unwrap())
+            .collect::<Vec<_>>();
+        let files = ["src/main.rs", "src/lib.rs", "src/io.rs"].map(|file| root.join(file));
+        assert_eq!(
+            paths,
+            files
+                .iter()
+                .map(|f| f.to_str().unwrap())
+                .collect::<Vec<_>>()
+        );
+        let io = fs::read(&files[2]).unwrap();
+        assert_eq!(inputs[2]["sha256"], sha256::hex_digest(&io));
+        assert_eq!(provenance["edition"], "2021");
+        assert_eq!(provenance["minify_level"], 1);
+        assert_eq!(provenance["options"]["crate_name"], "fx");
+        assert_eq!(
+            provenance["options"]["define"],
+            serde_json::json!([{"name": "N", "value": "3"}])
+        );
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn includes_of_source_files_are_expanded() {
+        let root = package(
+            "include",
+            &[
+                ("Cargo.toml", MANIFEST),
+                ("src/lib.rs", "pub mod io;\n"),
+                (
+                    "src/io.rs",
+                    "include!(\"tables.rs\");\npub fn read() -> u32 {\n    TABLE[1]\n}\ninclude!(concat!(env!(\"OUT_DIR\"), \"/gen.rs\"));\n",
+                ),
+                ("src/tables.rs", "const TABLE: [u32; 2] = [1, 2];\n"),
+                ("src/main.rs", "use fx::io::read;\nfn main() {\n    read();\n}\n"),
+            ],
+        );
+        let bundle = bundle_of(&root, |_| {});
+        assert!(
+            bundle.contains("const TABLE: [u32; 2] = [1, 2];\npub fn read()"),
+            "{}",
+            bundle
+        );
+        assert!(!bundle.contains("include!(\"tables.rs\")"), "{}", bundle);
+        let report = report_of(&root, |_| {});
+        let unexpanded = Warning::UnexpandedInclude {
+            file: root.join("src/io.rs"),
+            line: 5,
+        };
+        assert!(
+            report.warnings.contains(&unexpanded),
+            "{:?}",
+            report.warnings
+        );
+        assert!(report.sources.contains(&root.join("src/tables.rs")));
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn plans_have_the_size_and_usage_of_each_module() {
+        let root = package(
+            "module-sizes",
+            &[
+                ("Cargo.toml", MANIFEST),
+                ("src/lib.rs", "pub mod io;\npub mod graph;\n"),
+                (
+                    "src/io.rs",
+                    "// Input.\npub fn read() {}\n\npub fn write() {}\n",
+                ),
+                ("src/graph.rs", "pub mod flow;\npub struct Graph;\n"),
+                (
+                    "src/graph/flow.rs",
+                    "pub fn max_flow() -> u32 {\n    0\n}\n",
+                ),
+                (
+                    "src/main.rs",
+                    "use fx::io::read;\nfn main() {\n    read();\n}\n",
+                ),
+            ],
+        );
+        let plan = Bundler::for_single_package(&root).unwrap().plan().unwrap();
+        let modules = plan
+            .modules
+            .iter()
+            .map(|m| (&m.path[..], m.lines, m.bytes, m.referenced))
+            .collect::<Vec<_>>();
+        let expected = [
+            ("io", 4, 50, true),
+            ("graph", 3, 36, false),
+            ("graph::flow", 5, 52, false),
+        ];
+        assert_eq!(modules, expected);
+        let bundle = bundle_of(&root, |_| {});
+        let library = bundle.find("use crate::io::read;").unwrap();
+        assert_eq!(plan.modules.iter().map(|m| m.bytes).sum::<usize>(), library);
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn run_all_gives_a_result_for_each_target() {
+        let root = package(
+            "run-all",
+            &[
+                ("Cargo.toml", MANIFEST),
+                ("src/lib.rs", "pub mod io;\n"),
+                ("src/io.rs", "pub fn read() {}\n"),
+                (
+                    "src/bin/a.rs",
+                    "use fx::io::read;\nfn main() {\n    read();\n}\n",
+                ),
+                ("src/bin/b.rs", "mod missing;\nfn main() {}\n"),
+            ],
+        );
+        let files = ["src/bin/a.rs", "a.rs", "src/bin/b.rs", "b.rs"].map(|file| root.join(file));
+        let bundler = Bundler::for_single_package(&root).unwrap();
+        let bundlers = [
+            bundler.for_target(&files[0], &files[1]),
+            bundler.for_target(&files[2], &files[3]),
+        ];
+        let results = run_all(&bundlers);
+        assert_eq!(
+            (&results[0].bin, &results[0].bundle),
+            (&files[0], &files[1])
+        );
+        let size = fs::metadata(&files[1]).unwrap().len() as usize;
+        assert_eq!(results[0].result.as_ref().unwrap().size, size);
+        assert!(matches!(
+            results[1].result,
+            Err(Error::ModuleNotFound { .. })
+        ));
+        let json: serde_json::Value = serde_json::from_str(&results_json(&results)).unwrap();
+        assert_eq!(json["ok"], false);
+        assert_eq!(json["targets"][0]["ok"], true);
+        assert_eq!(json["targets"][0]["size"], size);
+        assert_eq!(json["targets"][0]["warnings"], serde_json::json!([]));
+        assert_eq!(json["targets"][1]["bin"], files[2].to_str().unwrap());
+        let error = results[1].result.as_ref().unwrap_err().to_string();
+        assert_eq!(json["targets"][1]["error"], error);
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn nested_paths_of_the_entry_file_resolve_in_the_bundle() {
+        let root = package(
+            "nested-use",
+            &[
+                ("Cargo.toml", MANIFEST),
+                ("src/lib.rs", "pub mod io;\npub mod graph;\n"),
+                ("src/io.rs", "pub fn read() -> u32 {\n    1\n}\n"),
+                ("src/graph.rs", "pub mod flow;\npub struct Graph;\n"),
+                ("src/graph/flow.rs", "pub fn max_flow() -> u32 {\n    2\n}\n"),
+                (
+                    "src/main.rs",
+                    "use fx::{graph::{flow::max_flow, Graph}, io::read};\nfn main() {\n    let _g = Graph;\n    println!(\"{}\", read() + max_flow());\n}\n",
+                ),
+            ],
+        );
+        let bundle = bundle_of(&root, |_| {});
+        assert!(!bundle.contains("fx::"), "{}", bundle);
+        assert_builds(&root, &bundle);
+        let report = report_of(&root, |_| {});
+        assert!(report.warnings.is_empty(), "{:?}", report.warnings);
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn modules_of_the_entry_file_are_expanded() {
+        let root = package(
+            "entry-mods",
+            &[
+                ("Cargo.toml", MANIFEST),
+                ("src/lib.rs", "pub mod io;\n"),
+                ("src/io.rs", "pub fn read() -> u32 {\n    3\n}\n"),
+                ("src/solver.rs", "use fx::io::read;\npub fn solve() -> u32 {\n    read() * 2\n}\n"),
+                (
+                    "src/main.rs",
+                    "mod solver;\nuse fx::io;\nfn main() {\n    println!(\"{}\", solver::solve() + io::read());\n}\n",
+                ),
+            ],
+        );
+        let bundle = bundle_of(&root, |_| {});
+        assert!(bundle.contains("mod solver {"), "{}", bundle);
+        assert!(!bundle.contains("mod solver;"), "{}", bundle);
+        assert_builds(&root, &bundle);
+        let plan = Bundler::for_single_package(&root).unwrap().plan().unwrap();
+        assert!(plan.files.contains(&root.join("src/solver.rs")));
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn the_public_api_keeps_the_private_items_it_uses() {
+        let root = package(
+            "public-api",
+            &[
+                ("Cargo.toml", MANIFEST),
+                ("src/lib.rs", "pub mod math;\n"),
+                (
+                    "src/math.rs",
+                    "fn square(x: u64) -> u64 {\n    x * x\n}\nfn cube(x: u64) -> u64 {\n    x * x * x\n}\npub fn norm(x: u64, y: u64) -> u64 {\n    square(x) + square(y)\n}\n",
+                ),
+                ("src/main.rs", "use fx::math::norm;\nfn main() {\n    println!(\"{}\", norm(3, 4));\n}\n"),
+            ],
+        );
+        let bundle = bundle_of(&root, |bundler| bundler.public_api_only_set(true));
+        assert!(bundle.contains("fn square("), "{}", bundle);
+        assert!(!bundle.contains("fn cube("), "{}", bundle);
+        assert_builds(&root, &bundle);
+        let report = report_of(&root, |bundler| bundler.public_api_only_set(true));
+        assert_eq!(report.pruned_items, ["cube"]);
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn kept_and_prefix_comments_survive_the_stripping() {
+        let root = package(
+            "kept-comments",
+            &[
+                ("Cargo.toml", MANIFEST),
+                ("src/lib.rs", "pub mod io;\n"),
+                ("src/io.rs", "// @judge: fast\n// Reading.\npub fn read() {}\n"),
+                (
+                    "src/main.rs",
+                    "// Problem A\n// by me\n// not kept\nuse fx::io::read;\nfn main() {\n    read();\n}\n",
+                ),
+            ],
+        );
+        let bundle = bundle_of(&root, |bundler| {
+            bundler.keep_comment(Regex::new(r"@judge").unwrap());
+            bundler.preserve_prefix_comments(2);
+        });
+        assert!(bundle.starts_with("// Problem A\n// by me\n"), "{}", bundle);
+        assert!(bundle.contains("// @judge: fast\n"), "{}", bundle);
+        assert!(
+            !bundle.contains("not kept") && !bundle.contains("Reading"),
+            "{}",
+            bundle
+        );
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn forced_modules_are_bundled_at_the_end_of_the_library() {
+        let root = package(
+            "force-include",
+            &[
+                ("Cargo.toml", MANIFEST),
+                ("src/lib.rs", "pub mod io;\n"),
+                ("src/io.rs", "pub fn read() -> u32 {\n    1\n}\n"),
+                ("extra/debug.rs", "pub fn dump(x: u32) -> u32 {\n    x\n}\n"),
+                (
+                    "src/main.rs",
+                    "use fx::debug::dump;\nuse fx::io::read;\nfn main() {\n    println!(\"{}\", dump(read()));\n}\n",
+                ),
+            ],
+        );
+        let debug = root.join("extra/debug.rs");
+        let bundle = bundle_of(&root, |bundler| bundler.force_include_mod("debug", &debug));
+        let io = bundle.find("pub mod io {").unwrap();
+        let forced = bundle.find("pub mod debug {").unwrap();
+        assert!(
+            io < forced && forced < bundle.find("fn main").unwrap(),
+            "{}",
+            bundle
+        );
+        assert_builds(&root, &bundle);
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn profiles_change_a_copy_of_the_configuration() {
+        let root = package(
+            "profiles",
+            &[
+                ("Cargo.toml", MANIFEST),
+                ("src/lib.rs", "pub mod io;\n"),
+                ("src/io.rs", "// Reading.\npub fn read() {}\n"),
+                (
+                    "src/main.rs",
+                    "use fx::io::read;\nfn main() {\n    read();\n}\n",
+                ),
+            ],
+        );
+        let submit = root.join("submit.rs");
+        let mut bundler = Bundler::for_single_package(&root).unwrap();
+        bundler.profile("debug", |b| b.strip_comments_set(false));
+        bundler.profile("submit", |b| b.minify_level(1));
+        bundler.profile("submit", |b| {
+            b.minify_level(3);
+            b.bundle_file(&submit);
+        });
+        bundler.run_profile("debug").unwrap();
+        let debug = fs::read_to_string(root.join("bundle.rs")).unwrap();
+        assert!(debug.contains("// Reading."), "{}", debug);
+        let report = bundler.run_profile("submit").unwrap();
+        assert_eq!(report.minify_level, 3);
+        assert_eq!(fs::read_to_string(&submit).unwrap().len(), report.size);
+        assert_eq!(bundler.bundle_filename(), root.join("bundle.rs"));
+        assert_eq!(
+            bundler.options(),
+            Bundler::for_single_package(&root).unwrap().options()
+        );
+        match bundler.run_profile("release") {
+            Err(Error::UnknownProfile { name }) => assert_eq!(name, "release"),
+            other => panic!("{:?}", other),
+        }
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn super_and_crate_paths_stay_valid_in_the_wrappers() {
+        let root = package(
+            "super-paths",
+            &[
+                ("Cargo.toml", MANIFEST),
+                ("src/lib.rs", "pub mod graph;\npub mod util;\n"),
+                ("src/util.rs", "pub fn min(a: u32, b: u32) -> u32 {\n    a.min(b)\n}\n"),
+                ("src/graph.rs", "pub mod flow;\nconst INF: u32 = 1 << 30;\n"),
+                (
+                    "src/graph/flow.rs",
+                    "use super::INF;\nuse crate::util::min;\npub fn cap() -> u32 {\n    min(INF, super::super::util::min(5, 9))\n}\n",
+                ),
+                ("src/main.rs", "use fx::graph::flow::cap;\nfn main() {\n    println!(\"{}\", cap());\n}\n"),
+            ],
+        );
+        let bundle = bundle_of(&root, |_| {});
+        assert!(
+            bundle.contains("use super::INF;\nuse crate::util::min;"),
+            "{}",
+            bundle
+        );
+        assert_builds(&root, &bundle);
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn verification_compares_the_outputs_for_each_input() {
+        let root = package(
+            "verify-mismatch",
+            &[
+                ("Cargo.toml", MANIFEST),
+                ("src/lib.rs", "pub fn double(x: u32) -> u32 {\n    2 * x\n}\n"),
+                (
+                    "src/main.rs",
+                    "use fx::double;\nuse std::io::Read;\nfn main() {\n    let mut s = String::new();\n    std::io::stdin().read_to_string(&mut s).unwrap();\n    println!(\"{}\", double(s.trim().parse().unwrap()));\n}\n",
+                ),
+                ("a.txt", "4\n"),
+                ("b.txt", "10\n"),
+            ],
+        );
+        let inputs = [root.join("a.txt"), root.join("b.txt")];
+        let mut bundler = Bundler::for_single_package(&root).unwrap();
+        bundler.verify_input(&inputs[0]);
+        bundler.verify_input(&inputs[1]);
+        let report = bundler.run().unwrap();
+        assert!(report.warnings.is_empty(), "{:?}", report.warnings);
+        let outputs = report
+            .verify_runs
+            .iter()
+            .map(|run| (run.input.clone(), run.program, &run.stdout[..]))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            outputs,
+            [
+                (inputs[0].clone(), "original", "8\n"),
+                (inputs[0].clone(), "bundle", "8\n"),
+                (inputs[1].clone(), "original", "20\n"),
+                (inputs[1].clone(), "bundle", "20\n"),
+            ]
+        );
+        let mut bundler = Bundler::for_single_package(&root).unwrap();
+        bundler.verify_input(&inputs[0]);
+        bundler.rewrite_bin(Regex::new(r"double\(s").unwrap(), "1 + double(s");
+        let report = bundler.run().unwrap();
+        let mismatch = Warning::OutputMismatch {
+            input: inputs[0].clone(),
+        };
+        assert_eq!(report.warnings, [mismatch]);
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn regex_is_the_only_dependency_of_the_default_features() {
+        let manifest = Path::new(env!("CARGO_MANIFEST_DIR")).join("Cargo.toml");
+        let manifest = fs::read_to_string(manifest).unwrap();
+        let dependencies = manifest
+            .split("[dependencies]\n")
+            .nth(1)
+            .unwrap()
+            .lines()
+            .take_while(|line| !line.starts_with('['))
+            .filter(|line| !line.trim().is_empty() && !line.contains("optional = true"))
+            .map(|line| line.split(' ').next().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(dependencies, ["regex"]);
+    }
+
+    #[test]
+    fn library_only_bundles_are_libraries_without_the_entry_file() {
+        let root = package(
+            "library-only",
+            &[
+                ("Cargo.toml", MANIFEST),
+                (
+                    "src/lib.rs",
+                    "#![allow(dead_code)]\npub mod io;\nfn helper() {}\n",
+                ),
+                ("src/io.rs", "pub fn read() -> u32 {\n    1\n}\n"),
+                ("src/main.rs", "fn main() {\n    unreachable!();\n}\n"),
+            ],
+        );
+        let bundle = bundle_of(&root, |bundler| bundler.library_only_set(true));
+        assert!(
+            !bundle.contains("fn main") && !bundle.contains("#![allow"),
+            "{}",
+            bundle
+        );
+        assert!(
+            bundle.contains("pub mod io {") && bundle.contains("fn helper() {}"),
+            "{}",
+            bundle
+        );
+        let report = report_of(&root, |bundler| bundler.library_only_set(true));
+        assert!(report.warnings.is_empty(), "{:?}", report.warnings);
+        let mut bundler = Bundler::for_single_package(&root).unwrap();
+        bundler.library_only_set(true);
+        let plan = bundler.plan().unwrap();
+        assert!(!plan.files.contains(&root.join("src/main.rs")));
+        fs::write(root.join("bundle.rs"), &bundle).unwrap();
+        let rustc = env::var("RUSTC").unwrap_or_else(|_| "rustc".to_string());
+        let output = Command::new(rustc)
+            .args(["--edition", "2021", "--crate-type", "lib", "--out-dir"])
+            .arg(&root)
+            .arg(root.join("bundle.rs"))
+            .output()
+            .unwrap();
+        assert!(
+            output.status.success(),
+            "{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn inner_docs_of_module_files_become_docs_of_the_wrapper() {
+        let root = package(
+            "module-docs",
+            &[
+                ("Cargo.toml", MANIFEST),
+                ("src/lib.rs", "pub mod io;\n"),
+                (
+                    "src/io.rs",
+                    "//! Fast input.\n//!\n//! Reads tokens.\npub fn read() {}\n",
+                ),
+                (
+                    "src/main.rs",
+                    "use fx::io::read;\nfn main() {\n    read();\n}\n",
+                ),
+            ],
+        );
+        let bundle = bundle_of(&root, |bundler| bundler.strip_comments_set(false));
+        let docs = "/// Fast input.\n///\n/// Reads tokens.\npub mod io {\npub fn read() {}\n}\n";
+        assert!(bundle.starts_with(docs), "{}", bundle);
+        assert_builds(&root, &bundle);
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn license_headers_are_kept_once_at_the_top() {
+        let mit = "// Copyright (c) 2020 Someone\n// SPDX-License-Identifier: MIT\n";
+        let graph = format!("{}pub fn bfs() {{}}\n", mit);
+        let flow = format!("{}\n// Max flow.\npub fn dinic() {{}}\n", mit);
+        let root = package(
+            "licenses",
+            &[
+                ("Cargo.toml", MANIFEST),
+                (
+                    "src/lib.rs",
+                    "// Just a library.\npub mod graph;\npub mod flow;\npub mod seg;\n",
+                ),
+                ("src/graph.rs", &graph),
+                ("src/flow.rs", &flow),
+                (
+                    "src/seg.rs",
+                    "/* Licensed under Apache-2.0 */\npub fn build() {}\n",
+                ),
+                (
+                    "src/main.rs",
+                    "use fx::graph::bfs;\nfn main() {\n    bfs();\n}\n",
+                ),
+            ],
+        );
+        let bundle = bundle_of(&root, |bundler| {
+            bundler.strip_comments_set(true);
+            bundler.collect_licenses_set(true);
+        });
+        let licenses = format!("{}/* Licensed under Apache-2.0 */\n", mit);
+        assert!(bundle.starts_with(&licenses), "{}", bundle);
+        assert_eq!(bundle.matches("Copyright").count(), 1, "{}", bundle);
+        assert!(!bundle.contains("library") && !bundle.contains("Max flow"));
+        assert_builds(&root, &bundle);
+        let bundle = bundle_of(&root, |bundler| bundler.strip_comments_set(true));
+        assert!(!bundle.contains("Copyright") && !bundle.contains("Licensed"));
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn modules_of_a_bin_target_are_found_next_to_it() {
+        let root = package(
+            "bin-modules",
+            &[
+                ("Cargo.toml", MANIFEST),
+                ("src/lib.rs", "pub fn one() -> u32 {\n    1\n}\n"),
+                ("src/util.rs", "compile_error!(\"not this one\");\n"),
+                ("src/bin/util.rs", "pub fn two() -> u32 {\n    2\n}\n"),
+                ("src/bin/shared/io.rs", "pub fn three() -> u32 {\n    3\n}\n"),
+                (
+                    "src/bin/a.rs",
+                    "use fx::one;\nmod util;\n#[path = \"shared/io.rs\"]\nmod io;\nfn main() {\n    println!(\"{}\", one() + util::two() + io::three());\n}\n",
+                ),
+            ],
+        );
+        let bin = root.join("src").join("bin").join("a.rs");
+        let out = root.join("a.bundle.rs");
+        let bundler = Bundler::for_single_package(&root).unwrap();
+        let bundle = bundler
+            .for_target(&bin, &out)
+            .reader()
+            .unwrap()
+            .into_inner();
+        let bundle = String::from_utf8(bundle).unwrap();
+        assert!(bundle.contains("mod util {\npub fn two()"), "{}", bundle);
+        assert!(bundle.contains("mod io {\npub fn three()"), "{}", bundle);
+        assert!(!bundle.contains("#[path") && !bundle.contains("compile_error"));
+        assert_builds(&root, &bundle);
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn forbidden_patterns_in_the_code_fail_the_bundle() {
+        let main =
+            "fn main() {\n    // todo!() later\n    println!(\"todo!()\");\n    dbg!(1);\n}\n";
+        let root = package("forbid", &[("Cargo.toml", MANIFEST), ("src/main.rs", main)]);
+        let mut bundler = Bundler::for_single_package(&root).unwrap();
+        bundler.forbid_unfinished();
+        assert!(bundler.reader().is_ok());
+        bundler.forbid(Regex::new(r"\bdbg!").unwrap());
+        match bundler.reader() {
+            Err(Error::ForbiddenPattern { pattern, line }) => {
+                assert_eq!((&pattern[..], line), (r"\bdbg!", 3));
+            }
+            other => panic!("{:?}", other.map(|reader| reader.report().clone())),
+        }
+        fs::write(
+            root.join("src/main.rs"),
+            "fn main() {\n    unimplemented !()\n}\n",
+        )
+        .unwrap();
+        let mut bundler = Bundler::for_single_package(&root).unwrap();
+        bundler.forbid_unfinished();
+        assert!(matches!(
+            bundler.reader(),
+            Err(Error::ForbiddenPattern { line: 2, .. })
+        ));
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn presets_set_the_limits_and_crates_of_the_judge() {
+        let root = package(
+            "presets",
+            &[
+                ("Cargo.toml", MANIFEST),
+                ("src/lib.rs", "pub fn one() -> u32 {\n    1\n}\n"),
+                (
+                    "src/main.rs",
+                    "use fx::one;\nuse itertools::Itertools;\nfn main() {\n    println!(\"{}\", [one()].iter().join(\" \"));\n}\n",
+                ),
+            ],
+        );
+        let disallowed = |preset: judge::Preset| {
+            report_of(&root, |bundler| bundler.preset(preset))
+                .warnings
+                .into_iter()
+                .filter(|w| matches!(w, Warning::DisallowedCrate { .. }))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            disallowed(judge::Preset::Codeforces),
+            [Warning::DisallowedCrate {
+                name: "itertools".to_string()
+            }]
+        );
+        assert_eq!(disallowed(judge::Preset::AtCoder), []);
+        let bundle = bundle_of(&root, |bundler| bundler.preset(judge::Preset::Exercism));
+        assert!(!bundle.contains("fn main"), "{}", bundle);
+        let mut bundler = Bundler::for_single_package(&root).unwrap();
+        bundler.preset(judge::Preset::Codeforces);
+        bundler.preset(|bundler: &mut Bundler| bundler.minify_level(2));
+        let options = bundler.options();
+        assert_eq!(options.size_limit, Some(65535));
+        assert!(options.minify_auto);
+        assert_eq!(options.minify_level, 2);
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn the_environment_overrides_the_configuration() {
+        let root = package(
+            "apply-env",
+            &[("Cargo.toml", MANIFEST), ("src/main.rs", "fn main() {}\n")],
+        );
+        let mut bundler = Bundler::for_single_package(&root).unwrap();
+        bundler.profile("release", |bundler| bundler.minify_level(3));
+        // Only this test sets these variables:
+        env::set_var("SOURCEBUNDLER_OUTPUT", "out/bundle.rs");
+        env::set_var("SOURCEBUNDLER_MINIFY", "1");
+        env::set_var("SOURCEBUNDLER_STRIP_COMMENTS", "false");
+        env::set_var("SOURCEBUNDLER_PROFILE", "");
+        bundler.apply_env().unwrap();
+        assert_eq!(bundler.bundle_filename(), Path::new("out/bundle.rs"));
+        let options = bundler.options();
+        assert_eq!((options.minify_level, options.strip_comments), (1, false));
+        env::set_var("SOURCEBUNDLER_PROFILE", "release");
+        bundler.apply_env().unwrap();
+        assert_eq!(bundler.options().minify_level, 3);
+        env::set_var("SOURCEBUNDLER_STRIP_COMMENTS", "maybe");
+        let error = bundler.apply_env().unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "invalid SOURCEBUNDLER_STRIP_COMMENTS: \"maybe\""
+        );
+        for name in ENV_VARS {
+            env::remove_var(name);
+        }
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn the_module_map_has_the_file_of_each_wrapper() {
+        let root = package(
+            "module-map",
+            &[
+                ("Cargo.toml", MANIFEST),
+                (
+                    "src/lib.rs",
+                    "pub mod graph;\npub mod util {\n    pub fn id() {}\n}\n",
+                ),
+                ("src/graph.rs", "pub mod flow;\n"),
+                ("src/graph/flow.rs", "pub fn dinic() {}\n"),
+                (
+                    "src/main.rs",
+                    "use fx::graph::flow::dinic;\nfn main() {\n    dinic();\n}\n",
+                ),
+            ],
+        );
+        let report = report_of(&root, |_| {});
+        let map: Vec<(&str, PathBuf)> = report
+            .module_map
+            .iter()
+            .map(|(module, file)| (&module[..], file.strip_prefix(&root).unwrap().to_path_buf()))
+            .collect();
+        assert_eq!(
+            map,
+            [
+                ("graph", PathBuf::from("src/graph.rs")),
+                ("graph::flow", PathBuf::from("src/graph/flow.rs")),
+            ]
+        );
+        let json: serde_json::Value = serde_json::from_str(&report.to_json()).unwrap();
+        assert_eq!(
+            json["module_map"]["graph::flow"],
+            root.join("src/graph/flow.rs").to_str().unwrap()
+        );
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn includes_in_one_line_inline_modules_are_expanded() {
+        let root = package(
+            "inline-include",
+            &[
+                ("Cargo.toml", MANIFEST),
+                (
+                    "src/lib.rs",
+                    "pub mod tables { include!(\"tables_gen.rs\"); }\n",
+                ),
+                (
+                    "src/tables_gen.rs",
+                    "pub const SQUARES: [u32; 3] = [0, 1, 4];\n",
+                ),
+                (
+                    "src/main.rs",
+                    "use fx::tables::SQUARES;\nfn main() {\n    println!(\"{}\", SQUARES[2]);\n}\n",
+                ),
+            ],
+        );
+        let bundle = bundle_of(&root, |_| {});
+        assert!(!bundle.contains("include!"), "{}", bundle);
+        assert!(bundle.contains("pub const SQUARES"), "{}", bundle);
+        assert_builds(&root, &bundle);
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn items_and_modules_marked_skip_are_not_bundled() {
+        let root = package(
+            "skip-marker",
+            &[
+                ("Cargo.toml", MANIFEST),
+                (
+                    "src/lib.rs",
+                    "#[cfg_attr(sourcebundle, skip)]\npub mod bench;\npub mod math;\n",
+                ),
+                ("src/bench.rs", "pub fn run() {}\n"),
+                (
+                    "src/math.rs",
+                    "#[cfg_attr(sourcebundle, skip)]\npub const BIG: [u8; 4] = [0; 4];\npub fn gcd(a: u64, b: u64) -> u64 {\n    if b == 0 { a } else { gcd(b, a % b) }\n}\n",
+                ),
+                (
+                    "src/main.rs",
+                    "use fx::math::gcd;\nfn main() {\n    println!(\"{}\", gcd(4, 6));\n}\n",
+                ),
+            ],
+        );
+        let report = report_of(&root, |_| {});
+        assert_eq!(report.excluded_items, ["bench", "math::BIG"]);
+        let bundle = bundle_of(&root, |_| {});
+        assert!(
+            !bundle.contains("bench") && !bundle.contains("BIG"),
+            "{}",
+            bundle
+        );
+        assert!(!bundle.contains("sourcebundle"), "{}", bundle);
+        assert_builds(&root, &bundle);
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn size_estimates_match_the_bundle_and_write_nothing() {
+        let root = package(
+            "estimate",
+            &[
+                ("Cargo.toml", MANIFEST),
+                (
+                    "src/lib.rs",
+                    "// Adds.\npub fn add(a: u32, b: u32) -> u32 {\n    a + b\n}\n",
+                ),
+                (
+                    "src/main.rs",
+                    "use fx::add;\nfn main() {\n    println!(\"{}\", add(1, 2));\n}\n",
+                ),
+            ],
+        );
+        let mut bundler = Bundler::for_single_package(&root).unwrap();
+        let size = bundler.estimate_size().unwrap();
+        assert_eq!(size, bundler.reader().unwrap().into_inner().len());
+        bundler.minify_level(3);
+        assert!(bundler.estimate_size().unwrap() < size);
+        assert!(!bundler.bundle_filename().exists());
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn single_packages_are_set_up_from_the_manifest() {
+        let root = package(
+            "single-package",
+            &[
+                (
+                    "Cargo.toml",
+                    "[package]\nname = \"my-algo\"\nedition = \"2018\"\n\n[lib]\nname = \"algo\"\n",
+                ),
+                ("src/lib.rs", "pub fn two() -> u32 {\n    2\n}\n"),
+                (
+                    "src/main.rs",
+                    "use algo::two;\nfn main() {\n    println!(\"{}\", two());\n}\n",
+                ),
+            ],
+        );
+        let bundler = Bundler::for_single_package(&root).unwrap();
+        assert_eq!(bundler.bundle_filename(), root.join("bundle.rs"));
+        assert_eq!(bundler.options().crate_name, "algo");
+        let report = bundler.run().unwrap();
+        let bundle = fs::read_to_string(root.join("bundle.rs")).unwrap();
+        assert_eq!(report.size, bundle.len());
+        assert!(bundle.starts_with("pub fn two()"), "{}", bundle);
+        assert_builds(&root, &bundle);
+        let missing = root.join("missing");
+        assert!(matches!(
+            Bundler::for_single_package(&missing),
+            Err(Error::Io { .. })
+        ));
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn runs_that_time_out_are_killed_and_reported() {
+        let root = package(
+            "verify-timeout",
+            &[
+                ("Cargo.toml", MANIFEST),
+                (
+                    "src/main.rs",
+                    "use std::io::Read;\nfn main() {\n    let mut s = String::new();\n    std::io::stdin().read_to_string(&mut s).unwrap();\n    eprintln!(\"start\");\n    while s.trim() == \"loop\" {}\n    println!(\"{}\", s.trim());\n}\n",
+                ),
+                ("done.txt", "done\n"),
+                ("loop.txt", "loop\n"),
+            ],
+        );
+        let inputs = [root.join("done.txt"), root.join("loop.txt")];
+        let mut bundler = Bundler::for_single_package(&root).unwrap();
+        bundler.verify_input(&inputs[0]);
+        bundler.verify_input(&inputs[1]);
+        let run_timeout = Duration::from_millis(500);
+        bundler.verify_timeouts(Duration::from_secs(300), run_timeout);
+        let report = bundler.run().unwrap();
+        let timeouts: Vec<Warning> = ["original", "bundle"]
+            .iter()
+            .map(|program| Warning::VerificationTimeout {
+                input: inputs[1].clone(),
+                program: program.to_string(),
+                timeout: run_timeout,
+            })
+            .collect();
+        let warnings: Vec<&Warning> = report
+            .warnings
+            .iter()
+            .filter(|w| matches!(w, Warning::VerificationTimeout { .. }))
+            .collect();
+        assert_eq!(warnings, timeouts.iter().collect::<Vec<_>>());
+        let runs: Vec<(bool, &str, &str)> = report
+            .verify_runs
+            .iter()
+            .map(|run| (run.timed_out, &run.stdout[..], &run.stderr[..]))
+            .collect();
+        assert_eq!(
+            runs,
+            [
+                (false, "done\n", "start\n"),
+                (false, "done\n", "start\n"),
+                (true, "", "start\n"),
+                (true, "", "start\n"),
+            ]
+        );
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn missing_modules_are_shown_at_their_declaration() {
+        let root = package(
+            "diagnostic",
+            &[
+                ("Cargo.toml", MANIFEST),
+                ("src/lib.rs", "pub mod io;\n"),
+                ("src/io.rs", "// Input.\n    pub mod scanner;\n"),
+                ("src/main.rs", "use fx::io;\nfn main() {}\n"),
+            ],
+        );
+        let error = Bundler::for_single_package(&root)
+            .unwrap()
+            .reader()
+            .unwrap_err();
+        let io = root.join("src").join("io");
+        assert_eq!(
+            error.diagnostic(),
+            format!(
+                "error: could not find file for module io::scanner\n --> {}:2\n  |\n2 |     pub mod scanner;\n  |     ^^^^^^^^^^^^^^^^\n  |\n  = note: tried {} {}\n",
+                root.join("src").join("io.rs").display(),
+                io.join("scanner.rs").display(),
+                io.join("scanner").join("mod.rs").display(),
+            )
+        );
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn local_sections_are_dropped_and_bundle_lines_uncommented() {
+        let main = "\
+use std::io::Read;
+fn main() {
+    let mut s = String::new();
+    // bundler:only-local
+    let mut input = std::fs::File::open(\"in.txt\").unwrap();
+    input.read_to_string(&mut s).unwrap();
+    // bundler:end
+    // bundler:only-bundle: std::io::stdin().read_to_string(&mut s).unwrap();
+    println!(\"{}\", s.trim());
+}
+";
+        let root = package(
+            "sections",
+            &[("Cargo.toml", MANIFEST), ("src/main.rs", main)],
+        );
+        let bundle = bundle_of(&root, |bundler| bundler.strip_comments_set(false));
+        assert_eq!(
+            bundle,
+            "use std::io::Read;\nfn main() {\n    let mut s = String::new();\n    std::io::stdin().read_to_string(&mut s).unwrap();\n    println!(\"{}\", s.trim());\n}\n"
+        );
+        assert_builds(&root, &bundle);
+        fs::write(
+            root.join("src/main.rs"),
+            main.replace("    // bundler:end\n", ""),
+        )
+        .unwrap();
+        let report = report_of(&root, |_| {});
+        let unterminated = Warning::UnterminatedSection {
+            file: root.join("src/main.rs"),
+            line: 4,
+        };
+        assert!(
+            report.warnings.contains(&unterminated),
+            "{:?}",
+            report.warnings
+        );
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn split_bundles_move_the_biggest_modules_to_their_own_files() {
+        let table = format!(
+            "pub const TABLE: [u32; 64] = [{}];\n",
+            vec!["1000"; 64].join(", ")
+        );
+        let root = package(
+            "split",
+            &[
+                ("Cargo.toml", MANIFEST),
+                ("src/lib.rs", "pub mod tables;\npub mod math;\n"),
+                ("src/tables.rs", &table),
+                ("src/math.rs", "pub fn sq(x: u32) -> u32 {\n    x * x\n}\n"),
+                (
+                    "src/main.rs",
+                    "use fx::math::sq;\nuse fx::tables::TABLE;\nfn main() {\n    println!(\"{}\", sq(TABLE[0]));\n}\n",
+                ),
+            ],
+        );
+        let mut bundler = Bundler::for_single_package(&root).unwrap();
+        bundler.split_output(200);
+        let report = bundler.run().unwrap();
+        let part = root.join("bundle.tables.rs");
+        assert_eq!(report.parts, [root.join("bundle.rs"), part.clone()]);
+        let bundle = fs::read_to_string(root.join("bundle.rs")).unwrap();
+        assert!(bundle.len() <= 200, "{}", bundle);
+        assert!(
+            bundle.starts_with("#[path = \"bundle.tables.rs\"] pub mod tables;\npub mod math {"),
+            "{}",
+            bundle
+        );
+        assert_eq!(fs::read_to_string(&part).unwrap(), table);
+        let parts = fs::read_to_string(root.join("bundle.parts")).unwrap();
+        assert_eq!(parts, "bundle.rs\nbundle.tables.rs\n");
+        assert_builds(&root, &bundle);
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn progress_events_come_in_the_order_of_the_run() {
+        let root = package(
+            "progress",
+            &[
+                ("Cargo.toml", MANIFEST),
+                ("src/lib.rs", "pub mod graph;\n"),
+                ("src/graph.rs", "pub mod flow;\n"),
+                ("src/graph/flow.rs", "pub fn dinic() {}\n"),
+                (
+                    "src/main.rs",
+                    "use fx::graph::flow::dinic;\nfn main() {\n    dinic();\n}\n",
+                ),
+            ],
+        );
+        let events = std::sync::Mutex::new(Vec::new());
+        let mut bundler = Bundler::for_single_package(&root).unwrap();
+        bundler.on_progress(|event| events.lock().unwrap().push(event));
+        let report = bundler.run().unwrap();
+        let src = root.join("src");
+        assert_eq!(
+            *events.lock().unwrap(),
+            [
+                ProgressEvent::FileOpened {
+                    path: src.join("main.rs")
+                },
+                ProgressEvent::FileOpened {
+                    path: src.join("lib.rs")
+                },
+                ProgressEvent::FileOpened {
+                    path: src.join("graph.rs")
+                },
+                ProgressEvent::ModuleExpanded {
+                    path: "graph".to_string(),
+                    file: src.join("graph.rs")
+                },
+                ProgressEvent::FileOpened {
+                    path: src.join("graph").join("flow.rs")
+                },
+                ProgressEvent::ModuleExpanded {
+                    path: "graph::flow".to_string(),
+                    file: src.join("graph").join("flow.rs")
+                },
+                ProgressEvent::BytesWritten {
+                    path: root.join("bundle.rs"),
+                    bytes: report.size
+                },
+            ]
+        );
+        drop(bundler);
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn inner_attributes_of_the_entry_file_follow_the_policy() {
+        let root = package(
+            "inner-attributes",
+            &[
+                ("Cargo.toml", MANIFEST),
+                ("src/lib.rs", "pub fn one() -> u32 {\n    1\n}\n"),
+                (
+                    "src/main.rs",
+                    "// bundler:insert-lib\n#![allow(clippy::needless_range_loop)]\n#![warn(missing_docs)]\nfn main() {\n    println!(\"{}\", one());\n}\n",
+                ),
+            ],
+        );
+        let lib = "pub fn one() -> u32 {\n    1\n}\n";
+        let main = "fn main() {\n    println!(\"{}\", one());\n}\n";
+        let attribute = "#![allow(clippy::needless_range_loop)]\n";
+        let bundle = bundle_of(&root, |_| {});
+        assert_eq!(bundle, format!("{}{}{}", lib, attribute, main));
+        let bundle = bundle_of(&root, |bundler| {
+            bundler.entry_inner_attributes(InnerAttributes::Top)
+        });
+        assert_eq!(bundle, format!("{}{}{}", attribute, lib, main));
+        assert_builds(&root, &bundle);
+        let bundle = bundle_of(&root, |bundler| {
+            bundler.entry_inner_attributes(InnerAttributes::Strip)
+        });
+        assert_eq!(bundle, format!("{}{}", lib, main));
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn the_package_name_with_dashes_is_a_crate_name() {
+        let root = package(
+            "dashed-name",
+            &[
+                (
+                    "Cargo.toml",
+                    "[package]\nname = \"my-contest-lib\"\nedition = \"2021\"\n\n[lib]\nname = \"algolib\"\n",
+                ),
+                ("src/lib.rs", "pub fn two() -> u32 {\n    2\n}\n"),
+                (
+                    "src/main.rs",
+                    "use algolib::two;\nuse my_contest_lib::two as deux;\nfn main() {\n    println!(\"{}\", two() + deux());\n}\n",
+                ),
+            ],
+        );
+        let bundle = bundle_of(&root, |bundler| bundler.crate_name("my-contest-lib"));
+        assert!(bundle.starts_with("pub fn two()"), "{}", bundle);
+        assert!(bundle.contains("use crate::two as deux;"), "{}", bundle);
+        assert!(!bundle.contains("algolib") && !bundle.contains("my_contest_lib"));
+        assert_builds(&root, &bundle);
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn excluded_submodules_leave_the_rest_of_their_parent() {
+        let root = package(
+            "exclude-submodule",
+            &[
+                ("Cargo.toml", MANIFEST),
+                ("src/lib.rs", "pub mod graph;\npub mod matching;\n"),
+                ("src/graph.rs", "pub mod matching;\npub fn bfs() {}\n"),
+                ("src/graph/matching.rs", "pub fn hopcroft_karp() {}\n"),
+                ("src/matching.rs", "pub fn hungarian() {}\n"),
+                (
+                    "src/main.rs",
+                    "use fx::graph::bfs;\nuse fx::matching::hungarian;\nfn main() {\n    bfs();\n    hungarian();\n}\n",
+                ),
+            ],
+        );
+        let bundle = bundle_of(&root, |bundler| bundler.exclude_mod("graph::matching"));
+        assert!(!bundle.contains("hopcroft_karp"), "{}", bundle);
+        assert!(bundle.contains("pub fn bfs()") && bundle.contains("pub fn hungarian()"));
+        assert_builds(&root, &bundle);
+        let bundle = bundle_of(&root, |bundler| bundler.exclude_mod("matching"));
+        assert!(bundle.contains("hopcroft_karp") && !bundle.contains("pub fn hungarian"));
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn attributes_and_docs_before_a_declaration_go_on_its_wrapper() {
+        let root = package(
+            "mod-attributes",
+            &[
+                ("Cargo.toml", MANIFEST),
+                (
+                    "src/lib.rs",
+                    "/// Graph algorithms.\n#[allow(dead_code)]\n#[cfg(not(test))]\npub mod graph;\n/// Tests.\n#[cfg(test)]\nmod tests;\n",
+                ),
+                ("src/graph.rs", "fn unused() {}\npub fn bfs() {}\n"),
+                (
+                    "src/main.rs",
+                    "use fx::graph::bfs;\nfn main() {\n    bfs();\n}\n",
+                ),
+            ],
+        );
+        let bundle = bundle_of(&root, |bundler| bundler.strip_comments_set(false));
+        assert!(
+            bundle.starts_with(
+                "/// Graph algorithms.\n#[allow(dead_code)]\n#[cfg(not(test))]\npub mod graph {\nfn unused() {}\n"
+            ),
+            "{}",
+            bundle
+        );
+        assert!(!bundle.contains("Tests.") && !bundle.contains("cfg(test)"));
+        assert_builds(&root, &bundle);
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn default_bundle_files_go_to_the_target_directory() {
+        let root = package(
+            "default-output",
+            &[
+                ("Cargo.toml", MANIFEST),
+                ("src/main.rs", "fn main() {}\n"),
+                ("src/bin/b.rs", "fn main() {}\n"),
+            ],
+        );
+        let bin = root.join("src").join("bin").join("b.rs");
+        let nested = root.join("out").join("deep").join("b.rs");
+        let target_dir =
+            env::var_os("CARGO_TARGET_DIR").map_or_else(|| root.join("target"), PathBuf::from);
+        let mut bundler = Bundler::for_single_package(&root).unwrap();
+        bundler.default_bundle_file();
+        assert_eq!(
+            bundler.bundle_filename(),
+            target_dir.join("sourcebundle").join("fx.rs")
+        );
+        let mut bundler = bundler.for_target(&bin, &nested);
+        bundler.run().unwrap();
+        assert!(nested.exists());
+        bundler.default_bundle_file();
+        assert_eq!(
+            bundler.bundle_filename(),
+            target_dir.join("sourcebundle").join("b.rs")
+        );
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn runs_see_the_current_options_and_files() {
+        let root = package(
+            "no-cache",
+            &[
+                ("Cargo.toml", MANIFEST),
+                ("src/lib.rs", "// One.\npub fn one() -> u32 {\n    1\n}\n"),
+                ("src/main.rs", "use fx::one;\nfn main() {\n    one();\n}\n"),
+            ],
+        );
+        let mut bundler = Bundler::for_single_package(&root).unwrap();
+        bundler.strip_comments_set(false);
+        let first = bundler.estimate_size().unwrap();
+        bundler.strip_comments_set(true);
+        assert_eq!(bundler.estimate_size().unwrap(), first - "// One.\n".len());
+        fs::write(
+            root.join("src/lib.rs"),
+            "pub fn one() -> u32 {\n    11\n}\n",
+        )
+        .unwrap();
+        assert_eq!(
+            bundler.estimate_size().unwrap(),
+            first - "// One.\n".len() + 1
+        );
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn macros_of_the_library_can_be_imported_from_the_entry_file() {
+        let root = package(
+            "macro-imports",
+            &[
+                ("Cargo.toml", MANIFEST),
+                ("src/lib.rs", "#[macro_use]\nmod macros;\npub mod math;\n"),
+                (
+                    "src/macros.rs",
+                    "#[macro_export]\nmacro_rules! chmin {\n    ($a:expr, $b:expr) => {\n        if $b < $a {\n            $a = $b;\n        }\n    };\n}\nmacro_rules! double {\n    ($a:expr) => {\n        2 * $a\n    };\n}\n",
+                ),
+                ("src/math.rs", "pub fn four() -> u32 {\n    double!(2)\n}\n"),
+                (
+                    "src/solve.rs",
+                    "use fx::chmin;\nuse fx::math::four;\npub fn best(mut a: u32) -> u32 {\n    chmin!(a, four());\n    a\n}\n",
+                ),
+                (
+                    "src/main.rs",
+                    "#[macro_use]\nextern crate fx;\nmod solve;\nfn main() {\n    let mut a = 9;\n    chmin!(a, solve::best(5));\n    println!(\"{}\", a);\n}\n",
+                ),
+            ],
+        );
+        let bundle = bundle_of(&root, |_| {});
+        assert!(
+            bundle.starts_with("#[macro_export]\nmacro_rules! chmin {"),
+            "{}",
+            bundle
+        );
+        assert!(
+            bundle.contains("#[macro_use]\npub mod macros {"),
+            "{}",
+            bundle
+        );
+        assert!(bundle.contains("use crate::chmin;"), "{}", bundle);
+        assert!(!bundle.contains("extern crate"), "{}", bundle);
+        assert_builds(&root, &bundle);
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn checks_fail_on_what_would_break_the_bundle_without_writing() {
+        let root = package(
+            "check-only",
+            &[
+                ("Cargo.toml", MANIFEST),
+                ("src/lib.rs", "pub mod io;\n"),
+                (
+                    "src/io.rs",
+                    "pub fn home() -> &'static str {\n    env!(\"HOME\")\n}\n",
+                ),
+                (
+                    "src/main.rs",
+                    "use fx::io::home;\nfn main() {\n    home();\n}\n",
+                ),
+            ],
+        );
+        let mut bundler = Bundler::for_single_package(&root).unwrap();
+        match bundler.check_only() {
+            Err(Error::CheckFailed { problems }) => {
+                let env = Warning::EnvMacro {
+                    file: root.join("src/io.rs"),
+                    line: 2,
+                };
+                assert_eq!(problems, [env]);
+            }
+            other => panic!("{:?}", other),
+        }
+        fs::write(
+            root.join("src/io.rs"),
+            "pub fn home() -> &'static str {\n    \"/\"\n}\n",
+        )
+        .unwrap();
+        assert!(bundler.check_only().unwrap().warnings.is_empty());
+        bundler.size_limit(10);
+        assert!(matches!(
+            bundler.check_only(),
+            Err(Error::CheckFailed { ref problems })
+                if matches!(problems[..], [Warning::OversizedOutput { limit: 10, .. }])
+        ));
+        assert!(!bundler.bundle_filename().exists());
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn snippets_are_bundled_with_the_library_on_disk() {
+        let root = package(
+            "snippet",
+            &[
+                ("Cargo.toml", MANIFEST),
+                ("src/lib.rs", "pub fn two() -> u32 {\n    2\n}\n"),
+                ("src/bin/helpers.rs", "pub fn three() -> u32 {\n    3\n}\n"),
+            ],
+        );
+        let bin = root.join("src").join("bin").join("gen.rs");
+        let out = root.join("gen.bundle.rs");
+        let bundler = Bundler::for_single_package(&root).unwrap();
+        let bundler = bundler.for_target(&bin, &out);
+        let bundle = bundler
+            .bundle_snippet("use fx::two;\nmod helpers;\nfn main() {\n    println!(\"{}\", two() + helpers::three());\n}\n")
+            .unwrap();
+        assert!(bundle.starts_with("pub fn two()"), "{}", bundle);
+        assert!(
+            bundle.contains("mod helpers {\npub fn three()"),
+            "{}",
+            bundle
+        );
+        assert!(!bin.exists() && !out.exists());
+        assert_builds(&root, &bundle);
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn limits_fail_the_run_when_exceeded() {
+        let root = package(
+            "limits",
+            &[
+                ("Cargo.toml", MANIFEST),
+                ("src/lib.rs", "pub mod a;\n"),
+                ("src/a.rs", "pub mod b;\n"),
+                ("src/a/b.rs", "pub mod c;\n"),
+                ("src/a/b/c.rs", "pub fn f() {}\n"),
+                (
+                    "src/main.rs",
+                    "use fx::a::b::c::f;\nfn main() {\n    f();\n}\n",
+                ),
+            ],
+        );
+        let limit_of = |configure: &dyn Fn(&mut Bundler)| {
+            let mut bundler = Bundler::for_single_package(&root).unwrap();
+            configure(&mut bundler);
+            match bundler.reader() {
+                Err(Error::LimitExceeded { limit, max, .. }) => Some((limit, max)),
+                Err(error) => panic!("{}", error),
+                Ok(_) => None,
+            }
+        };
+        assert_eq!(limit_of(&|bundler| bundler.max_depth(3)), None);
+        assert_eq!(
+            limit_of(&|bundler| bundler.max_depth(2)),
+            Some((Limit::Depth, 2))
+        );
+        assert_eq!(limit_of(&|bundler| bundler.max_files(5)), None);
+        assert_eq!(
+            limit_of(&|bundler| bundler.max_files(4)),
+            Some((Limit::Files, 4))
+        );
+        assert_eq!(
+            limit_of(&|bundler| bundler.max_output_size(20)),
+            Some((Limit::OutputSize, 20))
+        );
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn modules_are_also_found_in_the_extra_source_roots() {
+        let root = package(
+            "source-roots",
+            &[
+                ("app/Cargo.toml", MANIFEST),
+                ("app/src/lib.rs", "pub mod util;\n"),
+                ("app/src/util.rs", "pub mod a;\n"),
+                (
+                    "app/src/main.rs",
+                    "use fx::util::a::one;\nmod geom;\nfn main() {\n    println!(\"{}\", one() + geom::two());\n}\n",
+                ),
+                ("common/src/geom.rs", "pub fn two() -> u32 {\n    2\n}\n"),
+                ("common/src/util/a.rs", "pub fn one() -> u32 {\n    1\n}\n"),
+            ],
+        );
+        let app = root.join("app");
+        let common = root.join("common").join("src");
+        let bundle = bundle_of(&app, |bundler| bundler.add_source_root(&common));
+        assert!(bundle.contains("pub mod a {\npub fn one()"), "{}", bundle);
+        assert!(bundle.contains("mod geom {\npub fn two()"), "{}", bundle);
+        assert_builds(&root, &bundle);
+        let report = report_of(&app, |bundler| bundler.add_source_root(&common));
+        assert!(report.sources.contains(&common.join("geom.rs")));
+        assert_eq!(report.sources.last(), Some(&common));
+        let mut bundler = Bundler::for_single_package(&app).unwrap();
+        assert!(matches!(
+            bundler.reader(),
+            Err(Error::ModuleNotFound { .. })
+        ));
+        bundler.add_source_root(&common);
+        assert!(bundler.reader().is_ok());
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn assets_are_embedded_as_string_constants() {
+        let grammar = "expr = { term ~ (\"+\" ~ term)* }\nterm = @{ ASCII_DIGIT+ }\n";
+        let root = package(
+            "assets",
+            &[
+                ("Cargo.toml", MANIFEST),
+                ("src/lib.rs", "pub mod parser;\n"),
+                ("src/parser.rs", "pub fn rules() -> usize {\n    GRAMMAR.lines().count()\n}\n"),
+                ("src/grammar.pest", grammar),
+                ("src/banner.txt", "hi \"there\"\n"),
+                (
+                    "src/main.rs",
+                    "use fx::parser::rules;\nfn main() {\n    println!(\"{} {}\", rules(), crate::BANNER);\n}\n",
+                ),
+            ],
+        );
+        let grammar_file = root.join("src/grammar.pest");
+        let banner_file = root.join("src/banner.txt");
+        let bundle = bundle_of(&root, |bundler| {
+            bundler.embed_asset(&grammar_file, "parser::GRAMMAR");
+            bundler.embed_asset(&banner_file, "BANNER");
+        });
+        let constant = format!("pub const GRAMMAR: &str = {:?};\n}}\n", grammar);
+        assert!(bundle.contains(&constant), "{}", bundle);
+        assert!(
+            bundle.contains("pub const BANNER: &str = \"hi \\\"there\\\"\\n\";"),
+            "{}",
+            bundle
+        );
+        assert_builds(&root, &bundle);
+        let report = report_of(&root, |bundler| {
+            bundler.embed_asset(&grammar_file, "lexer::G")
+        });
+        let unembedded = Warning::UnembeddedAsset {
+            name: "lexer::G".to_string(),
+        };
+        assert!(
+            report.warnings.contains(&unembedded),
+            "{:?}",
+            report.warnings
+        );
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn modules_declared_in_impl_blocks_are_rejected() {
+        let root = package(
+            "mod-in-impl",
+            &[
+                ("Cargo.toml", MANIFEST),
+                (
+                    "src/lib.rs",
+                    "pub struct S;\nimpl S {\n    mod helpers;\n}\npub fn f() {\n    mod local;\n    local::g();\n}\n",
+                ),
+                ("src/helpers.rs", "pub fn h() {}\n"),
+                ("src/local.rs", "pub fn g() {}\n"),
+                ("src/main.rs", "use fx::f;\nfn main() {\n    f();\n}\n"),
+            ],
+        );
+        let error = Bundler::for_single_package(&root)
+            .unwrap()
+            .reader()
+            .unwrap_err();
+        match error {
+            Error::MisplacedModule {
+                name, item, line, ..
+            } => assert_eq!(
+                (&name[..], &item[..], line),
+                ("helpers", "an impl block", 3)
+            ),
+            error => panic!("{}", error),
+        }
+        fs::write(
+            root.join("src/lib.rs"),
+            "pub fn f() {\n    mod local;\n    local::g();\n}\n",
+        )
+        .unwrap();
+        let bundle = bundle_of(&root, |_| {});
+        assert!(
+            bundle.starts_with(
+                "pub fn f() {\npub mod local {\npub fn g() {}\n}\n    local::g();\n}\n"
+            ),
+            "{}",
+            bundle
+        );
+        assert_builds(&root, &bundle);
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn rustfmt_is_kept_off_the_wrappers_or_the_whole_file() {
+        let root = package(
+            "rustfmt-skip",
+            &[
+                ("Cargo.toml", MANIFEST),
+                ("src/lib.rs", "pub mod graph;\n"),
+                ("src/graph.rs", "pub mod flow;\npub fn bfs() {}\n"),
+                ("src/graph/flow.rs", "pub fn dinic() {}\n"),
+                (
+                    "src/main.rs",
+                    "use fx::graph::bfs;\nfn main() {\n    bfs();\n}\n",
+                ),
+            ],
+        );
+        let bundle = bundle_of(&root, |bundler| bundler.rustfmt_skip(RustfmtSkip::Wrappers));
+        assert!(
+            bundle.starts_with("#[rustfmt::skip]\npub mod graph {\npub mod flow {"),
+            "{}",
+            bundle
+        );
+        assert_eq!(bundle.matches("rustfmt").count(), 1, "{}", bundle);
+        assert_builds(&root, &bundle);
+        let bundle = bundle_of(&root, |bundler| bundler.rustfmt_skip(RustfmtSkip::File));
+        assert!(
+            bundle.starts_with("#![cfg_attr(rustfmt, rustfmt::skip)]\npub mod graph {"),
+            "{}",
+            bundle
+        );
+        assert_builds(&root, &bundle);
+        assert!(!bundle_of(&root, |_| {}).contains("rustfmt"));
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn unused_modules_are_the_ones_no_problem_uses() {
+        let root = package(
+            "unused-modules",
+            &[
+                ("Cargo.toml", MANIFEST),
+                ("src/lib.rs", "pub mod io;\npub mod graph;\npub mod math;\n"),
+                ("src/io.rs", "pub fn read() {}\n"),
+                ("src/graph.rs", "pub mod flow;\npub fn bfs() {}\n"),
+                ("src/graph/flow.rs", "pub fn dinic() {}\n"),
+                ("src/math.rs", "pub mod modint;\npub fn gcd() {}\n"),
+                ("src/math/modint.rs", "pub struct ModInt;\n"),
+                (
+                    "src/bin/a.rs",
+                    "use fx::io::read;\nfn main() {\n    read();\n}\n",
+                ),
+                (
+                    "src/bin/b.rs",
+                    "use fx::graph::bfs;\nfn main() {\n    bfs();\n}\n",
+                ),
+            ],
+        );
+        let contest = judge::Contest::new(&root, "fx");
+        assert_eq!(contest.unused_modules().unwrap(), ["graph::flow", "math"]);
+        assert!(!root.join("target").exists());
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn the_contest_header_has_the_fields_of_the_helpers() {
+        let root = package(
+            "contest-header",
+            &[("Cargo.toml", MANIFEST), ("src/main.rs", "fn main() {}\n")],
+        );
+        // Only this test sets these variables:
+        env::set_var("CONTEST_ID", "abc300");
+        env::set_var("PROBLEM", "abc300_a");
+        env::set_var("ONLINE_JUDGE", "");
+        let bundle = bundle_of(&root, |bundler| {
+            bundler.contest_header_set(true);
+            bundler.header_field("problem", "A");
+            bundler.header_field("author", "me");
+        });
+        assert_eq!(
+            bundle,
+            "// contest: abc300\n// problem: A\n// author: me\nfn main() {}\n"
+        );
+        let bundle = bundle_of(&root, |bundler| bundler.strip_comments_set(true));
+        assert_eq!(bundle, "fn main() {}\n");
+        for (var, _) in HEADER_ENV_VARS {
+            env::remove_var(var);
+        }
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn raw_code_is_inserted_at_its_anchor_in_order() {
+        let root = package(
+            "insert-raw",
+            &[
+                ("Cargo.toml", MANIFEST),
+                (
+                    "src/lib.rs",
+                    "#![allow(dead_code)]\npub fn one() -> u32 {\n    1\n}\n",
+                ),
+                ("src/main.rs", "use fx::one;\nfn main() {\n    one();\n}\n"),
+            ],
+        );
+        let configure = |bundler: &mut Bundler| {
+            bundler.insert_raw(BundleAnchor::FileEnd, "fn end() {}");
+            bundler.insert_raw(BundleAnchor::AfterLib, "fn after() {}\n");
+            bundler.insert_raw(BundleAnchor::BeforeLib, "fn before() {}\n");
+            bundler.insert_raw(BundleAnchor::FileStart, "fn start1() {}\n");
+            bundler.insert_raw(BundleAnchor::FileStart, "fn start2() {}\n");
+        };
+        let bundle = bundle_of(&root, configure);
+        assert_eq!(
+            bundle,
+            "#![allow(dead_code)]\nfn start1() {}\nfn start2() {}\nfn before() {}\npub fn one() -> u32 {\n    1\n}\nfn after() {}\nfn main() {\n    one();\n}\nfn end() {}\n"
+        );
+        assert_builds(&root, &bundle);
+        fs::write(root.join("src/main.rs"), "fn main() {}\n").unwrap();
+        let bundle = bundle_of(&root, configure);
+        assert_eq!(
+            bundle,
+            "fn start1() {}\nfn start2() {}\nfn main() {}\nfn end() {}\n"
+        );
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn features_newer_than_the_judge_are_polyfilled_or_reported() {
+        let root = package(
+            "polyfill",
+            &[
+                ("Cargo.toml", MANIFEST),
+                ("src/lib.rs", "pub fn half(n: u32) -> u32 {\n    n / 2\n}\n"),
+                (
+                    "src/main.rs",
+                    "use fx::half;\nfn main() {\n    let n = half(7u32.div_ceil(2)) * 2;\n    assert!(Some(n).is_some_and(|n| n == 4));\n}\n",
+                ),
+            ],
+        );
+        let polyfill = "trait DivCeil {\n    fn div_ceil(self, d: Self) -> Self;\n}\n";
+        let mut bundler = Bundler::for_single_package(&root).unwrap();
+        bundler.max_rust_version(70);
+        bundler.polyfill("div_ceil", polyfill);
+        let reader = bundler.reader().unwrap();
+        assert!(
+            reader.report().warnings.is_empty(),
+            "{:?}",
+            reader.report().warnings
+        );
+        let bundle = String::from_utf8(reader.into_inner()).unwrap();
+        assert!(bundle.ends_with(&format!("}}\n{}", polyfill)), "{}", bundle);
+        let report = report_of(&root, |bundler| bundler.max_rust_version(69));
+        let newer = |feature: &str, minor| Warning::NewerRustFeature {
+            feature: feature.to_string(),
+            minor,
+            max: 69,
+        };
+        assert_eq!(
+            report.warnings,
+            [newer("is_some_and", 70), newer("div_ceil", 73)]
+        );
+        assert!(report_of(&root, |_| {}).warnings.is_empty());
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn doc_includes_are_stripped_or_inlined() {
+        let root = package(
+            "doc-includes",
+            &[
+                ("Cargo.toml", MANIFEST),
+                ("README.md", "Fast io.\n"),
+                ("src/graph.md", "Graphs.\n"),
+                (
+                    "src/lib.rs",
+                    "#![doc = include_str!(\"../README.md\")]\npub mod graph;\n",
+                ),
+                (
+                    "src/graph.rs",
+                    "#![cfg_attr(docsrs, doc = include_str!(\"graph.md\"))]\n#![doc = include_str!(\n    \"graph.md\"\n)]\npub fn bfs() {}\n",
+                ),
+                (
+                    "src/main.rs",
+                    "//! The solution.\nuse fx::graph::bfs;\nfn main() {\n    bfs();\n}\n",
+                ),
+            ],
+        );
+        let bundle = bundle_of(&root, |_| {});
+        assert!(!bundle.contains("include_str!"), "{}", bundle);
+        assert_builds(&root, &bundle);
+        let bundle = bundle_of(&root, |bundler| bundler.doc_includes(DocIncludes::Inline));
+        assert!(
+            bundle.starts_with("#![doc = \"Fast io.\\n\"]\n"),
+            "{}",
+            bundle
+        );
+        assert!(
+            bundle.contains("pub mod graph {\n#![doc = \"Graphs.\\n\"]\npub fn bfs() {}\n"),
+            "{}",
+            bundle
+        );
+        assert!(!bundle.contains("docsrs"), "{}", bundle);
+        assert_builds(&root, &bundle);
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn appended_files_point_to_the_root_of_the_bundle() {
+        let root = package(
+            "append-file",
+            &[
+                ("Cargo.toml", MANIFEST),
+                ("src/lib.rs", "pub fn add(a: u32, b: u32) -> u32 {\n    a + b\n}\n"),
+                (
+                    "src/main.rs",
+                    "use fx::add;\nfn main() {\n    add(1, 2);\n}\n",
+                ),
+                (
+                    "tests/adds.rs",
+                    "extern crate fx;\nuse fx::add;\n#[test]\nfn adds() {\n    assert_eq!(::fx::add(1, 2), 3);\n}\n",
+                ),
+            ],
+        );
+        let tests = root.join("tests/adds.rs");
+        let bundle = bundle_of(&root, |bundler| bundler.append_file(&tests, Some("tests")));
+        assert!(
+            bundle.ends_with(
+                "}\n#[cfg(test)]\nmod tests {\nuse crate::add;\n#[test]\nfn adds() {\n    assert_eq!(crate::add(1, 2), 3);\n}\n}\n"
+            ),
+            "{}",
+            bundle
+        );
+        assert_builds(&root, &bundle);
+        let bundle = bundle_of(&root, |bundler| bundler.append_file(&tests, None));
+        assert!(
+            bundle.contains("}\nuse crate::add;\n#[test]\nfn adds() {\n")
+                && !bundle.contains("mod tests"),
+            "{}",
+            bundle
+        );
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn bins_are_bundled_with_their_required_features() {
+        let manifest = format!(
+            "{}\n[features]\nfast = []\n\n[[bin]]\nname = \"b\"\nrequired-features = [\"fast\"]\n",
+            MANIFEST
+        );
+        let main = "use fx::solve;\nfn main() {\n    println!(\"{}\", solve());\n}\n";
+        let root = package(
+            "required-features",
+            &[
+                ("Cargo.toml", &manifest),
+                (
+                    "src/lib.rs",
+                    "#[cfg(feature = \"fast\")]\npub fn solve() -> u32 { 2 }\n#[cfg(not(feature = \"fast\"))]\npub fn solve() -> u32 { 1 }\n",
+                ),
+                ("src/bin/a.rs", main),
+                ("src/bin/b.rs", main),
+            ],
+        );
+        let bin = root.join("src").join("bin").join("b.rs");
+        let out = root.join("b.bundle.rs");
+        let bundler = Bundler::for_single_package(&root).unwrap();
+        let bundle = bundler
+            .for_target(&bin, &out)
+            .reader()
+            .unwrap()
+            .into_inner();
+        let bundle = String::from_utf8(bundle).unwrap();
+        assert!(
+            bundle.starts_with("pub fn solve() -> u32 { 2 }\nfn main() {"),
+            "{}",
+            bundle
+        );
+        assert_builds(&root, &bundle);
+        let problems = |contest: &judge::Contest| -> Vec<String> {
+            let artifacts = contest.bundle_all().unwrap();
+            artifacts.into_iter().map(|a| a.problem).collect()
+        };
+        let mut contest = judge::Contest::new(&root, "fx");
+        assert_eq!(problems(&contest), ["a"]);
+        contest.enable_feature("fast");
+        assert_eq!(problems(&contest), ["a", "b"]);
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn lines_are_classified_as_the_bundler_reads_them() {
+        let bundler = Bundler::new(Path::new("src/main.rs"), Path::new("bundle.rs"));
+        let kinds = [
+            ("", LineKind::Blank),
+            ("  // a comment", LineKind::Comment),
+            ("// bundler:insert-lib", LineKind::Directive),
+            (
+                "pub mod graph; // bfs",
+                LineKind::ModDecl {
+                    name: "graph".to_string(),
+                },
+            ),
+            ("use std::io::Read;", LineKind::UseDecl),
+            (
+                "extern crate fx;",
+                LineKind::ExternCrate {
+                    name: "fx".to_string(),
+                },
+            ),
+            ("let x = 1; // mod y;", LineKind::Code),
+        ];
+        for (line, kind) in kinds {
+            assert_eq!(bundler.classify_line(line), kind, "{:?}", line);
+        }
+        assert_eq!(
+            bundler.classify_stmt("use std::{\n    io, // reading\n    fmt,\n};\n"),
+            LineKind::UseDecl
+        );
+        assert_eq!(
+            bundler.classify_stmt("// one\n  // two\n"),
+            LineKind::Comment
+        );
+    }
+
+    #[test]
+    fn modules_with_ignored_files_fail_the_bundle() {
+        let root = package(
+            "bundleignore",
+            &[
+                ("Cargo.toml", MANIFEST),
+                (".bundleignore", "# scratch\ndev_*.rs\n"),
+                ("src/lib.rs", "pub mod io;\nmod dev_io;\n"),
+                ("src/io.rs", "pub fn read() {}\n"),
+                ("src/dev_io.rs", "pub fn peek() {}\n"),
+                ("src/main.rs", "use fx::io;\nfn main() {}\n"),
+            ],
+        );
+        let bundler = Bundler::for_single_package(&root).unwrap();
+        match bundler.reader() {
+            Err(Error::IgnoredModule {
+                name,
+                file,
+                pattern,
+            }) => {
+                assert_eq!((&name[..], &pattern[..]), ("dev_io", "dev_*.rs"));
+                assert_eq!(file, root.join("src").join("dev_io.rs"));
+            }
+            other => panic!("{:?}", other.map(|reader| reader.into_inner())),
+        }
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn only_the_modules_the_entry_file_does_not_use_allow_unused() {
+        let root = package(
+            "allow-unused",
+            &[
+                ("Cargo.toml", MANIFEST),
+                ("src/lib.rs", "pub mod io;\npub mod graph;\npub mod math;\n"),
+                ("src/io.rs", "pub fn read() {}\nfn unused() {}\n"),
+                ("src/graph.rs", "pub mod flow;\npub fn bfs() {}\n"),
+                ("src/graph/flow.rs", "pub fn dinic() {}\n"),
+                ("src/math.rs", "pub mod mint;\npub fn gcd() {}\n"),
+                ("src/math/mint.rs", "pub fn pow() {}\n"),
+                (
+                    "src/main.rs",
+                    "use fx::io::read;\nuse fx::math::mint;\nfn main() {\n    read();\n    mint::pow();\n}\n",
+                ),
+            ],
+        );
+        let bundle = bundle_of(&root, |bundler| bundler.allow_unused_set(true));
+        assert_eq!(bundle.matches("#[allow(unused)]").count(), 1, "{}", bundle);
+        assert!(
+            bundle.contains("#[allow(unused)]\npub mod graph {\npub mod flow {"),
+            "{}",
+            bundle
+        );
+        assert!(bundle.starts_with("pub mod io {"), "{}", bundle);
+        assert!(!bundle_of(&root, |_| {}).contains("allow"));
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn meta_bundles_have_a_module_per_crate() {
+        let root = package(
+            "meta",
+            &[
+                (
+                    "geometry/Cargo.toml",
+                    "[package]\nname = \"geometry\"\nedition = \"2021\"\n",
+                ),
+                (
+                    "geometry/src/lib.rs",
+                    "pub mod point;\n#[macro_export]\nmacro_rules! p {\n    ($x:expr) => {\n        $crate::point::P($x)\n    };\n}\n",
+                ),
+                ("geometry/src/point.rs", "pub struct P(pub i64);\n"),
+                (
+                    "graph/Cargo.toml",
+                    "[package]\nname = \"graph\"\nedition = \"2021\"\n\n[dependencies]\ngeo = { path = \"../geometry\", package = \"geometry\" }\n",
+                ),
+                (
+                    "graph/src/lib.rs",
+                    "use geo::point::P;\npub fn start() -> P {\n    geo::p!(0)\n}\npub fn end() -> P {\n    crate::start()\n}\n",
+                ),
+            ],
+        );
+        let out = root.join("reference.rs");
+        let geometry = root.join("geometry");
+        let graph = root.join("graph");
+        let mut meta = MetaBundler::new(&out);
+        meta.add_crate(&geometry);
+        meta.add_crate(&graph);
+        assert_eq!(meta.run().unwrap().len(), 2);
+        let bundle = fs::read_to_string(&out).unwrap();
+        assert!(
+            bundle.contains("$crate::geometry::point::P($x)"),
+            "{}",
+            bundle
+        );
+        assert!(
+            bundle.contains("pub mod graph {\nuse crate::geometry::point::P;"),
+            "{}",
+            bundle
+        );
+        assert!(bundle.contains("    crate::p!(0)\n"), "{}", bundle);
+        assert!(bundle.contains("    crate::graph::start()\n"), "{}", bundle);
+        assert_builds(
+            &root,
+            &format!("{}fn main() {{\n    graph::end();\n}}\n", bundle),
+        );
+        meta.add_crate(&graph);
+        match meta.run() {
+            Err(Error::DuplicateCrate { name, .. }) => assert_eq!(name, "graph"),
+            other => panic!("{:?}", other),
+        }
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    /// Appends a `// <name>` line to the bundle, or fails if it's empty.
+    struct Mark(&'static str);
+
+    impl PostProcessor for Mark {
+        fn name(&self) -> &str {
+            self.0
+        }
+
+        fn process(&self, bundle: &str, _: &Report) -> Result<String, String> {
+            if self.0.is_empty() {
+                return Err("no name".to_string());
+            }
+            Ok(format!("{}// {}\n", bundle, self.0))
+        }
+    }
+
+    #[test]
+    fn post_processors_run_in_order_before_the_integrity_footer() {
+        let root = package(
+            "post-process",
+            &[("Cargo.toml", MANIFEST), ("src/main.rs", "fn main() {}\n")],
+        );
+        let bundle = bundle_of(&root, |bundler| {
+            bundler.integrity_footer_set(true);
+            bundler.post_process(Mark("one"));
+            bundler.post_process(post::Header {
+                fields: vec![("problem".to_string(), "A".to_string())],
+            });
+            bundler.post_process(Mark("two"));
+        });
+        assert!(
+            bundle.starts_with("// problem: A\nfn main() {}\n// one\n// two\n"),
+            "{}",
+            bundle
+        );
+        assert!(matches!(
+            integrity::check(bundle.as_bytes()),
+            Integrity::Intact { .. }
+        ));
+        let mut bundler = Bundler::for_single_package(&root).unwrap();
+        bundler.post_process(Mark(""));
+        match bundler.reader() {
+            Err(Error::PostProcess { name, message }) => {
+                assert_eq!((&name[..], &message[..]), ("", "no name"))
+            }
+            other => panic!("{:?}", other.map(|reader| reader.into_inner())),
+        }
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn explicit_globs_import_only_the_names_the_entry_file_uses() {
+        let root = package(
+            "explicit-globs",
+            &[
+                ("Cargo.toml", MANIFEST),
+                ("src/lib.rs", "pub mod graph;\n"),
+                (
+                    "src/graph.rs",
+                    "pub fn bfs() {}\npub fn dfs() {}\npub trait Walk {\n    fn walk(&self) {}\n}\nimpl Walk for u32 {}\n",
+                ),
+                (
+                    "src/main.rs",
+                    "use fx::graph::*;\nfn main() {\n    bfs();\n    3u32.walk();\n}\n",
+                ),
+            ],
+        );
+        let bundle = bundle_of(&root, |bundler| bundler.explicit_globs_set(true));
+        assert!(
+            bundle.contains("}\nuse crate::graph::{Walk, bfs};\nfn main() {"),
+            "{}",
+            bundle
+        );
+        assert_builds(&root, &bundle);
+        let bundle = bundle_of(&root, |_| {});
+        assert!(bundle.contains("use crate::graph::*;"), "{}", bundle);
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn items_with_the_same_name_in_different_modules_are_not_renamed() {
+        let root = package(
+            "same-names",
+            &[
+                ("Cargo.toml", MANIFEST),
+                ("src/lib.rs", "pub mod dp;\npub mod graph;\n"),
+                ("src/dp.rs", "pub fn solve() -> u32 {\n    1\n}\n"),
+                ("src/graph.rs", "pub fn solve() -> u32 {\n    2\n}\n"),
+                (
+                    "src/main.rs",
+                    "use fx::dp;\nuse fx::graph;\nfn main() {\n    assert_eq!(dp::solve() + graph::solve(), 3);\n}\n",
+                ),
+            ],
+        );
+        let bundle = bundle_of(&root, |_| {});
+        assert_eq!(bundle.matches("pub fn solve() -> u32 {").count(), 2);
+        assert!(
+            bundle.contains("pub mod dp {\npub fn solve()"),
+            "{}",
+            bundle
+        );
+        assert!(
+            bundle.contains("pub mod graph {\npub fn solve()"),
+            "{}",
+            bundle
+        );
+        assert_builds(&root, &bundle);
+        fs::remove_dir_all(&root).unwrap();
     }
 }
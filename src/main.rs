@@ -0,0 +1,234 @@
+/*!
+Command line interface to the bundler, for use outside of build.rs.
+*/
+
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::path::PathBuf;
+use std::process;
+
+extern crate rustsourcebundler;
+use rustsourcebundler::json;
+use rustsourcebundler::Bundler;
+
+const USAGE: &str = "\
+usage: rustsourcebundler [options] <bin.rs> [<bundle.rs>]
+       rustsourcebundler --diff [--format <format>] <old bundle> <new bundle>
+
+The bundle is written to target/sourcebundle/<bin name>.rs by default.
+
+options:
+    --crate-name <name>   name of the crate whose library is inlined
+    --lib <lib.rs>        library root, default: src/lib.rs
+    --exclude-mod <path>  don't bundle the module, like graph::matching, can be
+                          repeated
+    --allow <lint>        allow the lint in the library modules, can be repeated
+    --minify              minify the bundle, same as --minify-level 1
+    --minify-level <n>    minify the bundle more, from 0 to 3
+    --emit <what>         bundle, the default, or both to also write a
+                          minified copy
+    --min-suffix <suffix> suffix of the minified copy, default: .min.rs
+    --keep-comments       don't strip comments
+    --dry-run             print what would be done, without writing anything
+    --check               fail on imports of modules that are not bundled,
+                          include! and env! left in the bundle, without
+                          writing anything
+    --format <format>     text, the default, or json to print the plan and
+                          the report as JSON on stdout
+    --tree <format>       print the tree of the modules with their sizes, as
+                          text or as a dot graph, without writing anything
+    --diff                print the items that differ between two bundles,
+                          ignoring whitespace and comments
+    --help                print this message
+";
+
+fn usage_error(message: &str) -> ! {
+    eprintln!("error: {}\n\n{}", message, USAGE);
+    process::exit(2);
+}
+
+fn fail(error: &rustsourcebundler::Error, json: bool) -> ! {
+    if json {
+        let fields = [
+            ("ok", "false".to_string()),
+            ("error", json::string(&error.to_string())),
+        ];
+        println!("{}", json::object(&fields));
+    } else {
+        eprint!("{}", error.diagnostic());
+    }
+    process::exit(1);
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let mut crate_name = None;
+    let mut librs = None;
+    let mut exclude_mods = Vec::new();
+    let mut allow_lints = Vec::new();
+    let mut minify_level = 0;
+    let mut emit_both = false;
+    let mut min_suffix = ".min.rs";
+    let mut keep_comments = false;
+    let mut dry_run = false;
+    let mut check = false;
+    let mut json = false;
+    let mut diff = false;
+    let mut tree = None;
+    let mut positional = Vec::new();
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        let mut value = || {
+            iter.next()
+                .unwrap_or_else(|| usage_error(&format!("{} requires a value", arg)))
+        };
+        match arg.as_str() {
+            "--crate-name" => crate_name = Some(value()),
+            "--lib" => librs = Some(value()),
+            "--exclude-mod" => exclude_mods.push(value()),
+            "--allow" => allow_lints.push(value()),
+            "--minify" => minify_level = 1,
+            "--minify-level" => {
+                minify_level = value()
+                    .parse()
+                    .unwrap_or_else(|_| usage_error("--minify-level requires a number"))
+            }
+            "--emit" => match value().as_str() {
+                "bundle" => emit_both = false,
+                "both" => emit_both = true,
+                other => usage_error(&format!("unknown --emit value {}", other)),
+            },
+            "--min-suffix" => min_suffix = value(),
+            "--keep-comments" => keep_comments = true,
+            "--dry-run" => dry_run = true,
+            "--check" => check = true,
+            "--diff" => diff = true,
+            "--format" => match value().as_str() {
+                "text" => json = false,
+                "json" => json = true,
+                other => usage_error(&format!("unknown --format value {}", other)),
+            },
+            "--tree" => match value().as_str() {
+                format @ ("text" | "dot") => tree = Some(format),
+                other => usage_error(&format!("unknown --tree value {}", other)),
+            },
+            "--help" | "-h" => {
+                print!("{}", USAGE);
+                return;
+            }
+            _ if arg.starts_with("--") => usage_error(&format!("unknown option {}", arg)),
+            _ => positional.push(arg),
+        }
+    }
+    if diff {
+        if positional.len() != 2 {
+            usage_error("expected the old and the new bundle files");
+        }
+        let read = |path: &str| {
+            fs::read(path)
+                .map(|contents| String::from_utf8_lossy(&contents).into_owned())
+                .unwrap_or_else(|error| {
+                    let error = rustsourcebundler::Error::Io {
+                        path: PathBuf::from(path),
+                        error,
+                    };
+                    fail(&error, json)
+                })
+        };
+        let diff = rustsourcebundler::diff_bundles(&read(positional[0]), &read(positional[1]));
+        if json {
+            let fields = [("ok", "true".to_string()), ("diff", diff.to_json())];
+            println!("{}", json::object(&fields));
+        } else {
+            print!("{}", diff);
+        }
+        return;
+    }
+    if positional.is_empty() || positional.len() > 2 {
+        usage_error("expected the entry file and the bundle file");
+    }
+    let crate_name = crate_name.unwrap_or_else(|| usage_error("--crate-name is required"));
+    let bundle = positional.get(1).map_or("", |bundle| bundle.as_str());
+    let mut bundler = match librs {
+        Some(librs) => Bundler::new_with_librs(
+            Path::new(positional[0]),
+            Path::new(bundle),
+            Path::new(librs),
+        ),
+        None => Bundler::new(Path::new(positional[0]), Path::new(bundle)),
+    };
+    if bundle.is_empty() {
+        bundler.default_bundle_file();
+    }
+    bundler.crate_name(crate_name);
+    for exclude_mod in exclude_mods {
+        bundler.exclude_mod(exclude_mod);
+    }
+    for lint in allow_lints {
+        bundler.allow_lint(lint);
+    }
+    bundler.minify_level(minify_level);
+    bundler.strip_comments_set(!keep_comments);
+    if emit_both {
+        bundler.emit_minified(min_suffix);
+    }
+    if let Some(format) = tree {
+        let plan = bundler.plan().unwrap_or_else(|error| fail(&error, json));
+        match format {
+            "dot" => print!("{}", plan.to_dot()),
+            _ => print!("{}", plan.to_tree()),
+        }
+        return;
+    }
+    if check {
+        match bundler.check_only() {
+            Ok(report) if json => {
+                let fields = [("ok", "true".to_string()), ("report", report.to_json())];
+                println!("{}", json::object(&fields));
+            }
+            Ok(report) => {
+                for warning in &report.warnings {
+                    eprintln!("warning: {}", warning);
+                }
+            }
+            Err(error) => fail(&error, json),
+        }
+        return;
+    }
+    if json {
+        let mut fields = vec![("ok", "true".to_string())];
+        if dry_run {
+            let plan = bundler.plan().unwrap_or_else(|error| fail(&error, json));
+            fields.push(("plan", plan.to_json()));
+        } else {
+            let (plan, report) = bundler
+                .run_with_plan()
+                .unwrap_or_else(|error| fail(&error, json));
+            fields.push(("plan", plan.to_json()));
+            let bundle = bundler.bundle_filename();
+            fields.push(("bundle", json::path(bundle)));
+            if emit_both {
+                let stem = bundle.file_stem().unwrap_or_default().to_string_lossy();
+                let minified = bundle.with_file_name(format!("{}{}", stem, min_suffix));
+                fields.push(("minified", json::path(&minified)));
+            }
+            fields.push(("report", report.to_json()));
+        }
+        println!("{}", json::object(&fields));
+    } else if dry_run {
+        match bundler.plan() {
+            Ok(plan) => print!("{}", plan),
+            Err(error) => fail(&error, json),
+        }
+    } else {
+        match bundler.run() {
+            Ok(report) => {
+                for warning in &report.warnings {
+                    eprintln!("warning: {}", warning);
+                }
+            }
+            Err(error) => fail(&error, json),
+        }
+    }
+}
@@ -0,0 +1,158 @@
+/*!
+Minimal Cargo.toml reader, just enough to get the few keys the bundler
+cares about without depending on a full toml parser.
+*/
+
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// The `[section]`s of a manifest, in order, with their `key = value`
+/// entries.
+#[derive(Debug, Clone, Default)]
+pub struct Manifest {
+    sections: Vec<(String, Vec<(String, String)>)>,
+}
+
+/// Removes a `#` comment that is not inside a string.
+fn strip_comment(line: &str) -> &str {
+    let mut in_string = false;
+    for (i, c) in line.char_indices() {
+        match c {
+            '"' => in_string = !in_string,
+            '#' if !in_string => return &line[..i],
+            _ => {}
+        }
+    }
+    line
+}
+
+/// Removes the quotes of a string value, other values are returned as-is.
+fn unquote(value: &str) -> &str {
+    let value = value.trim();
+    if value.len() >= 2
+        && ((value.starts_with('"') && value.ends_with('"'))
+            || (value.starts_with('\'') && value.ends_with('\'')))
+    {
+        &value[1..value.len() - 1]
+    } else {
+        value
+    }
+}
+
+/// Value of `key` in an inline table like `{ path = ".", package = "x" }`.
+fn inline_get<'m>(table: &'m str, key: &str) -> Option<&'m str> {
+    let table = table.trim().strip_prefix('{')?.strip_suffix('}')?;
+    table
+        .split(',')
+        .filter_map(|entry| entry.split_once('='))
+        .find(|(k, _)| k.trim() == key)
+        .map(|(_, v)| unquote(v))
+}
+
+impl Manifest {
+    pub fn read(path: &Path) -> Result<Manifest, io::Error> {
+        Ok(Manifest::parse(&fs::read_to_string(path)?))
+    }
+
+    pub fn parse(contents: &str) -> Manifest {
+        let mut sections = vec![(String::new(), Vec::new())];
+        for line in contents.lines() {
+            let line = strip_comment(line).trim();
+            if line.starts_with('[') && line.ends_with(']') {
+                let name = line.trim_matches(|c| c == '[' || c == ']').trim();
+                sections.push((name.to_string(), Vec::new()));
+            } else if let Some((key, value)) = line.split_once('=') {
+                let entries = &mut sections.last_mut().unwrap().1;
+                entries.push((key.trim().to_string(), unquote(value).to_string()));
+            }
+        }
+        Manifest { sections }
+    }
+
+    /// Value of `key` in the first `[section]` with that name.
+    pub fn get(&self, section: &str, key: &str) -> Option<&str> {
+        self.sections
+            .iter()
+            .filter(|(name, _)| name == section)
+            .flat_map(|(_, entries)| entries.iter())
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Features that the bin target `bin` needs, from the
+    /// `required-features` of its `[[bin]]` table, written on one line.
+    pub fn required_features(&self, bin: &str) -> Vec<String> {
+        let features = self
+            .sections
+            .iter()
+            .filter(|(section, _)| section == "bin")
+            .map(|(_, entries)| entries)
+            .find(|entries| entries.iter().any(|(k, v)| k == "name" && v == bin))
+            .and_then(|entries| entries.iter().find(|(k, _)| k == "required-features"));
+        let features = match features {
+            Some((_, features)) => features.trim(),
+            None => return Vec::new(),
+        };
+        features
+            .trim_start_matches('[')
+            .trim_end_matches(']')
+            .split(',')
+            .map(unquote)
+            .filter(|feature| !feature.is_empty())
+            .map(str::to_string)
+            .collect()
+    }
+
+    /// Names the library of the package can be imported with: the
+    /// name of the `[lib]` or of the package, and the keys of the
+    /// dependencies on the package itself, like `algo` for
+    /// `algo = { path = ".", package = "my-algo" }`.
+    pub fn lib_names(&self) -> Vec<String> {
+        let package = self.get("package", "name");
+        let mut names: Vec<String> = self
+            .get("lib", "name")
+            .into_iter()
+            .chain(package)
+            .map(|name| name.replace('-', "_"))
+            .collect();
+        let is_self = |path: Option<&str>, renamed: Option<&str>| {
+            path.is_some_and(|path| path.trim_end_matches('/') == ".")
+                || (renamed.is_some() && renamed == package)
+        };
+        for (section, entries) in &self.sections {
+            if section == "dependencies" {
+                for (key, value) in entries {
+                    if is_self(inline_get(value, "path"), inline_get(value, "package")) {
+                        names.push(key.replace('-', "_"));
+                    }
+                }
+            } else if let Some(key) = section.strip_prefix("dependencies.") {
+                if is_self(self.get(section, "path"), self.get(section, "package")) {
+                    names.push(key.trim().replace('-', "_"));
+                }
+            }
+        }
+        names
+    }
+
+    /// The dependencies with a `path`, as the name they are imported
+    /// with and the path, relative to the package.
+    pub fn path_dependencies(&self) -> Vec<(String, String)> {
+        let mut dependencies = Vec::new();
+        for (section, entries) in &self.sections {
+            if section == "dependencies" {
+                for (key, value) in entries {
+                    if let Some(path) = inline_get(value, "path") {
+                        dependencies.push((key.replace('-', "_"), path.to_string()));
+                    }
+                }
+            } else if let Some(key) = section.strip_prefix("dependencies.") {
+                if let Some(path) = self.get(section, "path") {
+                    dependencies.push((key.trim().replace('-', "_"), path.to_string()));
+                }
+            }
+        }
+        dependencies
+    }
+}
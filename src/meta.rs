@@ -0,0 +1,229 @@
+/*!
+One file with several local crates, each as a top-level module named
+after its library, like a workspace of the algorithms of a team
+reference document:
+
+```no_run
+use std::path::Path;
+use rustsourcebundler::MetaBundler;
+
+let mut meta = MetaBundler::new(Path::new("reference.rs"));
+meta.add_crate(Path::new("graph"));
+meta.add_crate(Path::new("geometry"));
+meta.configure(|bundler| bundler.strip_comments_set(false));
+meta.run().unwrap();
+```
+
+Each crate is bundled as with [`super::Bundler::library_only_set`],
+then its paths are rewritten: `crate::` and `$crate::` go through its
+module, and the paths to the other crates, by their name or the name of
+a `path` dependency on them, start with `crate::`, except for the
+macros with `#[macro_export]`, which are at the root of the bundle.
+Paths are rewritten as in the 2018 edition and later.
+*/
+
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+use std::path::PathBuf;
+
+use regex::Captures;
+use regex::Regex;
+
+use super::lexer::Lexer;
+use super::manifest::Manifest;
+use super::source_line_regex;
+use super::Bundler;
+use super::Error;
+use super::Report;
+
+type Configure = Box<dyn Fn(&mut Bundler)>;
+
+/// Bundler of several local crates into one file.
+pub struct MetaBundler<'a> {
+    bundle_filename: &'a Path,
+    roots: Vec<&'a Path>,
+    configure: Option<Configure>,
+}
+
+/// A crate to bundle, with the module it goes in.
+struct Member {
+    root: PathBuf,
+    module: String,
+    manifest: Manifest,
+}
+
+impl<'a> MetaBundler<'a> {
+    pub fn new(bundle_filename: &'a Path) -> MetaBundler<'a> {
+        MetaBundler {
+            bundle_filename,
+            roots: Vec::new(),
+            configure: None,
+        }
+    }
+
+    /// Bundle the library of the package at `root`, in the order the
+    /// crates are added.
+    pub fn add_crate(&mut self, root: &'a Path) {
+        self.roots.push(root);
+    }
+
+    /// Function called to set up the bundler of each crate.
+    pub fn configure<F: Fn(&mut Bundler) + 'static>(&mut self, configure: F) {
+        self.configure = Some(Box::new(configure));
+    }
+
+    fn members(&self) -> Result<Vec<Member>, Error> {
+        let mut members: Vec<Member> = Vec::new();
+        for root in &self.roots {
+            let manifest_filename = root.join("Cargo.toml");
+            let manifest = Manifest::read(&manifest_filename).map_err(|error| Error::Io {
+                path: manifest_filename,
+                error,
+            })?;
+            let module = manifest.lib_names().into_iter().next().unwrap_or_else(|| {
+                let name = root.canonicalize().unwrap_or(root.to_path_buf());
+                let name = name.file_name().unwrap_or_default().to_string_lossy();
+                name.replace('-', "_")
+            });
+            if members.iter().any(|member| member.module == module) {
+                return Err(Error::DuplicateCrate {
+                    name: module,
+                    root: root.to_path_buf(),
+                });
+            }
+            members.push(Member {
+                root: root.canonicalize().unwrap_or(root.to_path_buf()),
+                module,
+                manifest,
+            });
+        }
+        Ok(members)
+    }
+
+    /// Names `member` imports the crates with, with their modules.
+    fn crate_names<'m>(member: &'m Member, members: &'m [Member]) -> Vec<(String, &'m str)> {
+        let mut names: Vec<(String, &str)> = members
+            .iter()
+            .map(|other| (other.module.clone(), &*other.module))
+            .collect();
+        for name in member.manifest.lib_names() {
+            names.push((name, &member.module));
+        }
+        for (name, path) in member.manifest.path_dependencies() {
+            let path = member.root.join(path);
+            let path = path.canonicalize().unwrap_or(path);
+            if let Some(other) = members.iter().find(|other| other.root == path) {
+                names.push((name, &other.module));
+            }
+        }
+        names.sort();
+        names.dedup();
+        names
+    }
+
+    /// Bundle all crates and write the bundle, returning the report of
+    /// each crate.
+    pub fn run(&self) -> Result<Vec<Report>, Error> {
+        let members = self.members()?;
+        let mut codes = Vec::new();
+        let mut reports = Vec::new();
+        for member in &members {
+            let mut bundler = Bundler::for_single_package(&member.root)?;
+            bundler.library_only_set(true);
+            if let Some(ref configure) = self.configure {
+                configure(&mut bundler);
+            }
+            let reader = bundler.reader()?;
+            reports.push(reader.report().clone());
+            codes.push(String::from_utf8_lossy(&reader.into_inner()).into_owned());
+        }
+        let exported_re =
+            Regex::new(r"#\[macro_export\]\s*(?:#\[[^\]]*\]\s*)*macro_rules!\s*(?P<name>\w+)")
+                .unwrap();
+        let exported: HashSet<&str> = codes
+            .iter()
+            .flat_map(|code| exported_re.captures_iter(code))
+            .map(|cap| cap.name("name").unwrap().as_str())
+            .collect();
+        let mut bundle = String::new();
+        for (member, code) in members.iter().zip(&codes) {
+            let names = MetaBundler::crate_names(member, &members);
+            bundle.push_str(&format!("pub mod {} {{\n", member.module));
+            bundle.push_str(&rewrite_paths(code, &member.module, &names, &exported));
+            if !bundle.ends_with('\n') {
+                bundle.push('\n');
+            }
+            bundle.push_str("}\n");
+        }
+        if let Some(dir) = self
+            .bundle_filename
+            .parent()
+            .filter(|dir| !dir.as_os_str().is_empty())
+        {
+            fs::create_dir_all(dir).map_err(|error| Error::Io {
+                path: dir.to_path_buf(),
+                error,
+            })?;
+        }
+        fs::write(self.bundle_filename, bundle).map_err(|error| Error::Io {
+            path: self.bundle_filename.to_path_buf(),
+            error,
+        })?;
+        Ok(reports)
+    }
+}
+
+/// `code` of the crate bundled in `module`, with its paths going
+/// through the modules of the crates, imported with `names`, and
+/// without the `extern crate` of them. The `exported` macros are at
+/// the root of the bundle, so their paths don't go through a module.
+fn rewrite_paths(
+    code: &str,
+    module: &str,
+    names: &[(String, &str)],
+    exported: &HashSet<&str>,
+) -> String {
+    let alternation: Vec<String> = names.iter().map(|(name, _)| regex::escape(name)).collect();
+    let alternation = alternation.join("|");
+    let extcrate_re = source_line_regex(format!(r" extern  crate  (?:{}) ; ", alternation));
+    let path_re = Regex::new(&format!(
+        r"(?P<pre>^|[^\w:$])(?:(?P<root>\$?crate)|(?:::\s*)?(?P<name>{}))\s*::(?P<macro>(?P<macro_name>\w+)\s*!)?",
+        alternation
+    ))
+    .unwrap();
+    let replace = |cap: &Captures| {
+        let root = cap.name("root").map_or("crate", |root| root.as_str());
+        let target = match cap.name("name") {
+            Some(name) => names
+                .iter()
+                .find(|(n, _)| n == name.as_str())
+                .map_or(module, |&(_, target)| target),
+            None => module,
+        };
+        let mac = cap.name("macro").map_or("", |mac| mac.as_str());
+        match cap.name("macro_name") {
+            Some(name) if exported.contains(name.as_str()) => {
+                format!("{}{}::{}", &cap["pre"], root, mac)
+            }
+            _ => format!("{}{}::{}::{}", &cap["pre"], root, target, mac),
+        }
+    };
+    let mut rewritten = String::new();
+    let mut lexer = Lexer::new();
+    for line in code.split_inclusive('\n') {
+        let (scan, ranges) = lexer.scan_code(line);
+        if scan.starts_in_code() && extcrate_re.is_match(line.trim_end()) {
+            continue;
+        }
+        let mut last = 0;
+        for range in ranges {
+            let part = path_re.replace_all(&line[range.clone()], &replace);
+            rewritten.push_str(&line[last..range.start]);
+            rewritten.push_str(&part);
+            last = range.end;
+        }
+        rewritten.push_str(&line[last..]);
+    }
+    rewritten
+}
@@ -0,0 +1,498 @@
+/*!
+Minification of the bundle, applied after it's been assembled.
+
+Level 1 trims the whitespace around the lines of code, level 2 also
+removes blank lines and moves lines that only close delimiters to the
+end of the previous one, and level 3 removes the whitespace between
+tokens and joins all the lines of code it can. Outer attributes stay
+before their items when lines are joined, like `#[inline]fn f(){}`.
+*/
+
+use std::ops::Range;
+
+use regex::Regex;
+
+use super::lexer::Lexer;
+use super::split_use_group;
+
+/// Highest minification level.
+pub const MAX_LEVEL: u8 = 3;
+
+fn is_ident_char(c: u8) -> bool {
+    c.is_ascii_alphanumeric() || c == b'_' || c >= 0x80
+}
+
+/// Delimiters and separators, that never join other characters into
+/// a different token.
+fn is_delimiter(c: u8) -> bool {
+    b"(){}[],;".contains(&c)
+}
+
+/// Whether the whitespace between `prev` and `next` has to be kept as
+/// a single space. `*_code` tell if the char is code, as opposed to
+/// the end or start of a literal or comment.
+fn needs_space(prev: Option<u8>, prev_code: bool, next: Option<u8>, next_code: bool) -> bool {
+    let (prev, next) = match (prev, next) {
+        (Some(prev), Some(next)) => (prev, next),
+        _ => return false,
+    };
+    if is_delimiter(prev) || is_delimiter(next) {
+        false
+    } else if is_ident_char(prev) && next == b'\'' {
+        // Labels and lifetimes, like `break 'outer`, which would be an
+        // unknown prefix `break'` from the 2021 edition on.
+        true
+    } else if is_ident_char(prev) == is_ident_char(next) {
+        // Identifiers would merge, and so could operators like `- >`.
+        true
+    } else {
+        // Literal suffixes and prefixes, like `"a" as` or `return "a"`,
+        // and tuple indexes like `1 .0`.
+        (!prev_code && is_ident_char(next))
+            || (!next_code && is_ident_char(prev))
+            || (prev.is_ascii_digit() && next == b'.')
+    }
+}
+
+/// Removes the whitespace between the tokens in the `code` ranges of
+/// `line`.
+fn squeeze(line: &str, code: &[Range<usize>]) -> String {
+    let bytes = line.as_bytes();
+    let mut out = String::with_capacity(line.len());
+    let mut last = 0;
+    for range in code {
+        out.push_str(&line[last..range.start]);
+        let mut i = range.start;
+        while i < range.end {
+            let mut j = i;
+            if bytes[i].is_ascii_whitespace() {
+                while j < range.end && bytes[j].is_ascii_whitespace() {
+                    j += 1;
+                }
+                let prev = out.as_bytes().last().copied();
+                let next = bytes.get(j).copied();
+                if needs_space(prev, i > range.start, next, j < range.end) {
+                    out.push(' ');
+                }
+            } else {
+                while j < range.end && !bytes[j].is_ascii_whitespace() {
+                    j += 1;
+                }
+                out.push_str(&line[i..j]);
+            }
+            i = j;
+        }
+        last = range.end;
+    }
+    out.push_str(&line[last..]);
+    out
+}
+
+/// Whether the line only closes delimiters, like `}` or `});`.
+fn closes_only(line: &str) -> bool {
+    !line.is_empty() && line.bytes().all(|c| b")]};,".contains(&c))
+}
+
+/// Minify `bundle` at the given level, 0 leaving it untouched.
+pub fn minify(bundle: &str, level: u8) -> String {
+    if level == 0 {
+        return bundle.to_string();
+    }
+    let mut lexer = Lexer::new();
+    let mut out = String::with_capacity(bundle.len());
+    // Whether more code can be appended to the last line of `out`:
+    let mut joinable = false;
+    for line in bundle.split_inclusive('\n') {
+        let line = line.strip_suffix('\n').unwrap_or(line);
+        let (scan, code) = lexer.scan_code(line);
+        if !scan.starts_in_code() {
+            out.push_str(line);
+            out.push('\n');
+            joinable = false;
+            continue;
+        }
+        let mut line = if level >= 3 {
+            squeeze(line, &code)
+        } else {
+            line.to_string()
+        };
+        if scan.ends_in_code() {
+            line.truncate(line.trim_end().len());
+        }
+        let line = line.trim_start();
+        if level >= 2 && line.is_empty() {
+            continue;
+        }
+        let join = joinable && (level >= 3 || (level >= 2 && closes_only(line)));
+        if join {
+            out.pop();
+            let prev = out.as_bytes().last().copied();
+            if needs_space(prev, false, line.bytes().next(), false) {
+                out.push(' ');
+            }
+        }
+        out.push_str(line);
+        out.push('\n');
+        joinable = scan.ends_in_code() && scan.comment.is_none();
+    }
+    out
+}
+
+/// Name of the outer attribute starting at the `#` at `start` of
+/// `text`, like `inline` or `rustfmt::skip`, and the offset after its
+/// `[`.
+fn attribute_name(text: &str, start: usize) -> Option<(&str, usize)> {
+    let rest = text[start + 1..].trim_start();
+    let rest = rest.strip_prefix('[')?;
+    let open = text.len() - rest.len();
+    let rest = rest.trim_start();
+    let len = rest
+        .find(|c: char| !(c.is_alphanumeric() || c == '_' || c == ':'))
+        .unwrap_or(rest.len());
+    Some((&rest[..len], open))
+}
+
+/// Removes the outer attributes of `bundle` with one of the given
+/// `names`, like `inline` for `#[inline]` and `#[inline(always)]`, or
+/// `doc` for `#[doc = "..."]`. Lines left empty are removed too.
+pub fn drop_attributes(bundle: &str, names: &[&str]) -> String {
+    let code = code_ranges(bundle);
+    let bytes = bundle.as_bytes();
+    let mut out = String::with_capacity(bundle.len());
+    let mut last = 0;
+    // Start of the attribute being removed, offset after its `[`, and
+    // depth of its brackets:
+    let mut removing: Option<(usize, usize, i32)> = None;
+    for range in code {
+        for i in range {
+            if let Some((start, open, depth)) = removing {
+                let depth = match bytes[i] {
+                    _ if i < open => depth,
+                    b'[' => depth + 1,
+                    b']' => depth - 1,
+                    _ => depth,
+                };
+                if depth > 0 {
+                    removing = Some((start, open, depth));
+                    continue;
+                }
+                removing = None;
+                cut(bundle, &mut out, &mut last, start..i + 1);
+            } else if bytes[i] == b'#' && i >= last {
+                if let Some((name, open)) = attribute_name(bundle, i) {
+                    if names.contains(&name) {
+                        removing = Some((i, open, 1));
+                    }
+                }
+            }
+        }
+    }
+    out.push_str(&bundle[last..]);
+    out
+}
+
+/// Removes the traits with one of the given `names`, like `Serialize`,
+/// from the `#[derive(...)]` attributes of `bundle`, also when they are
+/// written with their path, like `serde::Serialize`. Attributes left
+/// with no traits are removed, with their line if they were all of it,
+/// and so are the `use` declarations that only import the traits.
+pub fn strip_derives(bundle: &str, names: &[&str]) -> String {
+    let item_re = Regex::new(
+        r"#\s*\[\s*derive\s*\((?P<traits>[^()\[\]]*)\)\s*\]|\b(pub(\s*\([^)]*\))?\s+)?use\s+(?P<use>[^;]*);",
+    )
+    .unwrap();
+    let stripped = |path: &str| {
+        let path = path.split(" as ").next().unwrap_or(path);
+        names.contains(&path.rsplit("::").next().unwrap_or(path).trim())
+    };
+    let code = code_ranges(bundle);
+    let mut out = String::with_capacity(bundle.len());
+    let mut last = 0;
+    for cap in item_re.captures_iter(bundle) {
+        let item = cap.get(0).unwrap();
+        if item.start() < last || !code.iter().any(|range| range.contains(&item.start())) {
+            continue;
+        }
+        if let Some(path) = cap.name("use") {
+            if split_use_group(path.as_str()).iter().all(|p| stripped(p)) {
+                cut(bundle, &mut out, &mut last, item.range());
+            }
+            continue;
+        }
+        let traits: Vec<&str> = cap["traits"]
+            .split(',')
+            .map(str::trim)
+            .filter(|t| !t.is_empty())
+            .collect();
+        let kept: Vec<&str> = traits.iter().copied().filter(|t| !stripped(t)).collect();
+        if kept.len() == traits.len() {
+            continue;
+        }
+        if kept.is_empty() {
+            cut(bundle, &mut out, &mut last, item.range());
+        } else {
+            out.push_str(&bundle[last..item.start()]);
+            out.push_str(&format!("#[derive({})]", kept.join(", ")));
+            last = item.end();
+        }
+    }
+    out.push_str(&bundle[last..]);
+    out
+}
+
+/// Byte ranges of the code of `bundle`, without literals and comments.
+fn code_ranges(bundle: &str) -> Vec<Range<usize>> {
+    let mut code = Vec::new();
+    let mut lexer = Lexer::new();
+    let mut offset = 0;
+    for line in bundle.split_inclusive('\n') {
+        let (_, ranges) = lexer.scan_code(line);
+        code.extend(ranges.into_iter().map(|r| r.start + offset..r.end + offset));
+        offset += line.len();
+    }
+    code
+}
+
+/// Copies `bundle` to `out` from `last` to the start of `removed`, and
+/// moves `last` after it, also removing the line if `removed` was all
+/// of it.
+fn cut(bundle: &str, out: &mut String, last: &mut usize, removed: Range<usize>) {
+    let line_start = bundle[..removed.start].rfind('\n').map_or(0, |n| n + 1);
+    let line_end = bundle[removed.end..]
+        .find('\n')
+        .map_or(bundle.len(), |n| removed.end + n);
+    let alone = bundle[line_start..removed.start].trim().is_empty()
+        && bundle[removed.end..line_end].trim().is_empty();
+    if alone {
+        out.push_str(&bundle[*last..line_start]);
+        *last = (line_end + 1).min(bundle.len());
+    } else {
+        out.push_str(&bundle[*last..removed.start]);
+        let rest = &bundle[removed.end..];
+        *last = bundle.len() - rest.trim_start_matches([' ', '\t']).len();
+    }
+}
+
+/// Remove the whitespace at the start and/or at the end of the lines
+/// of code of `bundle`.
+pub fn trim(bundle: &str, leading: bool, trailing: bool) -> String {
+    let mut lexer = Lexer::new();
+    let mut out = String::with_capacity(bundle.len());
+    for line in bundle.split_inclusive('\n') {
+        let newline = line.ends_with('\n');
+        let mut line = line.strip_suffix('\n').unwrap_or(line);
+        let scan = lexer.scan(line);
+        if leading && scan.starts_in_code() {
+            line = line.trim_start();
+        }
+        if trailing && scan.ends_in_code() {
+            line = line.trim_end();
+        }
+        out.push_str(line);
+        if newline {
+            out.push('\n');
+        }
+    }
+    out
+}
+
+/// Limit runs of blank lines of code in `bundle` to `max` lines.
+pub fn collapse_blank_lines(bundle: &str, max: usize) -> String {
+    let mut lexer = Lexer::new();
+    let mut out = String::with_capacity(bundle.len());
+    let mut blanks = 0;
+    for line in bundle.split_inclusive('\n') {
+        let scan = lexer.scan(line);
+        if scan.starts_in_code() && line.trim().is_empty() {
+            blanks += 1;
+            if blanks > max {
+                continue;
+            }
+        } else {
+            blanks = 0;
+        }
+        out.push_str(line);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+    use std::process::Command;
+
+    use super::*;
+
+    const PROGRAM: &str = r##"
+struct Words<'a> {
+    text: &'a str,
+}
+
+impl<'a> Words<'a> {
+    fn first(&self) -> &'a str {
+        self.text.split(' ').next().unwrap_or("")
+    }
+}
+
+fn main() {
+    let words = Words { text: r#"a "quoted" b"# };
+    let mut found = 0;
+    'outer: for i in 0..10 {
+        'inner: loop {
+            if i % 2 == 0 {
+                continue 'outer;
+            }
+            if i > 6 {
+                break 'outer;
+            }
+            found += 1;
+            break 'inner;
+        }
+    }
+    let c = ' ';
+    let q = '\'';
+    let b = b'x' as u32;
+    let s = r"raw \n";
+    println!("{} {} {:?} {:?} {} {}", words.first(), found, c, q, b, s);
+}
+"##;
+
+    /// Builds `source` as a program of the 2021 edition, in a directory
+    /// named after `name`, and returns what it prints.
+    fn build_and_run(source: &str, name: &str) -> String {
+        let dir = env::temp_dir().join(format!(
+            "rustsourcebundler-minify-{}-{}",
+            name,
+            std::process::id()
+        ));
+        fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("main.rs");
+        let program = dir.join("main");
+        fs::write(&file, source).unwrap();
+        let rustc = env::var("RUSTC").unwrap_or_else(|_| "rustc".to_string());
+        let output = Command::new(rustc)
+            .args(["--edition", "2021", "-o"])
+            .arg(&program)
+            .arg(&file)
+            .output()
+            .unwrap();
+        assert!(
+            output.status.success(),
+            "{}\n{}",
+            source,
+            String::from_utf8_lossy(&output.stderr)
+        );
+        let output = Command::new(&program).output().unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        String::from_utf8(output.stdout).unwrap()
+    }
+
+    #[test]
+    fn minified_programs_build_and_print_the_same() {
+        let expected = build_and_run(PROGRAM, "0");
+        assert_eq!(expected, "a 3 ' ' '\\'' 120 raw \\n\n");
+        for level in 1..=MAX_LEVEL {
+            let minified = minify(PROGRAM, level);
+            assert_eq!(build_and_run(&minified, &level.to_string()), expected);
+        }
+    }
+
+    #[test]
+    fn spaces_are_kept_before_labels_and_lifetimes() {
+        assert_eq!(
+            minify("break 'outer;\ncontinue 'a;\n", 3),
+            "break 'outer;continue 'a;\n"
+        );
+        assert_eq!(
+            minify("fn f<'a>(x: &'a str) {}\n", 3),
+            "fn f<'a>(x: &'a str){}\n"
+        );
+        assert_eq!(minify("let c = 'x' as u8;\n", 3), "let c= 'x' as u8;\n");
+    }
+
+    #[test]
+    fn levels_remove_more_and_more_whitespace() {
+        let code = "fn f() {\n    let x = 1;\n\n    x\n}\n";
+        assert_eq!(minify(code, 0), code);
+        assert_eq!(minify(code, 1), "fn f() {\nlet x = 1;\n\nx\n}\n");
+        assert_eq!(minify(code, 2), "fn f() {\nlet x = 1;\nx}\n");
+        assert_eq!(minify(code, 3), "fn f(){let x=1;x}\n");
+    }
+
+    #[test]
+    fn literals_and_comments_are_not_squeezed() {
+        let code = "let s = \"a  b\"; // keep  this\nlet t = r#\"c  \"  d\"#;\n";
+        assert_eq!(
+            minify(code, 3),
+            "let s= \"a  b\";// keep  this\nlet t=r#\"c  \"  d\"#;\n"
+        );
+    }
+
+    #[test]
+    fn runs_of_blank_lines_are_collapsed() {
+        assert_eq!(collapse_blank_lines("a\n\n\n\nb\n", 1), "a\n\nb\n");
+        assert_eq!(collapse_blank_lines("a\n\n \n\t\nb\n\n", 0), "a\nb\n");
+        assert_eq!(collapse_blank_lines("a\n\n\nb\n", 2), "a\n\n\nb\n");
+        let literal = "let s = \"x\n\n\n\ny\";\n\n\n";
+        assert_eq!(
+            collapse_blank_lines(literal, 1),
+            "let s = \"x\n\n\n\ny\";\n\n"
+        );
+    }
+
+    #[test]
+    fn indentation_and_trailing_whitespace_are_trimmed_separately() {
+        let code = "fn f() {  \n    let s = \"a  \n    b\";\t\n}\n";
+        assert_eq!(
+            trim(code, true, false),
+            "fn f() {  \nlet s = \"a  \n    b\";\t\n}\n"
+        );
+        assert_eq!(
+            trim(code, false, true),
+            "fn f() {\n    let s = \"a  \n    b\";\n}\n"
+        );
+        assert_eq!(
+            trim(code, true, true),
+            "fn f() {\nlet s = \"a  \n    b\";\n}\n"
+        );
+        assert_eq!(trim(code, false, false), code);
+    }
+
+    #[test]
+    fn dropped_attributes_go_with_their_arguments_and_lines() {
+        let code = "#[inline]\nfn f() {}\n#[inline(always)] #[must_use]\nfn g() -> u32 { v[0] }\n#[doc = \"[x]\"]\nconst S: &str = \"#[inline]\";\n";
+        assert_eq!(
+            drop_attributes(code, &["inline", "doc"]),
+            "fn f() {}\n#[must_use]\nfn g() -> u32 { v[0] }\nconst S: &str = \"#[inline]\";\n"
+        );
+        assert_eq!(drop_attributes(code, &["inlined"]), code);
+    }
+
+    #[test]
+    fn stripped_derives_leave_the_other_traits() {
+        let code = "\
+use serde::{Deserialize, Serialize};
+use std::fmt::Debug;
+#[derive(Debug, serde::Serialize, Clone, Deserialize)]
+pub struct P(u32);
+#[derive(Serialize)]
+pub struct Q;
+const S: &str = \"#[derive(Serialize)]\";
+";
+        assert_eq!(
+            strip_derives(code, &["Serialize", "Deserialize"]),
+            "\
+use std::fmt::Debug;
+#[derive(Debug, Clone)]
+pub struct P(u32);
+pub struct Q;
+const S: &str = \"#[derive(Serialize)]\";
+"
+        );
+        assert_eq!(
+            strip_derives(code, &["Serialize"]).lines().next(),
+            Some("use serde::{Deserialize, Serialize};")
+        );
+    }
+}
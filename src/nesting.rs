@@ -0,0 +1,173 @@
+/*!
+Kind of the blocks a line of a source file is in, to tell a
+`mod <name>;` inside an `impl` or `trait` block, where a module can't
+be, from one at the top of a module or in the body of a function,
+where the wrapper of the module can go in place.
+*/
+
+use std::ops::Range;
+
+use regex::Regex;
+
+/// What a `{` opens, as found by the code before it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Block {
+    Module,
+    Function,
+    Impl,
+    Trait,
+    /// Anything else, like the body of a struct or an `if`, which is
+    /// in the item of the block around it.
+    Other,
+}
+
+/// Keeps the open blocks across the lines of a file.
+#[derive(Debug)]
+pub struct Nesting {
+    blocks: Vec<Block>,
+    /// Code since the last `;`, `{` or `}` outside of parentheses and
+    /// brackets.
+    header: String,
+    /// Depth of the parentheses and brackets of the header.
+    parens: i32,
+    header_re: Regex,
+}
+
+impl Nesting {
+    pub fn new() -> Nesting {
+        Nesting {
+            blocks: Vec::new(),
+            header: String::new(),
+            parens: 0,
+            header_re: Regex::new(r"\b(?P<kind>fn|impl|trait|mod)\b").unwrap(),
+        }
+    }
+
+    /// Goes through the code of `line`, given by its `ranges` as found
+    /// by [`super::lexer::Lexer::scan_code`].
+    pub fn advance(&mut self, line: &str, ranges: &[Range<usize>]) {
+        for range in ranges {
+            for c in line[range.clone()].chars() {
+                match c {
+                    '(' | '[' => self.parens += 1,
+                    ')' | ']' => self.parens -= 1,
+                    ';' if self.parens <= 0 => {
+                        self.header.clear();
+                        continue;
+                    }
+                    '{' => {
+                        let block = self.header_block();
+                        self.blocks.push(block);
+                        self.header.clear();
+                        self.parens = 0;
+                        continue;
+                    }
+                    '}' => {
+                        self.blocks.pop();
+                        self.header.clear();
+                        self.parens = 0;
+                        continue;
+                    }
+                    _ => {}
+                }
+                self.header.push(c);
+            }
+            self.header.push(' ');
+        }
+    }
+
+    /// Kind of the block opened after the current header. The first
+    /// keyword decides, so that `fn f() -> impl Trait {` is a function.
+    fn header_block(&self) -> Block {
+        match self.header_re.captures(&self.header) {
+            Some(cap) => match &cap["kind"] {
+                "fn" => Block::Function,
+                "impl" => Block::Impl,
+                "trait" => Block::Trait,
+                _ => Block::Module,
+            },
+            None => Block::Other,
+        }
+    }
+
+    /// Whether the next line is outside of any block, at the top of
+    /// the file.
+    pub fn at_top(&self) -> bool {
+        self.blocks.is_empty()
+    }
+
+    /// The `impl` or `trait` block the next line is in, if that's the
+    /// innermost item around it, described for messages.
+    pub fn item(&self) -> Option<&'static str> {
+        let block = self
+            .blocks
+            .iter()
+            .rev()
+            .find(|&&block| block != Block::Other)?;
+        match block {
+            Block::Impl => Some("an impl block"),
+            Block::Trait => Some("a trait"),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::lexer::Lexer;
+    use super::*;
+
+    /// The item around each line of `code`, after the lines before it.
+    fn items(code: &str) -> Vec<Option<&'static str>> {
+        let mut lexer = Lexer::new();
+        let mut nesting = Nesting::new();
+        code.lines()
+            .map(|line| {
+                let item = nesting.item();
+                let (_, ranges) = lexer.scan_code(line);
+                nesting.advance(line, &ranges);
+                item
+            })
+            .collect()
+    }
+
+    #[test]
+    fn impl_and_trait_blocks_are_told_apart_from_functions() {
+        let code = "\
+impl<T: Fn() -> u32> Graph<T> {
+    mod a;
+    fn f() -> impl Iterator<Item = u32> {
+        mod b;
+        if x { mod c; }
+    }
+    const S: &str = \"}\";
+    mod d;
+}
+trait Solve where Self: Sized {
+    mod e;
+}
+mod f {
+    mod g;
+}
+";
+        let at = |n: usize| items(code)[n];
+        assert_eq!(at(1), Some("an impl block"));
+        assert_eq!((at(3), at(4)), (None, None));
+        assert_eq!(at(7), Some("an impl block"));
+        assert_eq!(at(10), Some("a trait"));
+        assert_eq!(at(13), None);
+    }
+
+    #[test]
+    fn only_lines_outside_of_blocks_are_at_the_top() {
+        let mut lexer = Lexer::new();
+        let mut nesting = Nesting::new();
+        let mut tops = Vec::new();
+        for line in ["use a;", "fn main() {", "    let s = \"{\";", "}", "mod b;"] {
+            tops.push(nesting.at_top());
+            let (_, ranges) = lexer.scan_code(line);
+            nesting.advance(line, &ranges);
+        }
+        assert_eq!(tops, [true, true, false, false, true]);
+    }
+}
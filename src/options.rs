@@ -0,0 +1,366 @@
+/*!
+The options of a [`super::Bundler`] that are plain data, to keep them
+in a configuration file. With the `serde` feature, they can be read and
+written with any format of serde, like TOML:
+
+```ignore
+let options: BundlerOptions = toml::from_str(&fs::read_to_string("bundle.toml")?)?;
+let bundler = Bundler::from_options(Path::new("src/main.rs"), Path::new("bundle.rs"), &options);
+```
+
+Missing fields take their default value, which is the one of
+[`super::Bundler::new`]. The options given as regexes or functions,
+like [`super::Bundler::forbid`], are not in it.
+*/
+
+use std::borrow::Cow;
+use std::path::Path;
+use std::path::PathBuf;
+
+use super::BuildProfile;
+use super::Bundler;
+use super::DocIncludes;
+use super::Edition;
+use super::FastIoKind;
+use super::IgnoredModules;
+use super::InnerAttributes;
+use super::RustfmtSkip;
+use super::Visibility;
+
+/// Options of a [`super::Bundler`], named after the methods that set
+/// them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(default, deny_unknown_fields)
+)]
+pub struct BundlerOptions {
+    pub crate_name: String,
+    pub crate_aliases: Vec<String>,
+    pub edition: Option<Edition>,
+    pub minify_level: u8,
+    pub minify_auto: bool,
+    pub collapse_blank_lines: Option<usize>,
+    pub emit_minified: Option<String>,
+    /// Modules not bundled, `tests` by default.
+    pub exclude_mod: Vec<String>,
+    pub source_roots: Vec<PathBuf>,
+    pub exclude_item: Vec<String>,
+    pub allow_lint: Vec<String>,
+    pub allow_unused: bool,
+    pub inject_fastio: Option<FastIoKind>,
+    pub strip_comments: bool,
+    pub collect_licenses: bool,
+    pub preserve_prefix_comments: usize,
+    pub trim_trailing_whitespace: bool,
+    pub strip_indentation: bool,
+    pub strict_utf8: bool,
+    pub lenient: bool,
+    pub explicit_globs: bool,
+    pub public_api_only: bool,
+    pub library_only: bool,
+    pub enable_feature: Vec<String>,
+    pub judge_profile: Option<BuildProfile>,
+    pub drop_attribute: Vec<String>,
+    pub strip_derive: Vec<String>,
+    pub ignore_file: Vec<String>,
+    pub ignored_modules: IgnoredModules,
+    pub max_rust_version: Option<u32>,
+    pub check_syntax: bool,
+    pub hoist_macros: bool,
+    pub git_header: bool,
+    pub contest_header: bool,
+    /// Name and value of each field of the header.
+    pub header_field: Vec<(String, String)>,
+    pub table_of_contents: bool,
+    pub integrity_footer: bool,
+    pub source_markers: bool,
+    pub wrapper_visibility: Visibility,
+    pub entry_inner_attributes: InnerAttributes,
+    pub doc_includes: DocIncludes,
+    pub rustfmt_skip: RustfmtSkip,
+    pub size_limit: Option<usize>,
+    pub max_depth: usize,
+    pub max_files: usize,
+    pub max_output_size: usize,
+    pub split_output: Option<usize>,
+    pub allowed_crates: Option<Vec<String>>,
+    /// Name and value of each constant.
+    pub define: Vec<(String, String)>,
+    /// Path of each renamed module, and its new name.
+    pub rename_module: Vec<(String, String)>,
+}
+
+impl Default for BundlerOptions {
+    fn default() -> BundlerOptions {
+        BundlerOptions {
+            crate_name: String::new(),
+            crate_aliases: Vec::new(),
+            edition: None,
+            minify_level: 0,
+            minify_auto: false,
+            collapse_blank_lines: None,
+            emit_minified: None,
+            exclude_mod: vec!["tests".to_string()],
+            source_roots: Vec::new(),
+            exclude_item: Vec::new(),
+            allow_lint: Vec::new(),
+            allow_unused: false,
+            inject_fastio: None,
+            strip_comments: true,
+            collect_licenses: false,
+            preserve_prefix_comments: 0,
+            trim_trailing_whitespace: true,
+            strip_indentation: false,
+            strict_utf8: false,
+            lenient: false,
+            explicit_globs: false,
+            public_api_only: false,
+            library_only: false,
+            enable_feature: Vec::new(),
+            judge_profile: None,
+            drop_attribute: Vec::new(),
+            strip_derive: Vec::new(),
+            ignore_file: Vec::new(),
+            ignored_modules: IgnoredModules::Fail,
+            max_rust_version: None,
+            check_syntax: false,
+            hoist_macros: true,
+            git_header: false,
+            contest_header: false,
+            header_field: Vec::new(),
+            table_of_contents: false,
+            integrity_footer: false,
+            source_markers: false,
+            wrapper_visibility: Visibility::Pub,
+            entry_inner_attributes: InnerAttributes::Keep,
+            doc_includes: DocIncludes::Strip,
+            rustfmt_skip: RustfmtSkip::Off,
+            size_limit: None,
+            max_depth: 64,
+            max_files: 10_000,
+            max_output_size: 64 << 20,
+            split_output: None,
+            allowed_crates: None,
+            define: Vec::new(),
+            rename_module: Vec::new(),
+        }
+    }
+}
+
+impl<'a> Bundler<'a> {
+    /// Bundler with the given `options`, as [`Bundler::new`] followed
+    /// by [`Bundler::set_options`].
+    pub fn from_options(
+        binrs_filename: &'a Path,
+        bundle_filename: &'a Path,
+        options: &'a BundlerOptions,
+    ) -> Bundler<'a> {
+        let mut bundler = Bundler::new(binrs_filename, bundle_filename);
+        bundler.set_options(options);
+        bundler
+    }
+
+    /// Replace all the options of `options`, as if each method had been
+    /// called with them on a new bundler. The others, like the regexes
+    /// of [`Bundler::forbid`] and the callbacks, are kept.
+    pub fn set_options(&mut self, options: &'a BundlerOptions) {
+        fn strs(values: &[String]) -> Vec<&str> {
+            values.iter().map(String::as_str).collect()
+        }
+        fn pairs(values: &[(String, String)]) -> Vec<(&str, &str)> {
+            values
+                .iter()
+                .map(|(a, b)| (a.as_str(), b.as_str()))
+                .collect()
+        }
+        self._crate_name = Cow::Borrowed(&options.crate_name);
+        self.crate_aliases = strs(&options.crate_aliases);
+        self.edition = options.edition;
+        self.minify_level = options.minify_level;
+        self.minify_auto = options.minify_auto;
+        self.max_blank_lines = options.collapse_blank_lines;
+        self.minified_suffix = options.emit_minified.as_deref();
+        self.skip_mod = options.exclude_mod.iter().map(String::as_str).collect();
+        self.source_roots = options.source_roots.iter().map(PathBuf::as_path).collect();
+        self.excluded_items = strs(&options.exclude_item);
+        self.allow_lints = strs(&options.allow_lint);
+        self.allow_unused = options.allow_unused;
+        self.fastio = options.inject_fastio;
+        self.strip_comments = options.strip_comments;
+        self.collect_licenses = options.collect_licenses;
+        self.prefix_comments = options.preserve_prefix_comments;
+        self.trim_trailing_whitespace = options.trim_trailing_whitespace;
+        self.strip_indentation = options.strip_indentation;
+        self.strict_utf8 = options.strict_utf8;
+        self.lenient = options.lenient;
+        self.explicit_globs = options.explicit_globs;
+        self.public_api_only = options.public_api_only;
+        self.library_only = options.library_only;
+        self.features = strs(&options.enable_feature);
+        self.judge_profile = options.judge_profile;
+        self.dropped_attributes = strs(&options.drop_attribute);
+        self.stripped_derives = strs(&options.strip_derive);
+        self.ignored_files = strs(&options.ignore_file);
+        self.ignored_modules = options.ignored_modules;
+        self.max_rust_version = options.max_rust_version;
+        self.check_syntax = options.check_syntax;
+        self.hoist_macros = options.hoist_macros;
+        self.git_header = options.git_header;
+        self.contest_header = options.contest_header;
+        self.header_fields = pairs(&options.header_field);
+        self.table_of_contents = options.table_of_contents;
+        self.integrity_footer = options.integrity_footer;
+        self.source_markers = options.source_markers;
+        self.wrapper_visibility = options.wrapper_visibility;
+        self.entry_inner_attributes = options.entry_inner_attributes;
+        self.doc_includes = options.doc_includes;
+        self.rustfmt_skip = options.rustfmt_skip;
+        self.size_limit = options.size_limit;
+        self.max_depth = options.max_depth;
+        self.max_files = options.max_files;
+        self.max_output_size = options.max_output_size;
+        self.split_limit = options.split_output;
+        self.allowed_crates = options.allowed_crates.as_deref().map(strs);
+        self.defines = pairs(&options.define);
+        self.renamed_modules = pairs(&options.rename_module);
+    }
+
+    /// The options of the bundler, to save them. The modules of
+    /// [`Bundler::exclude_mod`] are sorted.
+    pub fn options(&self) -> BundlerOptions {
+        fn strings(values: &[&str]) -> Vec<String> {
+            values.iter().map(|value| value.to_string()).collect()
+        }
+        fn pairs(values: &[(&str, &str)]) -> Vec<(String, String)> {
+            values
+                .iter()
+                .map(|(a, b)| (a.to_string(), b.to_string()))
+                .collect()
+        }
+        let mut exclude_mod = self
+            .skip_mod
+            .iter()
+            .map(|m| m.to_string())
+            .collect::<Vec<_>>();
+        exclude_mod.sort();
+        BundlerOptions {
+            crate_name: self._crate_name.to_string(),
+            crate_aliases: strings(&self.crate_aliases),
+            edition: self.edition,
+            minify_level: self.minify_level,
+            minify_auto: self.minify_auto,
+            collapse_blank_lines: self.max_blank_lines,
+            emit_minified: self.minified_suffix.map(str::to_string),
+            exclude_mod,
+            source_roots: self.source_roots.iter().map(|p| p.to_path_buf()).collect(),
+            exclude_item: strings(&self.excluded_items),
+            allow_lint: strings(&self.allow_lints),
+            allow_unused: self.allow_unused,
+            inject_fastio: self.fastio,
+            strip_comments: self.strip_comments,
+            collect_licenses: self.collect_licenses,
+            preserve_prefix_comments: self.prefix_comments,
+            trim_trailing_whitespace: self.trim_trailing_whitespace,
+            strip_indentation: self.strip_indentation,
+            strict_utf8: self.strict_utf8,
+            lenient: self.lenient,
+            explicit_globs: self.explicit_globs,
+            public_api_only: self.public_api_only,
+            library_only: self.library_only,
+            enable_feature: strings(&self.features),
+            judge_profile: self.judge_profile,
+            drop_attribute: strings(&self.dropped_attributes),
+            strip_derive: strings(&self.stripped_derives),
+            ignore_file: strings(&self.ignored_files),
+            ignored_modules: self.ignored_modules,
+            max_rust_version: self.max_rust_version,
+            check_syntax: self.check_syntax,
+            hoist_macros: self.hoist_macros,
+            git_header: self.git_header,
+            contest_header: self.contest_header,
+            header_field: pairs(&self.header_fields),
+            table_of_contents: self.table_of_contents,
+            integrity_footer: self.integrity_footer,
+            source_markers: self.source_markers,
+            wrapper_visibility: self.wrapper_visibility,
+            entry_inner_attributes: self.entry_inner_attributes,
+            doc_includes: self.doc_includes,
+            rustfmt_skip: self.rustfmt_skip,
+            size_limit: self.size_limit,
+            max_depth: self.max_depth,
+            max_files: self.max_files,
+            max_output_size: self.max_output_size,
+            split_output: self.split_limit,
+            allowed_crates: self.allowed_crates.as_deref().map(strings),
+            define: pairs(&self.defines),
+            rename_module: pairs(&self.renamed_modules),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn custom() -> BundlerOptions {
+        BundlerOptions {
+            crate_name: "algo".to_string(),
+            edition: Some(Edition::Rust2021),
+            minify_level: 2,
+            exclude_mod: vec!["debug".to_string(), "tests".to_string()],
+            source_roots: vec![PathBuf::from("../common/src")],
+            wrapper_visibility: Visibility::PubCrate,
+            inject_fastio: Some(FastIoKind::Scanner),
+            header_field: vec![("Problem".to_string(), "A".to_string())],
+            allowed_crates: Some(vec!["rand".to_string()]),
+            define: vec![("N".to_string(), "100".to_string())],
+            ..BundlerOptions::default()
+        }
+    }
+
+    #[test]
+    fn the_default_options_are_those_of_a_new_bundler() {
+        let bundler = Bundler::new(Path::new("src/main.rs"), Path::new("bundle.rs"));
+        assert_eq!(bundler.options(), BundlerOptions::default());
+    }
+
+    #[test]
+    fn bundlers_built_from_options_have_them() {
+        let options = custom();
+        let bundler = Bundler::from_options(Path::new("src/main.rs"), Path::new("b.rs"), &options);
+        assert_eq!(bundler.options(), options);
+    }
+
+    #[test]
+    fn the_options_are_the_same_as_with_the_methods() {
+        let options = custom();
+        let mut bundler = Bundler::new(Path::new("src/main.rs"), Path::new("b.rs"));
+        bundler.crate_name("algo");
+        bundler.edition(Edition::Rust2021);
+        bundler.minify_level(2);
+        bundler.exclude_mod("debug");
+        bundler.add_source_root(Path::new("../common/src"));
+        bundler.wrapper_visibility(Visibility::PubCrate);
+        bundler.inject_fastio(FastIoKind::Scanner);
+        bundler.header_field("Problem", "A");
+        bundler.allowed_crates(&["rand"]);
+        bundler.define("N", "100");
+        assert_eq!(bundler.options(), options);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn the_options_round_trip_through_json() {
+        let options = custom();
+        let json = serde_json::to_string(&options).unwrap();
+        let read: BundlerOptions = serde_json::from_str(&json).unwrap();
+        assert_eq!(read, options);
+        let partial: BundlerOptions =
+            serde_json::from_str(r#"{"crate_name": "algo", "edition": "2021"}"#).unwrap();
+        assert_eq!(partial.crate_name, "algo");
+        assert_eq!(partial.exclude_mod, vec!["tests"]);
+        assert!(serde_json::from_str::<BundlerOptions>(r#"{"minify": 1}"#).is_err());
+    }
+}
@@ -0,0 +1,326 @@
+/*!
+What a bundler run does, without the bundle itself.
+*/
+
+use std::fmt;
+use std::path::PathBuf;
+
+use super::json;
+use super::Warning;
+
+/// Why a line of the sources doesn't go to the bundle.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SkipReason {
+    /// Empty line, removed with the comments.
+    Blank,
+    /// Comment-only line.
+    Comment,
+    /// `#![warn(...)]` attribute line.
+    WarnAttribute,
+    /// `extern crate <crate>;` line of the bundled crate.
+    ExternCrate,
+    /// `use <crate>::...;` that is not needed once the library is inlined.
+    RedundantUse,
+    /// `// bundler:...` directive.
+    Directive,
+    /// `mod <name>;` declaration of a module that is not bundled.
+    ExcludedModule,
+    /// `#![no_std]` of the library, as it's the entry file that decides
+    /// if the bundle uses std.
+    NoStd,
+    /// `extern crate` of a crate that is already declared at the root
+    /// of the bundle, like `alloc` in both the entry file and lib.rs.
+    DuplicateExternCrate,
+    /// `#[path = "..."]` of an expanded module.
+    PathAttribute,
+    /// `include!("...")` of a file that is bundled in its place.
+    ExpandedInclude,
+    /// Inner attribute of lib.rs, like `#![allow(...)]`, that is not
+    /// allowed in a bundle of the library only, or of the entry file
+    /// with [`super::InnerAttributes::Strip`].
+    InnerAttribute,
+    /// Line of the entry file between `// bundler:only-local` and
+    /// `// bundler:end`.
+    LocalOnly,
+    /// Inner doc attribute with an `include_str!`, like
+    /// `#![doc = include_str!("../README.md")]`, that is removed or
+    /// inlined, see [`super::DocIncludes`].
+    DocInclude,
+}
+
+impl fmt::Display for SkipReason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            SkipReason::Blank => "blank",
+            SkipReason::Comment => "comment",
+            SkipReason::WarnAttribute => "warn attribute",
+            SkipReason::ExternCrate => "extern crate",
+            SkipReason::RedundantUse => "redundant use",
+            SkipReason::Directive => "directive",
+            SkipReason::ExcludedModule => "excluded module",
+            SkipReason::NoStd => "no_std of the library",
+            SkipReason::DuplicateExternCrate => "duplicate extern crate",
+            SkipReason::PathAttribute => "path attribute",
+            SkipReason::ExpandedInclude => "expanded include",
+            SkipReason::InnerAttribute => "inner attribute",
+            SkipReason::LocalOnly => "local only",
+            SkipReason::DocInclude => "doc include",
+        })
+    }
+}
+
+/// A line that is not going to the bundle.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SkippedLine {
+    pub file: PathBuf,
+    /// Line number, starting at 1.
+    pub line: usize,
+    pub reason: SkipReason,
+}
+
+/// A `mod <name>;` declaration found in the library.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PlannedModule {
+    /// Path of the module in the crate, as in `graph::flow`.
+    pub path: String,
+    /// File with the contents of the module, if it was expanded.
+    pub file: Option<PathBuf>,
+    /// Whether the module goes to the bundle.
+    pub expanded: bool,
+    /// Lines the module adds to the bundle before minification, not
+    /// counting its submodules.
+    pub lines: usize,
+    /// Bytes the module adds to the bundle before minification, not
+    /// counting its submodules.
+    pub bytes: usize,
+    /// Whether the entry file imports or names something in the
+    /// module or in its submodules.
+    pub referenced: bool,
+}
+
+/// Everything a bundler run reads, expands and skips.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BundlePlan {
+    /// Files read, in order.
+    pub files: Vec<PathBuf>,
+    /// Modules expanded or excluded, in order.
+    pub modules: Vec<PlannedModule>,
+    /// Lines that don't go to the bundle.
+    pub skipped_lines: Vec<SkippedLine>,
+    /// Warnings that the run would produce.
+    pub warnings: Vec<Warning>,
+}
+
+impl BundlePlan {
+    /// The plan as a JSON object, for editor integrations.
+    pub fn to_json(&self) -> String {
+        let modules = self.modules.iter().map(|module| {
+            json::object(&[
+                ("path", json::string(&module.path)),
+                (
+                    "file",
+                    module
+                        .file
+                        .as_deref()
+                        .map_or_else(|| "null".to_string(), json::path),
+                ),
+                ("expanded", module.expanded.to_string()),
+                ("lines", module.lines.to_string()),
+                ("bytes", module.bytes.to_string()),
+                ("referenced", module.referenced.to_string()),
+            ])
+        });
+        let skipped_lines = self.skipped_lines.iter().map(|skipped| {
+            json::object(&[
+                ("file", json::path(&skipped.file)),
+                ("line", skipped.line.to_string()),
+                ("reason", json::string(&skipped.reason.to_string())),
+            ])
+        });
+        json::object(&[
+            (
+                "files",
+                json::array(self.files.iter().map(|f| json::path(f))),
+            ),
+            ("modules", json::array(modules)),
+            ("skipped_lines", json::array(skipped_lines)),
+            (
+                "warnings",
+                json::array(self.warnings.iter().map(|w| json::string(&w.to_string()))),
+            ),
+        ])
+    }
+
+    /// Bytes of `module` and of its submodules.
+    fn subtree_bytes(&self, module: &PlannedModule) -> usize {
+        let prefix = format!("{}::", module.path);
+        self.modules
+            .iter()
+            .filter(|m| m.path == module.path || m.path.starts_with(&prefix))
+            .map(|m| m.bytes)
+            .sum()
+    }
+
+    /// The modules as an indented tree, each with its size or why it's
+    /// not in the bundle, to spot the ones that shouldn't be there:
+    ///
+    /// ```text
+    /// graph: 12 lines, 310 bytes, 540 with submodules
+    ///   flow: 8 lines, 230 bytes
+    ///   matching: excluded
+    /// ```
+    pub fn to_tree(&self) -> String {
+        let mut tree = String::new();
+        for module in &self.modules {
+            let depth = module.path.matches("::").count();
+            let name = module.path.rsplit("::").next().unwrap_or(&module.path);
+            tree.push_str(&"  ".repeat(depth));
+            tree.push_str(name);
+            if module.file.is_none() {
+                tree.push_str(": excluded\n");
+                continue;
+            }
+            tree.push_str(&format!(": {} lines, {} bytes", module.lines, module.bytes));
+            let total = self.subtree_bytes(module);
+            if total != module.bytes {
+                tree.push_str(&format!(", {} with submodules", total));
+            }
+            tree.push('\n');
+        }
+        tree
+    }
+
+    /// The modules as a graph in the DOT language of Graphviz, with the
+    /// crate at the root, and the excluded modules dashed.
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph modules {\n");
+        dot.push_str("    \"crate\" [shape=box];\n");
+        for module in &self.modules {
+            let name = module.path.rsplit("::").next().unwrap_or(&module.path);
+            let (label, style) = match module.file {
+                Some(_) => {
+                    let total = self.subtree_bytes(module);
+                    (format!("{}\n{} bytes", name, total), "")
+                }
+                None => (format!("{}\nexcluded", name), ", style=dashed"),
+            };
+            dot.push_str(&format!(
+                "    {} [label={}{}];\n",
+                json::string(&module.path),
+                json::string(&label),
+                style
+            ));
+            let parent = match module.path.rsplit_once("::") {
+                Some((parent, _)) => parent,
+                None => "crate",
+            };
+            dot.push_str(&format!(
+                "    {} -> {};\n",
+                json::string(parent),
+                json::string(&module.path)
+            ));
+        }
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+impl fmt::Display for BundlePlan {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "files:")?;
+        for file in &self.files {
+            writeln!(f, "  {}", file.display())?;
+        }
+        writeln!(f, "modules:")?;
+        for module in &self.modules {
+            match module.file {
+                Some(ref file) => writeln!(
+                    f,
+                    "  {} ({}): {} lines, {} bytes, {}",
+                    module.path,
+                    file.display(),
+                    module.lines,
+                    module.bytes,
+                    if module.referenced {
+                        "referenced"
+                    } else {
+                        "not referenced"
+                    }
+                )?,
+                None => writeln!(f, "  {} (excluded)", module.path)?,
+            }
+        }
+        writeln!(f, "skipped lines:")?;
+        for skipped in &self.skipped_lines {
+            writeln!(
+                f,
+                "  {}:{}: {}",
+                skipped.file.display(),
+                skipped.line,
+                skipped.reason
+            )?;
+        }
+        writeln!(f, "warnings:")?;
+        for warning in &self.warnings {
+            writeln!(f, "  {}", warning)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn plan() -> BundlePlan {
+        let module = |path: &str, file: Option<&str>, lines, bytes| PlannedModule {
+            path: path.to_string(),
+            file: file.map(PathBuf::from),
+            expanded: file.is_some(),
+            lines,
+            bytes,
+            referenced: true,
+        };
+        BundlePlan {
+            modules: vec![
+                module("graph", Some("src/graph.rs"), 12, 310),
+                module("graph::flow", Some("src/graph/flow.rs"), 8, 230),
+                module("graph::matching", None, 0, 0),
+                module("io", Some("src/io.rs"), 3, 40),
+            ],
+            ..BundlePlan::default()
+        }
+    }
+
+    #[test]
+    fn trees_have_the_sizes_of_the_subtrees() {
+        assert_eq!(
+            plan().to_tree(),
+            "\
+graph: 12 lines, 310 bytes, 540 with submodules
+  flow: 8 lines, 230 bytes
+  matching: excluded
+io: 3 lines, 40 bytes
+"
+        );
+    }
+
+    #[test]
+    fn graphs_dash_the_excluded_modules() {
+        let dot = plan().to_dot();
+        assert!(dot.starts_with("digraph modules {\n"), "{}", dot);
+        assert!(
+            dot.contains(
+                "    \"graph\" [label=\"graph\\n540 bytes\"];\n    \"crate\" -> \"graph\";\n"
+            ),
+            "{}",
+            dot
+        );
+        assert!(
+            dot.contains("    \"graph::matching\" [label=\"matching\\nexcluded\", style=dashed];\n    \"graph\" -> \"graph::matching\";\n"),
+            "{}",
+            dot
+        );
+        assert!(dot.ends_with("    \"crate\" -> \"io\";\n}\n"), "{}", dot);
+    }
+}
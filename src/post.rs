@@ -0,0 +1,188 @@
+/*!
+Steps run on the whole text of the bundle once it is created, as
+[`PostProcessor`]s.
+
+The steps run in this order, each on the text of the previous one:
+
+1. [`Minify`], at the level of [`super::Bundler::minify_level`],
+2. [`CollapseBlankLines`], with [`super::Bundler::collapse_blank_lines`],
+3. [`TableOfContents`], with [`super::Bundler::table_of_contents_set`],
+4. the processors given to [`super::Bundler::post_process`], in the
+   order they were given,
+5. [`IntegrityFooter`], with [`super::Bundler::integrity_footer_set`],
+   last so that the checksum is the one of the final text.
+
+The size limits, the forbidden patterns and the syntax check see the
+final text. With [`super::Bundler::minify_auto_set`] or a minified
+copy, the steps run once for each minification level tried.
+*/
+
+use std::io::Write;
+use std::process::Command;
+use std::process::Stdio;
+use std::thread;
+
+use super::integrity;
+use super::minify;
+use super::toc;
+use super::Edition;
+use super::Error;
+use super::Report;
+
+/// A step that turns the text of the bundle into a new one.
+pub trait PostProcessor: Send + Sync {
+    /// Name of the step, for errors and the provenance file.
+    fn name(&self) -> &str;
+
+    /// The new text of `bundle`, or why it could not be made. `report`
+    /// has the warnings and the removed items of the run, and the size
+    /// of the bundle before the steps.
+    fn process(&self, bundle: &str, report: &Report) -> Result<String, String>;
+}
+
+/// Minifies the bundle at `level`, 0 leaving it untouched.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Minify {
+    pub level: u8,
+}
+
+impl PostProcessor for Minify {
+    fn name(&self) -> &str {
+        "minify"
+    }
+
+    fn process(&self, bundle: &str, _: &Report) -> Result<String, String> {
+        Ok(minify::minify(bundle, self.level))
+    }
+}
+
+/// Keeps at most `max` blank lines in a row.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CollapseBlankLines {
+    pub max: usize,
+}
+
+impl PostProcessor for CollapseBlankLines {
+    fn name(&self) -> &str {
+        "collapse_blank_lines"
+    }
+
+    fn process(&self, bundle: &str, _: &Report) -> Result<String, String> {
+        Ok(minify::collapse_blank_lines(bundle, self.max))
+    }
+}
+
+/// Adds the comment with the line where each module starts at the top.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TableOfContents;
+
+impl PostProcessor for TableOfContents {
+    fn name(&self) -> &str {
+        "table_of_contents"
+    }
+
+    fn process(&self, bundle: &str, _: &Report) -> Result<String, String> {
+        Ok(toc::table_of_contents(bundle) + bundle)
+    }
+}
+
+/// Appends the integrity footer, see [`super::verify_bundle`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IntegrityFooter;
+
+impl PostProcessor for IntegrityFooter {
+    fn name(&self) -> &str {
+        "integrity_footer"
+    }
+
+    fn process(&self, bundle: &str, _: &Report) -> Result<String, String> {
+        Ok(integrity::with_footer(bundle.to_string()))
+    }
+}
+
+/// Inserts a `// <name>: <value>` line for each field after the
+/// comment lines at the top of the bundle, like
+/// [`super::Bundler::header_field`] does before the other steps.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Header {
+    pub fields: Vec<(String, String)>,
+}
+
+impl Header {
+    /// The comment lines of the fields.
+    pub fn lines<N: AsRef<str>, V: AsRef<str>>(fields: &[(N, V)]) -> String {
+        fields
+            .iter()
+            .map(|(name, value)| format!("// {}: {}\n", name.as_ref(), value.as_ref()))
+            .collect()
+    }
+}
+
+impl PostProcessor for Header {
+    fn name(&self) -> &str {
+        "header"
+    }
+
+    fn process(&self, bundle: &str, _: &Report) -> Result<String, String> {
+        let position: usize = bundle
+            .split_inclusive('\n')
+            .take_while(|line| line.trim_start().starts_with("//"))
+            .map(str::len)
+            .sum();
+        let mut text = bundle.to_string();
+        text.insert_str(position, &Header::lines(&self.fields));
+        Ok(text)
+    }
+}
+
+/// Formats the bundle with `rustfmt`, which has to be installed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rustfmt {
+    pub edition: Edition,
+}
+
+impl PostProcessor for Rustfmt {
+    fn name(&self) -> &str {
+        "rustfmt"
+    }
+
+    fn process(&self, bundle: &str, _: &Report) -> Result<String, String> {
+        let mut child = Command::new("rustfmt")
+            .args(["--edition", self.edition.year(), "--emit", "stdout"])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| format!("could not run rustfmt: {}", e))?;
+        let mut stdin = child.stdin.take().unwrap();
+        let input = bundle.to_string();
+        let writer = thread::spawn(move || stdin.write_all(input.as_bytes()));
+        let output = child.wait_with_output().map_err(|e| e.to_string())?;
+        writer
+            .join()
+            .unwrap_or(Ok(()))
+            .map_err(|e| format!("could not write to rustfmt: {}", e))?;
+        if !output.status.success() {
+            return Err(String::from_utf8_lossy(&output.stderr).trim().to_string());
+        }
+        String::from_utf8(output.stdout).map_err(|e| e.to_string())
+    }
+}
+
+/// Runs the `steps` in order on `bundle`.
+pub(crate) fn run(
+    bundle: &str,
+    steps: &[&dyn PostProcessor],
+    report: &Report,
+) -> Result<String, Error> {
+    let mut text = bundle.to_string();
+    for step in steps {
+        text = step
+            .process(&text, report)
+            .map_err(|message| Error::PostProcess {
+                name: step.name().to_string(),
+                message,
+            })?;
+    }
+    Ok(text)
+}
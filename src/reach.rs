@@ -0,0 +1,279 @@
+/*!
+Pruning of the private items that nothing public uses, for bundles that
+are submitted as a library.
+
+The bundle is split into the items of each module, as found by the
+delimiters of the lines. Public items, `fn main`, and anything that is
+not a named item are kept, and so are the private items whose name
+appears in what's kept. Names are not resolved, so an item is kept if
+anything with the same name is used anywhere.
+*/
+
+use std::collections::HashMap;
+use std::collections::HashSet;
+
+use regex::Regex;
+
+use super::lexer::Lexer;
+
+/// A run of lines of the bundle.
+#[derive(Debug)]
+pub struct Chunk {
+    pub text: String,
+    pub kind: ChunkKind,
+    /// Path of the module that has the chunk, like `graph::flow`,
+    /// empty at the root.
+    pub module: String,
+    /// Name of the item, public or private, if the chunk is one.
+    pub name: Option<String>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum ChunkKind {
+    /// Always kept.
+    Root,
+    /// Private item with a name.
+    Private(String),
+    /// `impl` block, kept with its type.
+    Impl(String),
+}
+
+/// Identifiers in `text`, literals and comments included.
+pub fn idents(text: &str) -> impl Iterator<Item = &str> {
+    text.split(|c: char| !(c.is_alphanumeric() || c == '_'))
+        .filter(|s| !s.is_empty())
+}
+
+/// Name of the type of an `impl` header, like `X` in
+/// `impl<T> Trait for a::X<T>`.
+fn impl_type(header: &str) -> Option<String> {
+    let header = header.trim_start();
+    let mut rest = header.strip_prefix("unsafe").unwrap_or(header).trim_start();
+    rest = rest.strip_prefix("impl")?;
+    if rest.trim_start().starts_with('<') {
+        let mut depth = 0;
+        for (i, c) in rest.char_indices() {
+            match c {
+                '<' => depth += 1,
+                '>' => depth -= 1,
+                _ => continue,
+            }
+            if depth == 0 {
+                rest = &rest[i + 1..];
+                break;
+            }
+        }
+    }
+    let rest = rest.split(['{', ';']).next()?;
+    let rest = rest.split(" where ").next()?;
+    let self_ty = rest.rsplit(" for ").next()?;
+    let self_ty = self_ty.split('<').next()?;
+    idents(self_ty).last().map(str::to_string)
+}
+
+/// Splits the bundle into chunks, each one either a whole item at the
+/// top of a module, with its attributes, docs and comments, or other
+/// lines.
+pub fn chunks(bundle: &str) -> Vec<Chunk> {
+    let item_re = Regex::new(
+        r"^\s*(?P<vis>pub(\s*\([^)]*\))?\s+)?((const|async|unsafe|extern(\s*\x22[^\x22]*\x22)?)\s+)*(fn|struct|enum|union|trait|type|const|static(\s+mut)?)\s+(?P<name>[A-Za-z_][A-Za-z0-9_]*)",
+    )
+    .unwrap();
+    let mod_re = Regex::new(r"^\s*(pub(\s*\([^)]*\))?\s+)?mod\s+(?P<name>\w+)\s*\{\s*$").unwrap();
+    let impl_re = Regex::new(r"^\s*(unsafe\s+)?impl\b").unwrap();
+    let mut lexer = Lexer::new();
+    let mut chunks = Vec::new();
+    // Depth of the delimiters at the top of the innermost module:
+    let mut modules = vec![0];
+    let mut path: Vec<String> = Vec::new();
+    let mut depth = 0;
+    let mut item = String::new();
+    // Attributes and docs before the item:
+    let mut prefix = String::new();
+    for line in bundle.split_inclusive('\n') {
+        let scan = lexer.scan(line);
+        let code = match scan.comment {
+            Some(comment) => &line[..comment],
+            None => line,
+        };
+        let code = code.trim();
+        let top = *modules.last().unwrap();
+        let at_top = depth == top && item.is_empty() && scan.starts_in_code();
+        depth += scan.delta;
+        let ends = depth == top && scan.ends_in_code();
+        let module = path.join("::");
+        if at_top && depth > top {
+            if let Some(cap) = mod_re.captures(line) {
+                prefix.push_str(line);
+                chunks.push(Chunk {
+                    text: std::mem::take(&mut prefix),
+                    kind: ChunkKind::Root,
+                    module,
+                    name: None,
+                });
+                modules.push(depth);
+                path.push(cap["name"].to_string());
+                continue;
+            }
+        }
+        // Code inserted as it is can close more than it opens; the root
+        // stays open then.
+        if at_top && depth < top && modules.len() > 1 {
+            modules.pop();
+            path.pop();
+            prefix.push_str(line);
+            chunks.push(Chunk {
+                text: std::mem::take(&mut prefix),
+                kind: ChunkKind::Root,
+                module,
+                name: None,
+            });
+            continue;
+        }
+        let text = line.trim();
+        if at_top && text.starts_with("//") {
+            prefix.push_str(line);
+            continue;
+        }
+        if at_top && code.is_empty() {
+            chunks.push(Chunk {
+                text: std::mem::take(&mut prefix) + line,
+                kind: ChunkKind::Root,
+                module,
+                name: None,
+            });
+            continue;
+        }
+        item.push_str(line);
+        if !ends {
+            continue;
+        }
+        if item.trim_start().starts_with("#[") && code.ends_with(']') {
+            prefix.push_str(&std::mem::take(&mut item));
+        } else if item.trim_start().starts_with("#![") && code.ends_with(']') {
+            chunks.push(Chunk {
+                text: std::mem::take(&mut prefix) + &std::mem::take(&mut item),
+                kind: ChunkKind::Root,
+                module,
+                name: None,
+            });
+        } else if code.ends_with('}') || code.ends_with(';') {
+            let name = item_re.captures(&item).map(|cap| cap["name"].to_string());
+            let kind = match item_re.captures(&item) {
+                Some(cap) if cap.name("vis").is_none_or(|v| v.as_str().contains('(')) => {
+                    match &cap["name"] {
+                        "main" => ChunkKind::Root,
+                        name => ChunkKind::Private(name.to_string()),
+                    }
+                }
+                Some(_) => ChunkKind::Root,
+                None if impl_re.is_match(&item) => match impl_type(&item) {
+                    Some(ty) => ChunkKind::Impl(ty),
+                    None => ChunkKind::Root,
+                },
+                None => ChunkKind::Root,
+            };
+            chunks.push(Chunk {
+                text: std::mem::take(&mut prefix) + &std::mem::take(&mut item),
+                kind,
+                module,
+                name,
+            });
+        }
+    }
+    chunks.push(Chunk {
+        text: prefix + &item,
+        kind: ChunkKind::Root,
+        module: path.join("::"),
+        name: None,
+    });
+    chunks
+}
+
+/// Removes the private items of `bundle` that are not used by public
+/// ones. Returns the pruned bundle and the names of the items removed.
+pub fn prune(bundle: &str) -> (String, Vec<String>) {
+    let chunks = chunks(bundle);
+    let mut by_name: HashMap<&str, Vec<usize>> = HashMap::new();
+    for (i, chunk) in chunks.iter().enumerate() {
+        match chunk.kind {
+            ChunkKind::Private(ref name) | ChunkKind::Impl(ref name) => {
+                by_name.entry(name).or_default().push(i)
+            }
+            ChunkKind::Root => {}
+        }
+    }
+    let private: HashSet<&str> = chunks
+        .iter()
+        .filter_map(|chunk| match chunk.kind {
+            ChunkKind::Private(ref name) => Some(name.as_str()),
+            _ => None,
+        })
+        .collect();
+    let mut kept = vec![false; chunks.len()];
+    let mut queue = Vec::new();
+    for (i, chunk) in chunks.iter().enumerate() {
+        let root = match chunk.kind {
+            ChunkKind::Root => true,
+            ChunkKind::Impl(ref ty) => !private.contains(ty.as_str()),
+            ChunkKind::Private(_) => false,
+        };
+        if root {
+            kept[i] = true;
+            queue.push(i);
+        }
+    }
+    let mut seen = HashSet::new();
+    while let Some(i) = queue.pop() {
+        for ident in idents(&chunks[i].text) {
+            if !seen.insert(ident) {
+                continue;
+            }
+            for &j in by_name.get(ident).into_iter().flatten() {
+                if !kept[j] {
+                    kept[j] = true;
+                    queue.push(j);
+                }
+            }
+        }
+    }
+    let mut out = String::with_capacity(bundle.len());
+    let mut removed = Vec::new();
+    for (chunk, kept) in chunks.iter().zip(kept) {
+        if kept {
+            out.push_str(&chunk.text);
+        } else if let ChunkKind::Private(ref name) = chunk.kind {
+            removed.push(name.clone());
+        }
+    }
+    (out, removed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn private_items_nothing_uses_are_pruned() {
+        let bundle = "pub mod graph {\n    fn unused() {}\n    fn helper() {}\n    pub fn bfs() {\n        helper();\n    }\n}\n";
+        let (pruned, removed) = prune(bundle);
+        assert_eq!(removed, ["unused"]);
+        assert!(!pruned.contains("fn unused"), "{}", pruned);
+        assert!(pruned.contains("fn helper"), "{}", pruned);
+    }
+
+    #[test]
+    fn code_closing_more_than_it_opens_keeps_the_root_open() {
+        let bundle = "pub fn a() {}\n}\nfn b() {}\npub mod io {\n    pub fn read() {}\n}\n";
+        let chunks = chunks(bundle);
+        assert!(
+            chunks.iter().all(|chunk| chunk.module.is_empty()),
+            "{:?}",
+            chunks
+        );
+        let text: String = chunks.iter().map(|chunk| &*chunk.text).collect();
+        assert_eq!(text, bundle);
+        let (pruned, _) = prune(bundle);
+        assert!(pruned.contains("pub fn a() {}\n}\n"), "{}", pruned);
+    }
+}
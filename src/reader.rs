@@ -0,0 +1,102 @@
+/*!
+Reading of a bundle without writing it to disk, e.g. to upload it.
+
+The reader doesn't stream the bundle as it's made: the start of a
+bundle is only final at the end of the run. The hoisted macros, the
+inner attributes moved to the top, the headers and the chunks of
+[`super::Bundler::insert_raw`] are inserted before code that was
+already written, and the excluded items, the defines, the renames and
+the minification go over the whole text. So the reader has the whole
+bundle, and an error is returned by [`super::Bundler::reader`] before
+anything is read, never in the middle of a bundle.
+*/
+
+use std::io;
+use std::io::BufRead;
+use std::io::Read;
+
+use super::Report;
+
+/// Bundle created by [`super::Bundler::reader`], that can be read
+/// with [`Read`], or line by line with [`BufRead::lines`].
+///
+/// The bundle is in memory, as hoisting macros, pruning and minifying
+/// need all of it before the first byte is final.
+#[derive(Debug, Clone)]
+pub struct BundleReader {
+    bundle: io::Cursor<Vec<u8>>,
+    report: Report,
+}
+
+impl BundleReader {
+    pub(crate) fn new(bundle: Vec<u8>, report: Report) -> BundleReader {
+        BundleReader {
+            bundle: io::Cursor::new(bundle),
+            report,
+        }
+    }
+
+    /// Report of the run that created the bundle.
+    pub fn report(&self) -> &Report {
+        &self.report
+    }
+
+    /// The whole bundle, regardless of what was already read.
+    pub fn into_inner(self) -> Vec<u8> {
+        self.bundle.into_inner()
+    }
+}
+
+impl Read for BundleReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.bundle.read(buf)
+    }
+}
+
+impl BufRead for BundleReader {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        self.bundle.fill_buf()
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.bundle.consume(amt)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bundle_reader() -> BundleReader {
+        let report = Report {
+            size: 24,
+            ..Report::default()
+        };
+        BundleReader::new(b"mod io {\n}\nfn main() {}\n".to_vec(), report)
+    }
+
+    #[test]
+    fn the_bundle_can_be_read_in_parts_or_by_lines() {
+        let mut reader = bundle_reader();
+        let mut start = [0; 4];
+        reader.read_exact(&mut start).unwrap();
+        assert_eq!(&start, b"mod ");
+        let mut rest = String::new();
+        reader.read_to_string(&mut rest).unwrap();
+        assert_eq!(rest, "io {\n}\nfn main() {}\n");
+        let lines = bundle_reader()
+            .lines()
+            .collect::<io::Result<Vec<_>>>()
+            .unwrap();
+        assert_eq!(lines, ["mod io {", "}", "fn main() {}"]);
+    }
+
+    #[test]
+    fn the_report_and_the_whole_bundle_stay_available() {
+        let mut reader = bundle_reader();
+        let mut line = String::new();
+        reader.read_line(&mut line).unwrap();
+        assert_eq!(reader.report().size, 24);
+        assert_eq!(reader.into_inner(), b"mod io {\n}\nfn main() {}\n");
+    }
+}
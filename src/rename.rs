@@ -0,0 +1,201 @@
+/*!
+Renaming of the modules given to [`super::Bundler::rename_module`], in
+their wrapper and in the paths that refer to them.
+
+Paths are resolved from the module they are in, following `crate::`,
+`self::` and `super::`, and a path that starts with a child module
+refers to it. Only paths with `::` are renamed, besides the wrappers,
+so a local variable or a field with the name of a module is left
+alone, as are paths inside `use` groups.
+*/
+
+use regex::Regex;
+
+use super::lexer::Lexer;
+
+/// Renames the modules at the paths of `renames`, like `io` or
+/// `graph::io`, to their new names in `bundle`. `use` paths start at
+/// the root of the crate with `crate_rooted_use`, as in the 2015
+/// edition. Returns the new bundle and the paths of the modules that
+/// were found.
+pub fn rename(
+    bundle: &str,
+    renames: &[(&str, &str)],
+    crate_rooted_use: bool,
+) -> (String, Vec<String>) {
+    let token_re = Regex::new(
+        r"(?P<mod>\bmod\s+(?P<name>\w+)\s*\{)|(?P<path>\$?\b[A-Za-z_]\w*(\s*::\s*[A-Za-z_]\w*)+)|(?P<delim>[{}()\[\]])",
+    )
+    .unwrap();
+    let segment_re = Regex::new(r"\$?[A-Za-z_]\w*").unwrap();
+    let renamed = |path: &[String]| {
+        let path = path.join("::");
+        renames.iter().position(|&(old, _)| old == path)
+    };
+    let mut found = vec![false; renames.len()];
+    let mut lexer = Lexer::new();
+    let mut out = String::with_capacity(bundle.len());
+    // Modules that are open, with the depth inside of them:
+    let mut open: Vec<(String, usize)> = Vec::new();
+    let mut depth = 0;
+    for line in bundle.split_inclusive('\n') {
+        let (_, ranges) = lexer.scan_code(line);
+        let mut edits = Vec::new();
+        for range in ranges {
+            let code = &line[range.clone()];
+            for cap in token_re.captures_iter(code) {
+                let current: Vec<String> = open.iter().map(|(name, _)| name.clone()).collect();
+                if let Some(name) = cap.name("name") {
+                    let mut path = current;
+                    path.push(name.as_str().to_string());
+                    if let Some(i) = renamed(&path) {
+                        found[i] = true;
+                        let start = range.start + name.start();
+                        edits.push((start..start + name.len(), renames[i].1));
+                    }
+                    depth += 1;
+                    open.push((name.as_str().to_string(), depth));
+                } else if let Some(path) = cap.name("path") {
+                    let before = code[..path.start()].trim_end();
+                    if before.ends_with('.') || before.ends_with("::") {
+                        continue;
+                    }
+                    let crate_rooted = crate_rooted_use && before.ends_with("use");
+                    let segments: Vec<_> = segment_re.find_iter(path.as_str()).collect();
+                    // The module the path starts from, and how many
+                    // segments lead to it:
+                    let mut module = current;
+                    let skip = match segments[0].as_str() {
+                        "crate" | "$crate" => {
+                            module.clear();
+                            1
+                        }
+                        "self" => 1,
+                        "super" => {
+                            let supers = segments
+                                .iter()
+                                .take_while(|s| s.as_str() == "super")
+                                .count();
+                            module.truncate(module.len().saturating_sub(supers));
+                            supers
+                        }
+                        _ => {
+                            if crate_rooted {
+                                module.clear();
+                            }
+                            0
+                        }
+                    };
+                    for segment in &segments[skip..] {
+                        module.push(segment.as_str().to_string());
+                        if let Some(i) = renamed(&module) {
+                            found[i] = true;
+                            let start = range.start + path.start() + segment.start();
+                            edits.push((start..start + segment.len(), renames[i].1));
+                        }
+                    }
+                } else {
+                    match &cap["delim"] {
+                        "{" | "(" | "[" => depth += 1,
+                        _ => {
+                            if open.last().is_some_and(|&(_, d)| d == depth) {
+                                open.pop();
+                            }
+                            depth = depth.saturating_sub(1);
+                        }
+                    }
+                }
+            }
+        }
+        let mut line = line.to_string();
+        for (range, name) in edits.into_iter().rev() {
+            line.replace_range(range, name);
+        }
+        out.push_str(&line);
+    }
+    let found = renames
+        .iter()
+        .zip(found)
+        .filter(|(_, found)| *found)
+        .map(|(&(old, _), _)| old.to_string())
+        .collect();
+    (out, found)
+}
+
+/// `path`, like `io::reader`, with the renamed modules in it.
+pub fn rename_path(path: &str, renames: &[(&str, &str)]) -> String {
+    let mut old = Vec::new();
+    let mut new = Vec::new();
+    for segment in path.split("::") {
+        old.push(segment);
+        let full = old.join("::");
+        match renames.iter().find(|&&(path, _)| path == full) {
+            Some(&(_, name)) => new.push(name),
+            None => new.push(segment),
+        }
+    }
+    new.join("::")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BUNDLE: &str = "\
+pub mod io {
+pub fn read() {}
+}
+pub mod graph {
+pub mod io {
+pub fn load() { super::super::io::read(); }
+}
+pub fn bfs() { io::load(); crate::io::read(); }
+}
+fn main() {
+    let io = 1;
+    \"io::read\";
+    graph::io::load();
+    io::read();
+}
+";
+
+    #[test]
+    fn wrappers_and_paths_are_renamed_from_where_they_are() {
+        let (out, found) = rename(BUNDLE, &[("io", "fastio")], false);
+        assert_eq!(
+            out,
+            BUNDLE
+                .replacen(
+                    "pub mod io {\npub fn read",
+                    "pub mod fastio {\npub fn read",
+                    1
+                )
+                .replace("super::super::io", "super::super::fastio")
+                .replace("crate::io", "crate::fastio")
+                .replace("    io::read();", "    fastio::read();")
+        );
+        assert_eq!(found, ["io"]);
+        let (out, found) = rename(BUNDLE, &[("graph::io", "gio"), ("net", "web")], false);
+        assert_eq!(
+            out,
+            BUNDLE
+                .replace("pub mod io {\npub fn load", "pub mod gio {\npub fn load")
+                .replace("{ io::load", "{ gio::load")
+                .replace("graph::io::load", "graph::gio::load")
+        );
+        assert_eq!(found, ["graph::io"]);
+    }
+
+    #[test]
+    fn use_paths_start_at_the_root_in_the_2015_edition() {
+        let code = "mod io {\n}\nmod a {\nuse io::Reader;\n}\n";
+        let (out, _) = rename(code, &[("io", "fastio")], true);
+        assert_eq!(out, "mod fastio {\n}\nmod a {\nuse fastio::Reader;\n}\n");
+        let (out, _) = rename(code, &[("io", "fastio")], false);
+        assert_eq!(out, "mod fastio {\n}\nmod a {\nuse io::Reader;\n}\n");
+        assert_eq!(
+            rename_path("graph::io::reader", &[("graph::io", "gio")]),
+            "graph::gio::reader"
+        );
+    }
+}
@@ -0,0 +1,203 @@
+/*!
+Reading of source files line by line, dealing with BOMs, CRLF line
+endings and invalid UTF-8.
+*/
+
+use std::fmt;
+use std::fs::File;
+use std::io::BufRead;
+use std::io::BufReader;
+use std::io::Cursor;
+use std::path::Path;
+use std::path::PathBuf;
+
+use super::Error;
+
+const BOM: &str = "\u{FEFF}";
+
+/// A file being read.
+struct OpenFile {
+    path: PathBuf,
+    reader: Box<dyn BufRead>,
+    lineno: usize,
+    /// Line that comes after the file, if it's included.
+    after: Option<String>,
+}
+
+impl OpenFile {
+    fn open(path: &Path) -> Result<OpenFile, Error> {
+        let fd = File::open(path).map_err(|error| Error::Io {
+            path: path.to_path_buf(),
+            error,
+        })?;
+        Ok(OpenFile {
+            path: path.to_path_buf(),
+            reader: Box::new(BufReader::new(fd)),
+            lineno: 0,
+            after: None,
+        })
+    }
+}
+
+impl fmt::Debug for OpenFile {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("OpenFile")
+            .field("path", &self.path)
+            .field("lineno", &self.lineno)
+            .finish()
+    }
+}
+
+/// A source file being read, along with the files it `include!`s.
+#[derive(Debug)]
+pub struct SourceFile {
+    /// The file itself, and then the files included, the last one
+    /// being the one read.
+    files: Vec<OpenFile>,
+    buf: Vec<u8>,
+}
+
+impl SourceFile {
+    pub fn open(path: &Path) -> Result<SourceFile, Error> {
+        Ok(SourceFile {
+            files: vec![OpenFile::open(path)?],
+            buf: Vec::new(),
+        })
+    }
+
+    /// Source that is not read from a file, like an entry file made
+    /// in memory, with `path` standing for it in messages.
+    pub fn from_string(path: &Path, contents: &str) -> SourceFile {
+        let file = OpenFile {
+            path: path.to_path_buf(),
+            reader: Box::new(Cursor::new(contents.as_bytes().to_vec())),
+            lineno: 0,
+            after: None,
+        };
+        SourceFile {
+            files: vec![file],
+            buf: Vec::new(),
+        }
+    }
+
+    fn current(&self) -> &OpenFile {
+        self.files.last().expect("the source file is never closed")
+    }
+
+    /// Path of the file the last line was read from.
+    pub fn path(&self) -> &Path {
+        &self.current().path
+    }
+
+    /// Number of the last line read, starting at 1.
+    pub fn lineno(&self) -> usize {
+        self.current().lineno
+    }
+
+    /// Whether `path` is being read, i.e. including it again would
+    /// be recursive.
+    pub fn is_open(&self, path: &Path) -> bool {
+        self.files.iter().any(|file| file.path == path)
+    }
+
+    /// Continue reading from `path`, and then from the line after the
+    /// current one once it ends, with `after` in between if given, like
+    /// the `}` of an inline module that has the include.
+    pub fn include(&mut self, path: &Path, after: Option<String>) -> Result<(), Error> {
+        let mut file = OpenFile::open(path)?;
+        file.after = after;
+        self.files.push(file);
+        Ok(())
+    }
+
+    /// Read the next line, with its terminator, into `line`. CRLF
+    /// terminators become LF, so that files checked out with either
+    /// give the same bundle, and invalid UTF-8 is replaced with U+FFFD.
+    /// Returns `None` at the end of the file, or whether the line was
+    /// valid UTF-8.
+    pub fn read_line(&mut self, line: &mut String) -> Result<Option<bool>, Error> {
+        self.buf.clear();
+        loop {
+            let file = self
+                .files
+                .last_mut()
+                .expect("the source file is never closed");
+            let len = file
+                .reader
+                .read_until(b'\n', &mut self.buf)
+                .map_err(|error| Error::Io {
+                    path: file.path.clone(),
+                    error,
+                })?;
+            if len > 0 {
+                file.lineno += 1;
+                break;
+            } else if self.files.len() == 1 {
+                return Ok(None);
+            }
+            if let Some(after) = self.files.pop().and_then(|file| file.after) {
+                line.clear();
+                line.push_str(&after);
+                line.push('\n');
+                return Ok(Some(true));
+            }
+        }
+        let (text, valid) = match String::from_utf8_lossy(&self.buf) {
+            std::borrow::Cow::Borrowed(text) => (text.to_string(), true),
+            std::borrow::Cow::Owned(text) => (text, false),
+        };
+        line.clear();
+        if self.lineno() == 1 && text.starts_with(BOM) {
+            line.push_str(&text[BOM.len()..]);
+        } else {
+            line.push_str(&text);
+        }
+        if line.ends_with("\r\n") {
+            line.truncate(line.len() - 2);
+            line.push('\n');
+        } else if line.ends_with('\r') {
+            line.pop();
+        }
+        if self.files.len() > 1 && !line.ends_with('\n') {
+            // The file that has the include! goes on in the next line.
+            line.push('\n');
+        }
+        Ok(Some(valid))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(contents: &str) -> Vec<String> {
+        let mut source = SourceFile::from_string(Path::new("test.rs"), contents);
+        let mut lines = Vec::new();
+        let mut line = String::new();
+        while source.read_line(&mut line).unwrap().is_some() {
+            lines.push(line.clone());
+        }
+        lines
+    }
+
+    #[test]
+    fn crlf_line_endings_become_lf() {
+        assert_eq!(
+            lines("mod io;\r\nfn main() {}\r\n"),
+            ["mod io;\n", "fn main() {}\n"]
+        );
+    }
+
+    #[test]
+    fn mixed_line_endings_become_lf() {
+        assert_eq!(
+            lines("\u{FEFF}mod io;\r\nmod graph;\n// end\r"),
+            ["mod io;\n", "mod graph;\n", "// end"]
+        );
+    }
+
+    #[test]
+    fn lone_carriage_returns_inside_lines_are_kept() {
+        assert_eq!(lines("let s = \"a\rb\";\n"), ["let s = \"a\rb\";\n"]);
+    }
+}
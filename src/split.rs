@@ -0,0 +1,110 @@
+/*!
+Splitting of the bundle into several files, for judges that take many
+files but limit the size of each one.
+
+The biggest modules at the root of the bundle are moved to their own
+files until the bundle fits, and their wrapper becomes a declaration
+with a `#[path]` to the file, like
+`#[path = "bundle.graph.rs"] pub mod graph;`.
+*/
+
+use std::ops::Range;
+
+use regex::Regex;
+
+use super::lexer::Lexer;
+
+/// A module at the root of the bundle.
+struct RootModule {
+    name: String,
+    /// Start of the header, like `pub mod graph {`.
+    header: usize,
+    /// From the `{` to the `}` of the module, both included.
+    body: Range<usize>,
+}
+
+/// The modules at the root of `bundle`.
+fn root_modules(bundle: &str) -> Vec<RootModule> {
+    let mod_re = Regex::new(r"(\bpub(\s*\([^)]*\))?\s+)?\bmod\s+(?P<name>\w+)\s*\{").unwrap();
+    let mut lexer = Lexer::new();
+    let mut modules = Vec::new();
+    let mut depth = 0;
+    // Name, start of the header and of the body of the module being
+    // read:
+    let mut open: Option<(String, usize, usize)> = None;
+    let mut offset = 0;
+    for line in bundle.split_inclusive('\n') {
+        let (_, ranges) = lexer.scan_code(line);
+        for range in ranges {
+            let code = &line[range.clone()];
+            let mut headers = mod_re.captures_iter(code).peekable();
+            for (i, c) in code.char_indices() {
+                let header = headers.peek().map(|cap| cap.get(0).unwrap());
+                if header.is_some_and(|header| i == header.end() - 1) {
+                    let cap = headers.next().unwrap();
+                    if depth == 0 {
+                        let start = offset + range.start;
+                        let header = start + cap.get(0).unwrap().start();
+                        open = Some((cap["name"].to_string(), header, start + i));
+                    }
+                }
+                match c {
+                    '{' | '(' | '[' => depth += 1,
+                    '}' | ')' | ']' => {
+                        depth -= 1;
+                        if depth == 0 {
+                            if let Some((name, header, start)) = open.take() {
+                                let end = offset + range.start + i + 1;
+                                modules.push(RootModule {
+                                    name,
+                                    header,
+                                    body: start..end,
+                                });
+                            }
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+        offset += line.len();
+    }
+    modules
+}
+
+/// Splits `bundle` so that it's at most `limit` bytes, if possible.
+/// Returns the new bundle, and the name and contents of each module
+/// file. `filename` gives the name of the file of a module.
+pub fn split(
+    bundle: &str,
+    limit: usize,
+    filename: impl Fn(&str) -> String,
+) -> (String, Vec<(String, String)>) {
+    let mut modules = root_modules(bundle);
+    modules.sort_by_key(|module| std::cmp::Reverse(module.body.len()));
+    let mut size = bundle.len();
+    let mut moved = Vec::new();
+    for module in modules {
+        if size <= limit {
+            break;
+        }
+        size -= module.body.len();
+        moved.push(module);
+    }
+    moved.sort_by_key(|module| module.body.start);
+    let mut out = String::with_capacity(size);
+    let mut parts = Vec::new();
+    let mut last = 0;
+    for module in moved {
+        let file = filename(&module.name);
+        out.push_str(&bundle[last..module.header]);
+        out.push_str(&format!("#[path = \"{}\"] ", file));
+        out.push_str(bundle[module.header..module.body.start].trim_end());
+        out.push(';');
+        let body = &bundle[module.body.start + 1..module.body.end - 1];
+        parts.push((file, body.trim_start_matches('\n').to_string()));
+        last = module.body.end;
+    }
+    out.push_str(&bundle[last..]);
+    (out, parts)
+}
@@ -0,0 +1,45 @@
+/*!
+Parsing of the bundle with syn, to catch the bundles broken by the
+bundler, like by a swallowed brace, without running rustc. Only with the
+`check-syntax` feature.
+*/
+
+use syn::parse_file;
+
+use super::Error;
+
+/// Parses `bundle`, failing with the first syntax error.
+pub fn check(bundle: &str) -> Result<(), Error> {
+    parse_file(bundle).map(|_| ()).map_err(|error| {
+        let start = error.span().start();
+        Error::InvalidSyntax {
+            message: error.to_string(),
+            line: start.line,
+            column: start.column + 1,
+            code: bundle
+                .lines()
+                .nth(start.line.wrapping_sub(1))
+                .unwrap_or("")
+                .to_string(),
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn errors_point_at_the_line_and_column_of_the_bundle() {
+        assert!(check("mod a {\n    pub fn f() {}\n}\nfn main() {}\n").is_ok());
+        match check("fn main() {}\nfn f() -> {}\n") {
+            Err(Error::InvalidSyntax {
+                line, column, code, ..
+            }) => {
+                assert_eq!((line, column), (2, 11));
+                assert_eq!(code, "fn f() -> {}");
+            }
+            other => panic!("{:?}", other),
+        }
+    }
+}
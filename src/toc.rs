@@ -0,0 +1,94 @@
+/*!
+Table of contents of the bundle, a comment with the line where each
+module starts.
+
+It's made from the final text, after minification, so the lines are
+those of the bundle as written. Modules are found by their header,
+like `mod graph {`, anywhere in the code, as lines can be joined.
+*/
+
+use regex::Regex;
+
+use super::lexer::Lexer;
+
+/// Follows the delimiters of `code`, closing the modules that end.
+fn delimiters(code: &str, depth: &mut usize, open: &mut Vec<(&str, usize)>) {
+    for c in code.chars() {
+        match c {
+            '{' | '(' | '[' => *depth += 1,
+            '}' | ')' | ']' => {
+                if open.last().is_some_and(|&(_, d)| d == *depth) {
+                    open.pop();
+                }
+                *depth = depth.saturating_sub(1);
+            }
+            _ => {}
+        }
+    }
+}
+
+/// The comment listing the modules of `bundle` with their first line,
+/// counting the lines of the comment itself, or an empty string if
+/// there are no modules.
+pub fn table_of_contents(bundle: &str) -> String {
+    let mod_re = Regex::new(r"\bmod\s+(?P<name>\w+)\s*\{").unwrap();
+    let mut lexer = Lexer::new();
+    let mut entries = Vec::new();
+    // Modules that are open, with the depth inside of them:
+    let mut open: Vec<(&str, usize)> = Vec::new();
+    let mut depth = 0;
+    for (i, line) in bundle.lines().enumerate() {
+        let (_, ranges) = lexer.scan_code(line);
+        for range in ranges {
+            let code = &line[range];
+            let mut pos = 0;
+            for cap in mod_re.captures_iter(code) {
+                let header = cap.get(0).unwrap();
+                delimiters(&code[pos..header.end() - 1], &mut depth, &mut open);
+                depth += 1;
+                open.push((cap.name("name").unwrap().as_str(), depth));
+                let path: Vec<&str> = open.iter().map(|&(name, _)| name).collect();
+                entries.push((i, path.join("::")));
+                pos = header.end();
+            }
+            delimiters(&code[pos..], &mut depth, &mut open);
+        }
+    }
+    if entries.is_empty() {
+        return String::new();
+    }
+    let offset = entries.len() + 1;
+    let mut toc = String::from("// Modules:\n");
+    for (i, path) in entries {
+        toc.push_str(&format!("//   {}: line {}\n", path, i + 1 + offset));
+    }
+    toc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lines_count_the_table_itself() {
+        let bundle =
+            "pub mod graph {\npub mod flow {\npub fn f() {}\n}\n}\npub mod io {\n}\nfn main() {}\n";
+        let toc = table_of_contents(bundle);
+        assert_eq!(
+            toc,
+            "// Modules:\n//   graph: line 5\n//   graph::flow: line 6\n//   io: line 10\n"
+        );
+        let with_toc = format!("{}{}", toc, bundle);
+        assert_eq!(with_toc.lines().nth(9), Some("pub mod io {"));
+    }
+
+    #[test]
+    fn modules_are_found_in_joined_lines_but_not_in_literals() {
+        let bundle = "mod a{fn f(){}}mod b{mod c{}}\nconst S: &str = \"mod d {\"; // mod e {\n";
+        assert_eq!(
+            table_of_contents(bundle),
+            "// Modules:\n//   a: line 5\n//   b: line 5\n//   b::c: line 5\n"
+        );
+        assert_eq!(table_of_contents("fn main() {}\n"), "");
+    }
+}
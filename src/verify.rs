@@ -0,0 +1,179 @@
+/*!
+Differential check of a bundle: the original entry file, built with
+cargo, and the bundle, built with rustc, are run with the same inputs,
+and their outputs compared.
+
+Every command is killed if it takes longer than its timeout, so that a
+solution that loops forever on an input can't hang the build.
+*/
+
+use std::fs;
+use std::io::Read;
+use std::path::Path;
+use std::path::PathBuf;
+use std::process::Child;
+use std::process::Command;
+use std::process::ExitStatus;
+use std::process::Stdio;
+use std::thread;
+use std::time::Duration;
+use std::time::Instant;
+
+use super::Edition;
+
+/// One run of a program of the verification, with its output.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VerifyRun {
+    pub input: PathBuf,
+    /// `original` or `bundle`.
+    pub program: &'static str,
+    pub stdout: String,
+    pub stderr: String,
+    /// Whether it was killed after the run timeout of
+    /// [`super::Bundler::verify_timeouts`].
+    pub timed_out: bool,
+}
+
+/// Output of a command, without an exit status if it timed out.
+struct Captured {
+    status: Option<ExitStatus>,
+    stdout: Vec<u8>,
+    stderr: Vec<u8>,
+}
+
+/// Reads `pipe` to the end in another thread.
+fn drain<R: Read + Send + 'static>(pipe: Option<R>) -> thread::JoinHandle<Vec<u8>> {
+    thread::spawn(move || {
+        let mut buffer = Vec::new();
+        if let Some(mut pipe) = pipe {
+            let _ = pipe.read_to_end(&mut buffer);
+        }
+        buffer
+    })
+}
+
+/// Waits for `child`, killing it after `timeout`.
+fn wait(mut child: Child, timeout: Duration) -> Result<Captured, String> {
+    let stdout = drain(child.stdout.take());
+    let stderr = drain(child.stderr.take());
+    let deadline = Instant::now() + timeout;
+    let status = loop {
+        match child.try_wait().map_err(|e| e.to_string())? {
+            Some(status) => break Some(status),
+            None if Instant::now() >= deadline => {
+                let _ = child.kill();
+                let _ = child.wait();
+                break None;
+            }
+            None => thread::sleep(Duration::from_millis(10)),
+        }
+    };
+    Ok(Captured {
+        status,
+        stdout: stdout.join().unwrap_or_default(),
+        stderr: stderr.join().unwrap_or_default(),
+    })
+}
+
+/// Runs `command`, returning an error with its output if it fails or
+/// takes longer than `timeout`.
+fn build(command: &mut Command, timeout: Duration) -> Result<(), String> {
+    let program = command.get_program().to_string_lossy().into_owned();
+    let child = command
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("could not run {}: {}", program, e))?;
+    let output = wait(child, timeout)?;
+    match output.status {
+        None => Err(format!("{} timed out after {:?}", program, timeout)),
+        Some(status) if !status.success() => {
+            Err(String::from_utf8_lossy(&output.stderr).trim().to_string())
+        }
+        Some(_) => Ok(()),
+    }
+}
+
+/// Runs `program` with the contents of `input` as its stdin, for at
+/// most `timeout`.
+fn run(program: &Path, input: &Path, timeout: Duration) -> Result<Captured, String> {
+    let stdin = fs::File::open(input).map_err(|e| format!("{}: {}", input.display(), e))?;
+    let child = Command::new(program)
+        .stdin(stdin)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("could not run {}: {}", program.display(), e))?;
+    wait(child, timeout)
+}
+
+/// What is compared.
+pub struct Verification<'v> {
+    /// Cargo.toml of the original crate.
+    pub manifest: &'v Path,
+    /// Name of the bin target of the entry file.
+    pub bin_name: &'v str,
+    pub bundle: &'v Path,
+    pub edition: Edition,
+    /// Where the programs are built.
+    pub work_dir: &'v Path,
+    pub build_timeout: Duration,
+    pub run_timeout: Duration,
+}
+
+impl Verification<'_> {
+    /// Builds both programs and runs them with each input, returning
+    /// the inputs for which the outputs differ. The runs are added to
+    /// `runs`, and the inputs where one timed out are not compared.
+    pub fn mismatches(
+        &self,
+        inputs: &[&Path],
+        runs: &mut Vec<VerifyRun>,
+    ) -> Result<Vec<PathBuf>, String> {
+        let target_dir = self.work_dir.join("target");
+        build(
+            Command::new("cargo")
+                .args(["build", "--release", "--quiet", "--bin", self.bin_name])
+                .arg("--manifest-path")
+                .arg(self.manifest)
+                .arg("--target-dir")
+                .arg(&target_dir),
+            self.build_timeout,
+        )?;
+        let original = target_dir.join("release").join(format!(
+            "{}{}",
+            self.bin_name,
+            std::env::consts::EXE_SUFFIX
+        ));
+        let bundled = self
+            .work_dir
+            .join(format!("bundle{}", std::env::consts::EXE_SUFFIX));
+        build(
+            Command::new("rustc")
+                .args(["-O", "--edition", self.edition.year(), "-o"])
+                .arg(&bundled)
+                .arg(self.bundle),
+            self.build_timeout,
+        )?;
+        let mut mismatches = Vec::new();
+        for &input in inputs {
+            let mut outputs = Vec::new();
+            for (program, path) in [("original", &original), ("bundle", &bundled)] {
+                let output = run(path, input, self.run_timeout)?;
+                runs.push(VerifyRun {
+                    input: input.to_path_buf(),
+                    program,
+                    stdout: String::from_utf8_lossy(&output.stdout).into_owned(),
+                    stderr: String::from_utf8_lossy(&output.stderr).into_owned(),
+                    timed_out: output.status.is_none(),
+                });
+                outputs.push(output);
+            }
+            let finished = outputs.iter().all(|output| output.status.is_some());
+            if finished && outputs[0].stdout != outputs[1].stdout {
+                mismatches.push(input.to_path_buf());
+            }
+        }
+        Ok(mismatches)
+    }
+}